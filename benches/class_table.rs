@@ -0,0 +1,177 @@
+//! Class table construction, subtype checks, field lookups and method
+//! dispatch on a synthetic hierarchy of 10k classes, and on a chain of
+//! classes each extending the previous one.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use featherweight_java::ast::{ClassName, FieldName, MethodName};
+use featherweight_java::class_table::ClassTable;
+use featherweight_java::{build_class_table, parse, FileId, TypecheckConfig};
+
+const CLASSES: usize = 10_000;
+const INTERFACES: usize = 10;
+const METHODS: usize = 4;
+const CHAIN: usize = 1_000;
+
+/// A binary tree of classes below `Object`, so the deepest classes have 13
+/// superclasses. Every class adds a field and overrides one of [`METHODS`]
+/// methods, and every seventh class implements an interface, each extending
+/// the previous one.
+fn source() -> String {
+    let mut source = String::new();
+    for i in 0..INTERFACES {
+        let extends = match i {
+            0 => String::new(),
+            _ => format!(" extends I{}", i - 1),
+        };
+        source += &format!("interface I{i}{extends} {{ }}\n");
+    }
+    for i in 0..CLASSES {
+        let super_class = match i {
+            0 => "Object".to_string(),
+            _ => format!("C{}", (i - 1) / 2),
+        };
+        let implements = match i % 7 {
+            0 => format!(" implements I{}", i % INTERFACES),
+            _ => String::new(),
+        };
+        let (super_args, super_params): (Vec<_>, Vec<_>) = ancestors(i)
+            .map(|j| (format!("f{j}"), format!("Object f{j}, ")))
+            .unzip();
+        source += &format!(
+            "class C{i} extends {super_class}{implements} {{\n\
+             \x20   Object f{i};\n\
+             \x20   C{i}({}Object f{i}) {{ super({}); this.f{i} = f{i}; }}\n\
+             \x20   Object m{}() {{ return this.f{i}; }}\n\
+             }}\n",
+            super_params.concat(),
+            super_args.join(", "),
+            i % METHODS,
+        );
+    }
+    source
+}
+
+/// A chain of [`CHAIN`] classes below `Object`, where every class adds a
+/// field and overrides one of [`METHODS`] methods.
+fn chain_source() -> String {
+    let mut source = String::new();
+    for i in 0..CHAIN {
+        let super_class = match i {
+            0 => "Object".to_string(),
+            _ => format!("C{}", i - 1),
+        };
+        let (super_args, super_params): (Vec<_>, Vec<_>) = (0..i)
+            .map(|j| (format!("f{j}"), format!("Object f{j}, ")))
+            .unzip();
+        source += &format!(
+            "class C{i} extends {super_class} {{\n\
+             \x20   Object f{i};\n\
+             \x20   C{i}({}Object f{i}) {{ super({}); this.f{i} = f{i}; }}\n\
+             \x20   Object m{}() {{ return this.f{i}; }}\n\
+             }}\n",
+            super_params.concat(),
+            super_args.join(", "),
+            i % METHODS,
+        );
+    }
+    source
+}
+
+/// The superclasses of `C{i}` below `Object`, root first.
+fn ancestors(i: usize) -> impl Iterator<Item = usize> {
+    let mut ancestors = Vec::new();
+    let mut j = i;
+    while j > 0 {
+        j = (j - 1) / 2;
+        ancestors.push(j);
+    }
+    ancestors.into_iter().rev()
+}
+
+fn class(i: usize) -> ClassName {
+    ClassName(format!("C{i}"))
+}
+
+fn class_table(c: &mut Criterion) {
+    let ast = parse(FileId(0), &source()).expect("the generated source parses");
+    let ct = ClassTable::try_from_ast(ast.clone()).expect("the generated classes are well-formed");
+
+    // the last classes are leaves, 13 levels below `C0`
+    let leaves: Vec<_> = (CLASSES - 100..CLASSES).map(class).collect();
+    let targets = [
+        class(0),
+        class(1),
+        class(2),
+        class(CLASSES / 2),
+        ClassName::object(),
+        ClassName("I0".into()),
+        ClassName(format!("I{}", INTERFACES - 1)),
+    ];
+
+    c.bench_function("construct", |b| {
+        b.iter(|| ClassTable::try_from_ast(black_box(ast.clone())))
+    });
+    c.bench_function("is_subtype", |b| {
+        b.iter(|| {
+            for leaf in &leaves {
+                for target in &targets {
+                    black_box(ct.is_subtype(leaf, target));
+                }
+            }
+        })
+    });
+    c.bench_function("subtypes", |b| {
+        b.iter(|| ct.subtypes(black_box(&class(1))).unwrap().count())
+    });
+    let methods: Vec<_> = (0..METHODS).map(|i| MethodName(format!("m{i}"))).collect();
+    c.bench_function("method_definition", |b| {
+        b.iter(|| {
+            for leaf in &leaves {
+                for method in &methods {
+                    black_box(ct.method_definition(method, leaf));
+                }
+            }
+        })
+    });
+    let fields = [FieldName("f0".into()), FieldName("f1".into())];
+    c.bench_function("field_index", |b| {
+        b.iter(|| {
+            for leaf in &leaves {
+                for field in &fields {
+                    black_box(ct.field_index(leaf, field));
+                }
+            }
+        })
+    });
+}
+
+fn chain(c: &mut Criterion) {
+    let ast = parse(FileId(0), &chain_source()).expect("the generated source parses");
+    let ct = ClassTable::try_from_ast(ast.clone()).expect("the generated classes are well-formed");
+    let (first, last) = (class(0), class(CHAIN - 1));
+
+    c.bench_function("chain/construct", |b| {
+        b.iter(|| ClassTable::try_from_ast(black_box(ast.clone())))
+    });
+    c.bench_function("chain/typecheck", |b| {
+        b.iter(|| build_class_table(black_box(&ast), &TypecheckConfig::default()))
+    });
+    c.bench_function("chain/is_subtype", |b| {
+        b.iter(|| ct.is_subtype(black_box(&last), &first))
+    });
+    let methods: Vec<_> = (0..METHODS).map(|i| MethodName(format!("m{i}"))).collect();
+    c.bench_function("chain/method_definition", |b| {
+        b.iter(|| {
+            for method in &methods {
+                black_box(ct.method_definition(method, black_box(&last)));
+            }
+        })
+    });
+    let field = FieldName("f0".into());
+    c.bench_function("chain/field_index", |b| {
+        b.iter(|| ct.field_index(black_box(&last), &field))
+    });
+}
+
+criterion_group!(benches, class_table, chain);
+criterion_main!(benches);
@@ -0,0 +1,657 @@
+//! Queries about a file being edited, answered by the language server of
+//! `fj lsp`.
+//!
+//! An [`Analysis`] loads a script, or a term (`.fje`) together with the
+//! libraries it is checked against, and collects all errors and warnings as
+//! diagnostics. A method or field name after `.` refers to its closest
+//! declaration in the static type of the receiver, as for the typechecker.
+//! Types carry no spans, so class names are found by scanning the tokens of
+//! all loaded files instead.
+
+use crate::ast::{
+    Ast, ClassName, ClassType, FieldAccess, FieldAssign, FieldName, MethodName, Term, Type,
+};
+use crate::class_table::ClassTable;
+use crate::diagnostics;
+use crate::lexer::{text, tokenize, Token, TokenKind};
+use crate::loader::{self, Loader};
+use crate::parser;
+use crate::source::{FileId, SourceMap, Span};
+use crate::typecheck::{
+    fields_of, method_type_of, typecheck_ast_all, typecheck_term, Gamma, TypecheckConfig,
+};
+use anyhow::Context;
+use codespan_reporting::diagnostic::Diagnostic;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// A class, or a method or field together with the class or interface
+/// declaring it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Symbol {
+    Class(ClassName),
+    Method(ClassName, MethodName),
+    Field(ClassName, FieldName),
+}
+
+/// A name in the source declaring or referring to `symbol`.
+#[derive(Debug, Clone)]
+struct Occurrence {
+    span: Span,
+    symbol: Symbol,
+    is_declaration: bool,
+}
+
+/// A field or method of the receiver of a `.`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub name: String,
+    /// The type of the field or method.
+    pub detail: String,
+    pub kind: CompletionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Field,
+    Method,
+}
+
+pub struct Analysis {
+    path: PathBuf,
+    libraries: Vec<PathBuf>,
+    config: TypecheckConfig,
+    sources: SourceMap,
+    file: FileId,
+    /// The definitions of the file and of all files loaded with it.
+    ast: Ast,
+    /// The `eval` blocks of a script, or the term of a `.fje` file.
+    eval_terms: Vec<Term>,
+    /// `None` if the classes are not well-formed, so no names are resolved.
+    ct: Option<ClassTable>,
+    diagnostics: Vec<Diagnostic<FileId>>,
+    tokens: BTreeMap<FileId, Vec<Token>>,
+    occurrences: Vec<Occurrence>,
+}
+
+impl Analysis {
+    /// Analyze `text`, the contents of the file `path`. A `.fje` file is a
+    /// term checked against `libraries`, any other file a script.
+    pub fn new(path: &Path, text: String, libraries: &[PathBuf], config: TypecheckConfig) -> Self {
+        let mut sources = SourceMap::new();
+        let mut errors = Vec::new();
+        let is_term = path.extension().is_some_and(|ext| ext == "fje");
+        let (file, ast, eval_terms) = if is_term {
+            let ast = loader::load_libraries(&mut sources, libraries).unwrap_or_else(|error| {
+                errors.push(error);
+                Ast::default()
+            });
+            let file = sources.add(path.display().to_string(), text);
+            let term =
+                parser::parse_eval_input(file, sources.source(file)).context("parsing failed");
+            let eval_terms = term.map_or_else(
+                |error| {
+                    errors.push(error);
+                    vec![]
+                },
+                |term| vec![term],
+            );
+            (file, ast, eval_terms)
+        } else {
+            let file = sources.add(path.display().to_string(), text);
+            match parser::parse_script(file, sources.source(file)).context("parsing failed") {
+                Ok(script) => {
+                    let mut loader = Loader::new(&mut sources);
+                    if let Err(error) = loader.load_ast(path, script.ast) {
+                        errors.push(error);
+                    }
+                    (file, loader.finish(), script.eval_terms)
+                }
+                Err(error) => {
+                    errors.push(error);
+                    (file, Ast::default(), vec![])
+                }
+            }
+        };
+        let mut diagnostics: Vec<_> = errors.iter().map(diagnostics::from_error).collect();
+
+        let ct = match ClassTable::try_from_ast_all(ast.clone()) {
+            Ok(ct) => Some(ct),
+            Err(errors) => {
+                diagnostics.extend(errors.into_iter().map(|error| {
+                    diagnostics::from_error(
+                        &anyhow::Error::from(error).context("could not build class table"),
+                    )
+                }));
+                None
+            }
+        };
+        if let Some(ct) = &ct {
+            let report = typecheck_ast_all(ct, &config, &ast);
+            diagnostics.extend(report.errors.iter().map(diagnostics::from_error));
+            diagnostics.extend(report.warnings.iter().map(diagnostics::from_warning));
+            for (i, term) in eval_terms.iter().enumerate() {
+                match typecheck_term(ct, &config, &Gamma::empty(), term).with_context(|| {
+                    if is_term {
+                        "Typechecking for input term failed".to_string()
+                    } else {
+                        format!("Typechecking for eval block #{} failed", i)
+                    }
+                }) {
+                    Ok(term_type) => {
+                        diagnostics.extend(term_type.warnings.iter().map(diagnostics::from_warning))
+                    }
+                    Err(error) => diagnostics.push(diagnostics::from_error(&error)),
+                }
+            }
+        }
+
+        let files: BTreeSet<_> = std::iter::once(file)
+            .chain(ast.class_definitions.iter().map(|class| class.span.file))
+            .chain(
+                ast.interface_definitions
+                    .iter()
+                    .map(|interface| interface.span.file),
+            )
+            .collect();
+        let tokens = files
+            .into_iter()
+            .map(|file| {
+                let tokens = tokenize(sources.source(file))
+                    .into_iter()
+                    .filter(|token| token.kind != TokenKind::Comment)
+                    .collect();
+                (file, tokens)
+            })
+            .collect();
+        let mut analysis = Analysis {
+            path: path.to_path_buf(),
+            libraries: libraries.to_vec(),
+            config,
+            sources,
+            file,
+            ast,
+            eval_terms,
+            ct,
+            diagnostics,
+            tokens,
+            occurrences: vec![],
+        };
+        if let Some(ct) = &analysis.ct {
+            analysis.occurrences = analysis.index(ct);
+        }
+        analysis
+    }
+
+    /// All files loaded, to locate spans.
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    /// The analyzed file.
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    /// The errors and warnings of the analyzed file and all files loaded
+    /// with it.
+    pub fn diagnostics(&self) -> &[Diagnostic<FileId>] {
+        &self.diagnostics
+    }
+
+    /// The innermost term at `offset` of the analyzed file that has a type,
+    /// with that type.
+    pub fn hover(&self, offset: usize) -> Option<(Span, Type)> {
+        let ct = self.ct.as_ref()?;
+        let contains = |span: Span| span.file == self.file && span.range().contains(&offset);
+        let mut terms = Vec::new();
+        for (root, gamma) in self.roots() {
+            visit(root, &gamma, &mut |term, gamma| {
+                if contains(term.span()) {
+                    terms.push((term, gamma.clone()));
+                }
+            });
+        }
+        terms.sort_by_key(|(term, _)| term.span().range().len());
+        terms.into_iter().find_map(|(term, gamma)| {
+            let term_type = typecheck_term(ct, &self.config, &gamma, term).ok()?;
+            Some((term.span(), term_type.ty))
+        })
+    }
+
+    /// The declaration of the class, method or field named at `offset`.
+    pub fn definition(&self, offset: usize) -> Option<Span> {
+        let symbol = self.symbol_at(offset)?;
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.is_declaration && occurrence.symbol == symbol)
+            .map(|occurrence| occurrence.span)
+    }
+
+    /// All names in the loaded files referring to the class, method or field
+    /// named at `offset`, and optionally its declaration.
+    pub fn references(&self, offset: usize, include_declaration: bool) -> Vec<Span> {
+        let Some(symbol) = self.symbol_at(offset) else {
+            return vec![];
+        };
+        self.occurrences
+            .iter()
+            .filter(|occurrence| {
+                occurrence.symbol == symbol && (include_declaration || !occurrence.is_declaration)
+            })
+            .map(|occurrence| occurrence.span)
+            .collect()
+    }
+
+    /// The fields and methods of the receiver of the `.` before `offset`,
+    /// which may be followed by the beginning of a name.
+    pub fn completions(&self, offset: usize) -> Vec<Completion> {
+        let source = self.sources.source(self.file);
+        let Some(before) = source.get(..offset) else {
+            return vec![];
+        };
+        let name_start = before
+            .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+            .len();
+        let Some(dot) = before[..name_start]
+            .trim_end()
+            .strip_suffix('.')
+            .map(str::len)
+        else {
+            return vec![];
+        };
+        // the file does not parse with a trailing `.`, so the receiver is
+        // typed in a copy without the `.` and the partial name
+        let mut text = source.to_string();
+        text.replace_range(dot..offset, &" ".repeat(offset - dot));
+        let analysis = Analysis::new(&self.path, text, &self.libraries, self.config.clone());
+        analysis.members_before(dot).unwrap_or_default()
+    }
+
+    /// The fields and methods of the term ending before `dot`.
+    fn members_before(&self, dot: usize) -> Option<Vec<Completion>> {
+        let ct = self.ct.as_ref()?;
+        let source = self.sources.source(self.file);
+        let end = source[..dot].trim_end().len();
+        // the span of a parenthesized term does not include the parentheses
+        let inner_end = source[..end]
+            .trim_end_matches(|c: char| c == ')' || c.is_whitespace())
+            .len();
+        let mut receiver: Option<(&Term, Gamma)> = None;
+        let mut parenthesized: Option<(&Term, Gamma)> = None;
+        for (root, gamma) in self.roots() {
+            visit(root, &gamma, &mut |term, gamma| {
+                let span = term.span();
+                let len = |other: &Option<(&Term, Gamma)>| {
+                    other.as_ref().map(|(other, _)| other.span().range().len())
+                };
+                if span.file != self.file {
+                    return;
+                }
+                // `a + b.` completes `b`, but `(a + b).` completes `a + b`
+                if span.end == end && len(&receiver).is_none_or(|len| span.range().len() < len) {
+                    receiver = Some((term, gamma.clone()));
+                } else if span.end == inner_end
+                    && inner_end < end
+                    && len(&parenthesized).is_none_or(|len| span.range().len() > len)
+                {
+                    parenthesized = Some((term, gamma.clone()));
+                }
+            });
+        }
+        let (term, gamma) = receiver.or(parenthesized)?;
+        let class_type = self.receiver_type(ct, &gamma, term)?;
+        Some(members(ct, &class_type))
+    }
+
+    /// The bodies of all methods and the `eval` blocks, with the variables in
+    /// scope.
+    fn roots(&self) -> Vec<(&Term, Gamma)> {
+        let method_bodies = self.ast.class_definitions.iter().flat_map(|class| {
+            class.methods.iter().map(move |method| {
+                (
+                    &*method.return_term,
+                    Gamma::from_class_method(class, method),
+                )
+            })
+        });
+        method_bodies
+            .chain(self.eval_terms.iter().map(|term| (term, Gamma::empty())))
+            .collect()
+    }
+
+    fn symbol_at(&self, offset: usize) -> Option<Symbol> {
+        self.occurrences
+            .iter()
+            .find(|occurrence| {
+                let span = occurrence.span;
+                span.file == self.file && span.start <= offset && offset <= span.end
+            })
+            .map(|occurrence| occurrence.symbol.clone())
+    }
+
+    /// The class type bounding the static type of `term`.
+    fn receiver_type(&self, ct: &ClassTable, gamma: &Gamma, term: &Term) -> Option<ClassType> {
+        let term_type = typecheck_term(ct, &self.config, gamma, term).ok()?;
+        gamma.delta().bound(&term_type.ty)
+    }
+
+    /// The span of the name after `object_term.`.
+    fn member_span(&self, object_term: &Term) -> Option<Span> {
+        let span = object_term.span();
+        let source = self.sources.source(span.file);
+        let tokens = self.tokens.get(&span.file)?;
+        let mut rest = tokens[tokens.partition_point(|token| token.start < span.end)..]
+            .iter()
+            .skip_while(|token| text(source, token) == ")");
+        match (rest.next(), rest.next()) {
+            (Some(dot), Some(name))
+                if text(source, dot) == "." && name.kind == TokenKind::Ident =>
+            {
+                Some(Span::new(span.file, name.start, name.end))
+            }
+            _ => None,
+        }
+    }
+
+    /// Find every name of a class, method or field in the loaded files.
+    fn index(&self, ct: &ClassTable) -> Vec<Occurrence> {
+        let mut occurrences = Vec::new();
+        let class_declarations: BTreeSet<_> = self
+            .ast
+            .class_definitions
+            .iter()
+            .map(|class| class.name_span)
+            .chain(
+                self.ast
+                    .interface_definitions
+                    .iter()
+                    .map(|interface| interface.name_span),
+            )
+            .collect();
+        for (&file, tokens) in &self.tokens {
+            let source = self.sources.source(file);
+            for (i, token) in tokens.iter().enumerate() {
+                let is_member = i > 0 && text(source, &tokens[i - 1]) == ".";
+                let name = ClassName(text(source, token).into());
+                if token.kind == TokenKind::Ident
+                    && !is_member
+                    && (ct.contains_class(&name) || ct.is_interface(&name))
+                {
+                    let span = Span::new(file, token.start, token.end);
+                    occurrences.push(Occurrence {
+                        span,
+                        symbol: Symbol::Class(name),
+                        is_declaration: class_declarations.contains(&span),
+                    });
+                }
+            }
+        }
+
+        for interface in &self.ast.interface_definitions {
+            occurrences.extend(interface.methods.iter().map(|method| Occurrence {
+                span: method.name_span,
+                symbol: Symbol::Method(interface.name.clone(), method.method_name.clone()),
+                is_declaration: true,
+            }));
+        }
+        for class in &self.ast.class_definitions {
+            let file = class.span.file;
+            let source = self.sources.source(file);
+            // NOTE(unwrap): the files of all classes are tokenized
+            let tokens = &self.tokens[&file];
+            let tokens_in = |start: usize, end: usize| {
+                let first = tokens.partition_point(|token| token.start < start);
+                let last = tokens.partition_point(|token| token.start < end);
+                &tokens[first..last]
+            };
+            // fields are declared as `T f;` before the constructor
+            for window in tokens_in(class.span.start, class.constructor.span.start).windows(2) {
+                let name = FieldName(text(source, &window[0]).into());
+                if window[0].kind == TokenKind::Ident
+                    && text(source, &window[1]) == ";"
+                    && class.fields.iter().any(|(_, field)| field == &name)
+                {
+                    occurrences.push(Occurrence {
+                        span: Span::new(file, window[0].start, window[0].end),
+                        symbol: Symbol::Field(class.name.clone(), name),
+                        is_declaration: true,
+                    });
+                }
+            }
+            // the constructor assigns its own fields with `this.f = f;`
+            let constructor = tokens_in(class.constructor.span.start, class.constructor.span.end);
+            for window in constructor.windows(3) {
+                if text(source, &window[0]) == "this" && text(source, &window[1]) == "." {
+                    occurrences.push(Occurrence {
+                        span: Span::new(file, window[2].start, window[2].end),
+                        symbol: Symbol::Field(
+                            class.name.clone(),
+                            FieldName(text(source, &window[2]).into()),
+                        ),
+                        is_declaration: false,
+                    });
+                }
+            }
+            let methods = class
+                .methods
+                .iter()
+                .map(|method| (&method.method_name, method.name_span));
+            let abstract_methods = class
+                .abstract_methods
+                .iter()
+                .map(|method| (&method.method_name, method.name_span));
+            occurrences.extend(
+                methods
+                    .chain(abstract_methods)
+                    .map(|(name, span)| Occurrence {
+                        span,
+                        symbol: Symbol::Method(class.name.clone(), name.clone()),
+                        is_declaration: true,
+                    }),
+            );
+        }
+
+        for (root, gamma) in self.roots() {
+            visit(root, &gamma, &mut |term, gamma| {
+                let occurrence = match term {
+                    Term::FieldAccess(FieldAccess {
+                        object_term, field, ..
+                    })
+                    | Term::FieldAssign(FieldAssign {
+                        object_term, field, ..
+                    }) => self.member_occurrence(ct, gamma, object_term, |class_name| {
+                        field_owner(ct, class_name, field)
+                            .map(|owner| Symbol::Field(owner, field.clone()))
+                    }),
+                    Term::MethodCall(call) => {
+                        self.member_occurrence(ct, gamma, &call.object_term, |class_name| {
+                            method_owner(ct, class_name, &call.method_name)
+                                .map(|owner| Symbol::Method(owner, call.method_name.clone()))
+                        })
+                    }
+                    _ => None,
+                };
+                occurrences.extend(occurrence);
+            });
+        }
+        occurrences
+    }
+
+    /// The name after `object_term.`, referring to the member `symbol`
+    /// returns for the class of the receiver.
+    fn member_occurrence(
+        &self,
+        ct: &ClassTable,
+        gamma: &Gamma,
+        object_term: &Term,
+        symbol: impl FnOnce(&ClassName) -> Option<Symbol>,
+    ) -> Option<Occurrence> {
+        let span = self.member_span(object_term)?;
+        let class_type = self.receiver_type(ct, gamma, object_term)?;
+        Some(Occurrence {
+            span,
+            symbol: symbol(&class_type.name)?,
+            is_declaration: false,
+        })
+    }
+}
+
+/// Call `f` on `term` and all its subterms, with the variables in scope.
+fn visit<'t>(term: &'t Term, gamma: &Gamma, f: &mut dyn FnMut(&'t Term, &Gamma)) {
+    f(term, gamma);
+    match term {
+        Term::Variable(_) | Term::Literal(_) | Term::Location(_) => {}
+        Term::FieldAccess(access) => visit(&access.object_term, gamma, f),
+        Term::MethodCall(call) => {
+            visit(&call.object_term, gamma, f);
+            for arg in &call.arg_terms {
+                visit(arg, gamma, f);
+            }
+        }
+        Term::NewCall(new) => {
+            for arg in &new.arg_terms {
+                visit(arg, gamma, f);
+            }
+        }
+        Term::Cast(cast) => visit(&cast.term, gamma, f),
+        Term::Lambda(lambda) => visit(&lambda.body, &gamma.with_args(&lambda.args), f),
+        Term::BinaryOp(op) => {
+            visit(&op.lhs, gamma, f);
+            visit(&op.rhs, gamma, f);
+        }
+        Term::UnaryOp(op) => visit(&op.term, gamma, f),
+        Term::If(i) => {
+            visit(&i.condition, gamma, f);
+            visit(&i.then_term, gamma, f);
+            visit(&i.else_term, gamma, f);
+        }
+        Term::FieldAssign(assign) => {
+            visit(&assign.object_term, gamma, f);
+            visit(&assign.value_term, gamma, f);
+        }
+        Term::Let(l) => {
+            visit(&l.value_term, gamma, f);
+            let gamma = gamma.with_args(&[(l.ty.clone(), l.name.clone())]);
+            visit(&l.body, &gamma, f);
+        }
+        Term::Seq(seq) => {
+            visit(&seq.first, gamma, f);
+            visit(&seq.second, gamma, f);
+        }
+        Term::Throw(throw) => visit(&throw.term, gamma, f),
+        Term::Try(t) => {
+            visit(&t.body, gamma, f);
+            for catch in &t.catches {
+                let exception_type = ClassType::new(catch.class_name.clone()).into_type();
+                let gamma = gamma.with_args(&[(exception_type, catch.name.clone())]);
+                visit(&catch.body, &gamma, f);
+            }
+        }
+    }
+}
+
+/// The fields and methods of `class_type`, with their instantiated types.
+fn members(ct: &ClassTable, class_type: &ClassType) -> Vec<Completion> {
+    let fields = fields_of(ct, class_type)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(field_type, name)| Completion {
+            name: name.0,
+            detail: field_type.to_string(),
+            kind: CompletionKind::Field,
+        });
+    let mut method_names = BTreeSet::new();
+    collect_method_names(ct, &class_type.name, &mut method_names);
+    let methods = method_names.into_iter().filter_map(|name| {
+        let method_type = method_type_of(ct, &name, class_type)?;
+        Some(Completion {
+            name: name.0,
+            detail: method_type.to_string(),
+            kind: CompletionKind::Method,
+        })
+    });
+    fields.chain(methods).collect()
+}
+
+/// Add the names of all methods of `class_name` and its supertypes to `names`.
+fn collect_method_names(ct: &ClassTable, class_name: &ClassName, names: &mut BTreeSet<MethodName>) {
+    if let Some(interface) = ct.interfaces().get(class_name) {
+        names.extend(
+            interface
+                .methods
+                .iter()
+                .map(|method| method.method_name.clone()),
+        );
+        for super_type in &interface.super_types {
+            collect_method_names(ct, &super_type.name, names);
+        }
+    } else if let Some(class) = ct.inner().get(class_name) {
+        names.extend(
+            class
+                .methods
+                .iter()
+                .map(|method| method.method_name.clone()),
+        );
+        names.extend(
+            class
+                .abstract_methods
+                .iter()
+                .map(|method| method.method_name.clone()),
+        );
+        collect_method_names(ct, &class.super_type.name, names);
+        for interface in &class.interfaces {
+            collect_method_names(ct, &interface.name, names);
+        }
+    }
+}
+
+/// The class or interface declaring the method `method_name` of
+/// `class_name`, searched in the same order as by [`method_type_of`].
+fn method_owner(
+    ct: &ClassTable,
+    class_name: &ClassName,
+    method_name: &MethodName,
+) -> Option<ClassName> {
+    if let Some(interface) = ct.interfaces().get(class_name) {
+        if interface
+            .methods
+            .iter()
+            .any(|method| &method.method_name == method_name)
+        {
+            return Some(class_name.clone());
+        }
+        return interface
+            .super_types
+            .iter()
+            .find_map(|super_type| method_owner(ct, &super_type.name, method_name));
+    }
+    let class = ct.inner().get(class_name)?;
+    let methods = class.methods.iter().map(|method| &method.method_name);
+    let abstract_methods = class
+        .abstract_methods
+        .iter()
+        .map(|method| &method.method_name);
+    if methods
+        .chain(abstract_methods)
+        .any(|name| name == method_name)
+    {
+        return Some(class_name.clone());
+    }
+    method_owner(ct, &class.super_type.name, method_name).or_else(|| {
+        class
+            .interfaces
+            .iter()
+            .find_map(|interface| method_owner(ct, &interface.name, method_name))
+    })
+}
+
+/// The class declaring the field `field` of `class_name`.
+fn field_owner(ct: &ClassTable, class_name: &ClassName, field: &FieldName) -> Option<ClassName> {
+    let class = ct.inner().get(class_name)?;
+    if class.fields.iter().any(|(_, name)| name == field) {
+        Some(class_name.clone())
+    } else {
+        field_owner(ct, &class.super_type.name, field)
+    }
+}
@@ -1,19 +1,62 @@
 use std::fmt::Display;
 
-#[derive(Debug, Clone)]
+pub use crate::source::{FileId, Span};
+
+#[derive(Debug, Clone, Default)]
 pub struct Ast {
+    /// Files whose definitions are loaded together with this one, see
+    /// [`crate::loader`].
+    pub imports: Vec<Import>,
     pub class_definitions: Vec<ClassDefinition>,
+    pub interface_definitions: Vec<InterfaceDefinition>,
+}
+
+/// `import "path";`, with `path` relative to the directory of the importing
+/// file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub path: String,
+    pub span: Span,
+}
+
+/// A self-contained program: class definitions mixed with `eval { ... }`
+/// blocks, which are evaluated in order against all defined classes.
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub ast: Ast,
+    pub eval_terms: Vec<Term>,
 }
 
-pub type ArgPair = (ClassName, FieldName);
+pub type ArgPair = (Type, FieldName);
 
 #[derive(Debug, Clone)]
 pub struct ClassDefinition {
+    /// Declared `abstract class`, so it can not be instantiated.
+    pub is_abstract: bool,
     pub name: ClassName,
-    pub super_type: ClassName,
+    pub type_params: Vec<TypeParam>,
+    pub super_type: ClassType,
+    /// Interfaces `I<T̄>` listed after `implements`.
+    pub interfaces: Vec<ClassType>,
     pub fields: Vec<ArgPair>,
     pub constructor: Constructor,
     pub methods: Vec<MethodDefinition>,
+    /// Methods declared `abstract`, without a body.
+    pub abstract_methods: Vec<MethodSignature>,
+    pub span: Span,
+    pub name_span: Span,
+}
+
+/// `interface I<X̄ ◁ N̄> extends J̄ { ... }`, declaring methods without bodies.
+/// Interfaces share the namespace of classes.
+#[derive(Debug, Clone)]
+pub struct InterfaceDefinition {
+    pub name: ClassName,
+    pub type_params: Vec<TypeParam>,
+    pub super_types: Vec<ClassType>,
+    pub methods: Vec<MethodSignature>,
+    pub span: Span,
+    pub name_span: Span,
 }
 
 #[derive(
@@ -31,37 +74,348 @@ pub struct FieldName(pub String);
 )]
 pub struct MethodName(pub String);
 
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, derive_more::Display, derive_more::Into,
+)]
+pub struct TypeVariable(pub String);
+
+/// A nonvariable type `C<T̄>`, written `N` in FGJ. Non-generic classes have no
+/// type arguments.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClassType {
+    pub name: ClassName,
+    pub args: Vec<Type>,
+}
+
+/// A type `T`, either a type variable, a class type or a primitive type.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Type {
+    Var(TypeVariable),
+    Class(ClassType),
+    Primitive(PrimitiveType),
+}
+
+/// The built-in types `int`, `boolean` and `String`. They are not subtypes
+/// of `Object` and can not be type arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PrimitiveType {
+    Int,
+    Boolean,
+    String,
+}
+
+impl PrimitiveType {
+    /// The primitive type written as `name`, which is not a class name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(PrimitiveType::Int),
+            "boolean" => Some(PrimitiveType::Boolean),
+            "String" => Some(PrimitiveType::String),
+            _ => None,
+        }
+    }
+    pub fn into_type(self) -> Type {
+        Type::Primitive(self)
+    }
+}
+
+/// Declaration `X extends N` of a type parameter of a class or method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeParam {
+    pub name: TypeVariable,
+    pub bound: ClassType,
+}
+
+impl ClassType {
+    /// The non-generic class `name`.
+    pub fn new(name: ClassName) -> Self {
+        ClassType { name, args: vec![] }
+    }
+    pub fn object() -> Self {
+        ClassType::new(ClassName::object())
+    }
+    pub fn into_type(self) -> Type {
+        Type::Class(self)
+    }
+}
+
+impl From<ClassType> for Type {
+    fn from(class_type: ClassType) -> Self {
+        class_type.into_type()
+    }
+}
+
+impl ClassDefinition {
+    /// The type `C<X̄>` of `this` within the class.
+    pub fn this_type(&self) -> ClassType {
+        ClassType {
+            name: self.name.clone(),
+            args: self
+                .type_params
+                .iter()
+                .map(|param| Type::Var(param.name.clone()))
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Constructor {
     pub name: ClassName,
     pub args: Vec<ArgPair>,
     pub super_call: Vec<FieldName>,
     pub assignments: Vec<(FieldName, FieldName)>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct MethodDefinition {
-    pub return_type: ClassName,
+    pub type_params: Vec<TypeParam>,
+    pub return_type: Type,
     pub method_name: MethodName,
     pub args: Vec<ArgPair>,
     pub return_term: Box<Term>,
+    pub span: Span,
+    pub name_span: Span,
+}
+
+/// A method declaration `<Ȳ ◁ P̄> T m(T̄ x̄);` without a body.
+#[derive(Debug, Clone)]
+pub struct MethodSignature {
+    pub type_params: Vec<TypeParam>,
+    pub return_type: Type,
+    pub method_name: MethodName,
+    pub args: Vec<ArgPair>,
+    pub span: Span,
+    pub name_span: Span,
+}
+
+impl InterfaceDefinition {
+    /// The type `I<X̄>` of the interface within its declaration.
+    pub fn this_type(&self) -> ClassType {
+        ClassType {
+            name: self.name.clone(),
+            args: self
+                .type_params
+                .iter()
+                .map(|param| Type::Var(param.name.clone()))
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Term {
-    Variable(FieldName),
+    Variable(Variable),
     FieldAccess(FieldAccess),
     MethodCall(MethodCall),
     NewCall(NewCall),
     Cast(Cast),
+    Lambda(Lambda),
+    Literal(Literal),
+    BinaryOp(BinaryOp),
+    UnaryOp(UnaryOp),
+    If(If),
+    FieldAssign(FieldAssign),
+    Let(Let),
+    Seq(Seq),
+    Location(Location),
+    Throw(Throw),
+    Try(Try),
 }
 
 impl Term {
     pub fn boxed(self) -> Box<Self> {
         Box::new(self)
     }
-    pub fn from_variable_str(name: &str) -> Term {
-        Term::Variable(FieldName(name.into()))
+    pub fn from_variable_str(name: &str, span: Span) -> Term {
+        Variable {
+            name: FieldName(name.into()),
+            span,
+        }
+        .into_term()
+    }
+    pub fn span(&self) -> Span {
+        match self {
+            Term::Variable(v) => v.span,
+            Term::FieldAccess(f) => f.span,
+            Term::MethodCall(m) => m.span,
+            Term::NewCall(n) => n.span,
+            Term::Cast(c) => c.span,
+            Term::Lambda(l) => l.span,
+            Term::Literal(l) => l.span,
+            Term::BinaryOp(b) => b.span,
+            Term::UnaryOp(u) => u.span,
+            Term::If(i) => i.span,
+            Term::FieldAssign(a) => a.span,
+            Term::Let(l) => l.span,
+            Term::Seq(s) => s.span,
+            Term::Location(l) => l.span,
+            Term::Throw(t) => t.span,
+            Term::Try(t) => t.span,
+        }
+    }
+}
+
+/// Terms are compared structurally, ignoring their source spans.
+impl PartialEq for Term {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Term::Variable(a), Term::Variable(b)) => a.name == b.name,
+            (Term::FieldAccess(a), Term::FieldAccess(b)) => {
+                a.field == b.field && a.object_term == b.object_term
+            }
+            (Term::MethodCall(a), Term::MethodCall(b)) => {
+                a.method_name == b.method_name
+                    && a.type_args == b.type_args
+                    && a.object_term == b.object_term
+                    && a.arg_terms == b.arg_terms
+            }
+            (Term::NewCall(a), Term::NewCall(b)) => {
+                a.class_name == b.class_name
+                    && a.type_args == b.type_args
+                    && a.arg_terms == b.arg_terms
+            }
+            (Term::Cast(a), Term::Cast(b)) => {
+                a.to_class_name == b.to_class_name && a.type_args == b.type_args && a.term == b.term
+            }
+            (Term::Lambda(a), Term::Lambda(b)) => {
+                a.args == b.args && a.body == b.body && a.target == b.target
+            }
+            (Term::Literal(a), Term::Literal(b)) => a.value == b.value,
+            (Term::BinaryOp(a), Term::BinaryOp(b)) => {
+                a.op == b.op && a.lhs == b.lhs && a.rhs == b.rhs
+            }
+            (Term::UnaryOp(a), Term::UnaryOp(b)) => a.op == b.op && a.term == b.term,
+            (Term::If(a), Term::If(b)) => {
+                a.condition == b.condition
+                    && a.then_term == b.then_term
+                    && a.else_term == b.else_term
+            }
+            (Term::FieldAssign(a), Term::FieldAssign(b)) => {
+                a.field == b.field && a.object_term == b.object_term && a.value_term == b.value_term
+            }
+            (Term::Let(a), Term::Let(b)) => {
+                a.ty == b.ty && a.name == b.name && a.value_term == b.value_term && a.body == b.body
+            }
+            (Term::Seq(a), Term::Seq(b)) => a.first == b.first && a.second == b.second,
+            (Term::Location(a), Term::Location(b)) => a.id == b.id,
+            (Term::Throw(a), Term::Throw(b)) => a.term == b.term,
+            (Term::Try(a), Term::Try(b)) => {
+                a.body == b.body
+                    && a.catches.len() == b.catches.len()
+                    && a.catches.iter().zip(&b.catches).all(|(a, b)| {
+                        a.class_name == b.class_name && a.name == b.name && a.body == b.body
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Term {}
+
+/// Definitions are compared structurally, ignoring their source spans like
+/// [`Term`]s.
+impl PartialEq for Ast {
+    fn eq(&self, other: &Self) -> bool {
+        self.imports.len() == other.imports.len()
+            && self
+                .imports
+                .iter()
+                .zip(&other.imports)
+                .all(|(a, b)| a.path == b.path)
+            && self.class_definitions == other.class_definitions
+            && self.interface_definitions == other.interface_definitions
+    }
+}
+
+impl Eq for Ast {}
+
+impl PartialEq for Script {
+    fn eq(&self, other: &Self) -> bool {
+        self.ast == other.ast && self.eval_terms == other.eval_terms
+    }
+}
+
+impl Eq for Script {}
+
+impl PartialEq for ClassDefinition {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_abstract == other.is_abstract
+            && self.name == other.name
+            && self.type_params == other.type_params
+            && self.super_type == other.super_type
+            && self.interfaces == other.interfaces
+            && self.fields == other.fields
+            && self.constructor == other.constructor
+            && self.methods == other.methods
+            && self.abstract_methods == other.abstract_methods
+    }
+}
+
+impl Eq for ClassDefinition {}
+
+impl PartialEq for InterfaceDefinition {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.type_params == other.type_params
+            && self.super_types == other.super_types
+            && self.methods == other.methods
+    }
+}
+
+impl Eq for InterfaceDefinition {}
+
+impl PartialEq for Constructor {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.args == other.args
+            && self.super_call == other.super_call
+            && self.assignments == other.assignments
+    }
+}
+
+impl Eq for Constructor {}
+
+impl PartialEq for MethodDefinition {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_params == other.type_params
+            && self.return_type == other.return_type
+            && self.method_name == other.method_name
+            && self.args == other.args
+            && self.return_term == other.return_term
+    }
+}
+
+impl Eq for MethodDefinition {}
+
+impl PartialEq for MethodSignature {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_params == other.type_params
+            && self.return_type == other.return_type
+            && self.method_name == other.method_name
+            && self.args == other.args
+    }
+}
+
+impl Eq for MethodSignature {}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: FieldName,
+    pub span: Span,
+}
+
+impl Variable {
+    pub fn into_term(self) -> Term {
+        Term::Variable(self)
+    }
+}
+
+impl From<Variable> for Term {
+    fn from(v: Variable) -> Self {
+        v.into_term()
     }
 }
 
@@ -69,6 +423,7 @@ impl Term {
 pub struct FieldAccess {
     pub object_term: Box<Term>,
     pub field: FieldName,
+    pub span: Span,
 }
 
 impl FieldAccess {
@@ -87,7 +442,10 @@ impl From<FieldAccess> for Term {
 pub struct MethodCall {
     pub object_term: Box<Term>,
     pub method_name: MethodName,
+    /// Explicit method type arguments `e.m<V̄>(ē)`, inferred if empty.
+    pub type_args: Vec<Type>,
     pub arg_terms: Vec<Box<Term>>,
+    pub span: Span,
 }
 
 impl MethodCall {
@@ -105,10 +463,19 @@ impl From<MethodCall> for Term {
 #[derive(Debug, Clone)]
 pub struct NewCall {
     pub class_name: ClassName,
+    pub type_args: Vec<Type>,
     pub arg_terms: Vec<Box<Term>>,
+    pub span: Span,
 }
 
 impl NewCall {
+    /// The instantiated class `C<T̄>`.
+    pub fn class_type(&self) -> ClassType {
+        ClassType {
+            name: self.class_name.clone(),
+            args: self.type_args.clone(),
+        }
+    }
     pub fn into_term(self) -> Term {
         Term::NewCall(self)
     }
@@ -123,10 +490,19 @@ impl From<NewCall> for Term {
 #[derive(Debug, Clone)]
 pub struct Cast {
     pub to_class_name: ClassName,
+    pub type_args: Vec<Type>,
     pub term: Box<Term>,
+    pub span: Span,
 }
 
 impl Cast {
+    /// The target type `C<T̄>` of the cast.
+    pub fn to_type(&self) -> ClassType {
+        ClassType {
+            name: self.to_class_name.clone(),
+            args: self.type_args.clone(),
+        }
+    }
     pub fn into_term(self) -> Term {
         Term::Cast(self)
     }
@@ -138,38 +514,440 @@ impl From<Cast> for Term {
     }
 }
 
-impl Display for Term {
+/// A lambda expression `(T̄ x̄) -> t` of FJ&λ. It has no type of its own, but
+/// is typed against the functional interface expected where it occurs.
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub args: Vec<ArgPair>,
+    pub body: Box<Term>,
+    /// The interface the lambda was cast or passed to during evaluation,
+    /// which is checked by later casts. `None` in source terms.
+    pub target: Option<ClassName>,
+    pub span: Span,
+}
+
+impl Lambda {
+    pub fn into_term(self) -> Term {
+        Term::Lambda(self)
+    }
+}
+
+impl From<Lambda> for Term {
+    fn from(f: Lambda) -> Self {
+        f.into_term()
+    }
+}
+
+/// A value of a primitive type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    Int(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Primitive {
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            Primitive::Int(_) => PrimitiveType::Int,
+            Primitive::Boolean(_) => PrimitiveType::Boolean,
+            Primitive::String(_) => PrimitiveType::String,
+        }
+    }
+}
+
+/// A literal `42`, `true` or `"text"`, which is a value.
+#[derive(Debug, Clone)]
+pub struct Literal {
+    pub value: Primitive,
+    pub span: Span,
+}
+
+impl Literal {
+    pub fn into_term(self) -> Term {
+        Term::Literal(self)
+    }
+}
+
+impl From<Literal> for Term {
+    fn from(f: Literal) -> Self {
+        f.into_term()
+    }
+}
+
+/// Operators of [`BinaryOp`], from lowest to highest precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOperator {
+    /// Binding strength, all operators are left-associative.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Eq | BinaryOperator::Ne => 3,
+            BinaryOperator::Lt | BinaryOperator::Le | BinaryOperator::Gt | BinaryOperator::Ge => 4,
+            BinaryOperator::Add | BinaryOperator::Sub => 5,
+            BinaryOperator::Mul | BinaryOperator::Div | BinaryOperator::Rem => 6,
+        }
+    }
+}
+
+/// `lhs op rhs` on primitive values. `&&` and `||` only evaluate `rhs` if
+/// `lhs` does not determine the result.
+#[derive(Debug, Clone)]
+pub struct BinaryOp {
+    pub op: BinaryOperator,
+    pub lhs: Box<Term>,
+    pub rhs: Box<Term>,
+    pub span: Span,
+}
+
+impl BinaryOp {
+    pub fn into_term(self) -> Term {
+        Term::BinaryOp(self)
+    }
+}
+
+impl From<BinaryOp> for Term {
+    fn from(f: BinaryOp) -> Self {
+        f.into_term()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOperator {
+    /// Boolean negation `!`.
+    Not,
+    /// Integer negation `-`.
+    Neg,
+}
+
+/// `op term` on a primitive value.
+#[derive(Debug, Clone)]
+pub struct UnaryOp {
+    pub op: UnaryOperator,
+    pub term: Box<Term>,
+    pub span: Span,
+}
+
+impl UnaryOp {
+    pub fn into_term(self) -> Term {
+        Term::UnaryOp(self)
+    }
+}
+
+impl From<UnaryOp> for Term {
+    fn from(f: UnaryOp) -> Self {
+        f.into_term()
+    }
+}
+
+/// `if (condition) then_term else else_term`, an expression evaluating only
+/// one of its branches.
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Box<Term>,
+    pub then_term: Box<Term>,
+    pub else_term: Box<Term>,
+    pub span: Span,
+}
+
+impl If {
+    pub fn into_term(self) -> Term {
+        Term::If(self)
+    }
+}
+
+impl From<If> for Term {
+    fn from(f: If) -> Self {
+        f.into_term()
+    }
+}
+
+/// A field assignment `t.f = t'` of imperative FJ, evaluating to the
+/// assigned value.
+#[derive(Debug, Clone)]
+pub struct FieldAssign {
+    pub object_term: Box<Term>,
+    pub field: FieldName,
+    pub value_term: Box<Term>,
+    pub span: Span,
+}
+
+impl FieldAssign {
+    pub fn into_term(self) -> Term {
+        Term::FieldAssign(self)
+    }
+}
+
+impl From<FieldAssign> for Term {
+    fn from(f: FieldAssign) -> Self {
+        f.into_term()
+    }
+}
+
+/// A local variable declaration `{ T x = t; body }`, binding `x` to the
+/// value of `t` in `body`.
+#[derive(Debug, Clone)]
+pub struct Let {
+    pub ty: Type,
+    pub name: FieldName,
+    pub value_term: Box<Term>,
+    pub body: Box<Term>,
+    pub span: Span,
+}
+
+impl Let {
+    pub fn into_term(self) -> Term {
+        Term::Let(self)
+    }
+}
+
+impl From<Let> for Term {
+    fn from(f: Let) -> Self {
+        f.into_term()
+    }
+}
+
+/// A sequence `{ first; second }`, evaluating `first` for its effects and
+/// then `second`.
+#[derive(Debug, Clone)]
+pub struct Seq {
+    pub first: Box<Term>,
+    pub second: Box<Term>,
+    pub span: Span,
+}
+
+impl Seq {
+    pub fn into_term(self) -> Term {
+        Term::Seq(self)
+    }
+}
+
+impl From<Seq> for Term {
+    fn from(f: Seq) -> Self {
+        f.into_term()
+    }
+}
+
+/// The location `@n` of an object on the heap. Locations only occur during
+/// evaluation with a heap, where `new` allocates an object and reduces to its
+/// location; `span` is the span of that `new`.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub id: usize,
+    pub span: Span,
+}
+
+impl Location {
+    pub fn into_term(self) -> Term {
+        Term::Location(self)
+    }
+}
+
+impl From<Location> for Term {
+    fn from(f: Location) -> Self {
+        f.into_term()
+    }
+}
+
+/// `throw t`, raising the exception `t`, an instance of a subclass of
+/// `Throwable`.
+#[derive(Debug, Clone)]
+pub struct Throw {
+    pub term: Box<Term>,
+    pub span: Span,
+}
+
+impl Throw {
+    pub fn into_term(self) -> Term {
+        Term::Throw(self)
+    }
+}
+
+impl From<Throw> for Term {
+    fn from(f: Throw) -> Self {
+        f.into_term()
+    }
+}
+
+/// `try { body } catch (C x) { u } ...`, handling an exception raised by
+/// `body` with the first clause whose class it is an instance of.
+#[derive(Debug, Clone)]
+pub struct Try {
+    pub body: Box<Term>,
+    pub catches: Vec<Catch>,
+    pub span: Span,
+}
+
+impl Try {
+    pub fn into_term(self) -> Term {
+        Term::Try(self)
+    }
+}
+
+impl From<Try> for Term {
+    fn from(f: Try) -> Self {
+        f.into_term()
+    }
+}
+
+/// A clause `catch (C x) { body }` of a [`Try`], binding the exception to `x`
+/// in `body`.
+#[derive(Debug, Clone)]
+pub struct Catch {
+    pub class_name: ClassName,
+    pub name: FieldName,
+    pub body: Box<Term>,
+    pub span: Span,
+}
+
+impl Display for Import {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "import {};", Primitive::String(self.path.clone()))
+    }
+}
+
+impl Display for ClassType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", &self.name, DisplayTypeArgs(&self.args))
+    }
+}
+
+impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Term::Cast(Cast {
-                to_class_name,
-                term,
-            }) => write!(f, "(({}) {})", &to_class_name, &term),
-            Term::FieldAccess(FieldAccess { object_term, field }) => {
-                write!(f, "{}.{}", &object_term, &field)
-            }
-            Term::MethodCall(MethodCall {
-                method_name,
-                arg_terms,
-                object_term,
-            }) => {
-                write!(f, "{}.{}(", &object_term, &method_name)?;
-                for t in arg_terms {
-                    write!(f, "{},", t)?;
+            Type::Var(var) => write!(f, "{}", var),
+            Type::Class(class_type) => write!(f, "{}", class_type),
+            Type::Primitive(primitive_type) => write!(f, "{}", primitive_type),
+        }
+    }
+}
+
+impl Display for PrimitiveType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PrimitiveType::Int => "int",
+            PrimitiveType::Boolean => "boolean",
+            PrimitiveType::String => "String",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Strings are written as literals, with quotes and escapes.
+impl Display for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Primitive::Int(i) => write!(f, "{}", i),
+            Primitive::Boolean(b) => write!(f, "{}", b),
+            Primitive::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
                 }
-                write!(f, ")")
+                write!(f, "\"")
             }
-            Term::NewCall(NewCall {
-                class_name,
-                arg_terms,
-            }) => {
-                write!(f, "new {}(", &class_name)?;
-                for t in arg_terms {
-                    write!(f, "{},", t)?;
-                }
-                write!(f, ")")
+        }
+    }
+}
+
+impl Display for BinaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Or => "||",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Eq => "==",
+            BinaryOperator::Ne => "!=",
+            BinaryOperator::Lt => "<",
+            BinaryOperator::Le => "<=",
+            BinaryOperator::Gt => ">",
+            BinaryOperator::Ge => ">=",
+            BinaryOperator::Add => "+",
+            BinaryOperator::Sub => "-",
+            BinaryOperator::Mul => "*",
+            BinaryOperator::Div => "/",
+            BinaryOperator::Rem => "%",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Display for UnaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOperator::Not => write!(f, "!"),
+            UnaryOperator::Neg => write!(f, "-"),
+        }
+    }
+}
+
+/// `X extends N`, or just `X` for the default bound `Object`.
+impl Display for TypeParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.bound == ClassType::object() {
+            write!(f, "{}", &self.name)
+        } else {
+            write!(f, "{} extends {}", &self.name, &self.bound)
+        }
+    }
+}
+
+/// Type arguments `<T1, T2>`, or nothing if there are none.
+pub(crate) struct DisplayTypeArgs<'a>(pub(crate) &'a [Type]);
+
+impl Display for DisplayTypeArgs<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        for (i, ty) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", ty)?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// Type parameters `<X extends N, Y>`, or nothing if there are none.
+pub(crate) struct DisplayTypeParams<'a>(pub(crate) &'a [TypeParam]);
+
+impl Display for DisplayTypeParams<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        for (i, param) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
             }
-            Term::Variable(x) => write!(f, "{}", &x),
+            write!(f, "{}", param)?;
         }
+        write!(f, ">")
     }
 }
@@ -0,0 +1,1146 @@
+//! Compilation of a typechecked program to stack-based bytecode, executed by [`crate::vm`].
+//!
+//! All lookups in the class table are resolved at compile time: objects store
+//! their fields in constructor order, so a field has the same index in a
+//! class and all its subclasses. Likewise every class has a vtable which
+//! extends the vtable of its superclass, with an overriding method taking the
+//! slot of the overridden one. Method calls are compiled to that slot, using
+//! the static type of the receiver. Type arguments only matter for these
+//! static types, so generic classes compile like their erasure.
+//!
+//! A class can implement several interfaces, so the methods of an interface
+//! have no common slot. Calls on a receiver of interface type name the method
+//! by a selector instead, which is mapped to a slot by the itable of the class
+//! of the receiver at runtime.
+//!
+//! Every lambda is compiled to a class of its own, implementing the interface
+//! it is typed against. Its fields hold the variables the lambda captures and
+//! its only method, the method of the interface, runs the body of the lambda.
+
+use crate::ast::*;
+use crate::class_table::ClassTable;
+use crate::error::{EvalError, TypingError};
+use crate::typecheck::{self, ArgType, Delta, Gamma, TypecheckConfig};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClassId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FunctionId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instr {
+    /// Push local variable `n`: `this` is 0, followed by the method arguments
+    /// and the local variables declared in the body.
+    Load(u32),
+    /// Pop a value into local variable `n`.
+    Store(u32),
+    /// Pop a value and discard it.
+    Pop,
+    /// Pop `argc` field values and push a new object of `class`.
+    New { class: ClassId, argc: u32 },
+    /// Pop an object and push its field at the given index.
+    GetField(u32),
+    /// Push the captured variable at the given index, a field of the lambda
+    /// in local 0.
+    LoadCaptured(u32),
+    /// Pop `argc` arguments and the receiver and call the method in `slot` of
+    /// the receiver's vtable.
+    InvokeVirtual { slot: u32, argc: u32 },
+    /// Pop `argc` arguments and the receiver and call the method named by
+    /// `selector`, found through the itable of the receiver's class.
+    InvokeInterface { selector: u32, argc: u32 },
+    /// Fail unless the object on top of the stack is an instance of `class`.
+    CheckCast(ClassId),
+    /// Push the constant at the given index.
+    Const(u32),
+    /// Pop two primitive values and push the result of the operator, which
+    /// is not `&&` or `||`. These are compiled to jumps.
+    BinaryOp(BinaryOperator),
+    /// Pop a primitive value and push the result of the operator.
+    UnaryOp(UnaryOperator),
+    /// Pop a `boolean` and continue at the given index if it is `false`.
+    JumpIfFalse(u32),
+    /// Continue at the given index.
+    Jump(u32),
+    /// Return the top of the stack to the caller.
+    Return,
+    /// Pop an exception and continue at the handler of the innermost `TRY`
+    /// still running, in this or a calling function.
+    Throw,
+    /// Throw the exception on top of the stack again, which no `CATCH` of the
+    /// handler matched.
+    Rethrow,
+    /// Install a handler at the given index until the matching `ENDTRY`. It
+    /// is entered with the exception on top of the stack.
+    Try(u32),
+    /// Remove the handler installed by the matching `TRY`.
+    EndTry,
+    /// Pop the exception into local variable `local` if it is an instance of
+    /// `class`, and otherwise continue at `next`.
+    Catch {
+        class: ClassId,
+        local: u32,
+        next: u32,
+    },
+}
+
+/// Compiled body of a method, or of the term to evaluate.
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// `C.m` for methods.
+    pub name: String,
+    /// Number of locals including `this`, for which the frame reserves stack
+    /// slots.
+    pub locals: u32,
+    pub code: Vec<Instr>,
+    /// Source location of every instruction.
+    pub spans: Vec<Span>,
+    /// Primitive values pushed by `CONST`.
+    pub constants: Vec<Primitive>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+    pub name: ClassName,
+    /// `None` only for `Object` and interfaces.
+    pub super_class: Option<ClassId>,
+    pub is_interface: bool,
+    /// All interfaces implemented by the class, directly or through its
+    /// superclasses and the interfaces they extend.
+    pub interfaces: Vec<ClassId>,
+    pub field_count: u32,
+    /// Slot of every method understood by the class.
+    pub slots: BTreeMap<MethodName, u32>,
+    /// `None` for abstract methods, which only abstract classes have.
+    pub vtable: Vec<Option<FunctionId>>,
+    /// Slot of every method understood by the class, by selector.
+    pub itable: BTreeMap<u32, u32>,
+    /// The lambda the class was compiled from, if any.
+    pub lambda: Option<LambdaInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LambdaInfo {
+    /// The lambda, decorated with the interface it implements.
+    pub lambda: Lambda,
+    /// The variables captured by the lambda, in field order.
+    pub captured: Vec<FieldName>,
+}
+
+/// A lambda compiled by a [`FunctionCompiler`], which is added to the program
+/// once the function is compiled.
+struct LambdaClass {
+    name: ClassName,
+    info: LambdaInfo,
+    method_name: MethodName,
+    function: Function,
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub classes: Vec<ClassInfo>,
+    pub class_ids: BTreeMap<ClassName, ClassId>,
+    pub functions: Vec<Function>,
+    /// Names of all methods declared by interfaces, indexed by selector.
+    pub selectors: Vec<MethodName>,
+}
+
+impl Program {
+    /// Compile all classes of `ct`, which must be typechecked.
+    pub fn compile(ct: &ClassTable) -> Result<Self> {
+        let mut program = Program {
+            classes: vec![],
+            class_ids: BTreeMap::new(),
+            functions: vec![],
+            selectors: vec![],
+        };
+        // superclasses first, so vtables can extend the vtable of the superclass
+        let mut order = vec![];
+        let mut pending = vec![ClassName::object()];
+        while let Some(class_name) = pending.pop() {
+            pending.extend(ct.direct_subtypes(&class_name).unwrap().cloned());
+            program.declare_class(ct, &class_name);
+            order.push(class_name);
+        }
+        for (interface_name, interface) in ct.interfaces() {
+            program.declare_interface(interface_name);
+            for method in &interface.methods {
+                if !program.selectors.contains(&method.method_name) {
+                    program.selectors.push(method.method_name.clone());
+                }
+            }
+        }
+        for class_name in &order {
+            program.link_interfaces(ct, class_name);
+            program.compile_methods(ct, class_name)?;
+        }
+        Ok(program)
+    }
+
+    /// Compile the closed term `term` to a function without locals. The
+    /// classes of the lambdas in `term` are added to the program.
+    pub fn compile_term(&mut self, ct: &ClassTable, term: &Term) -> Result<Function> {
+        let mut compiler = FunctionCompiler::new(self, ct, "<main>", vec![], Delta::new());
+        compiler.term(term)?;
+        let (function, lambdas) = compiler.finish(term.span());
+        self.add_lambdas(ct, lambdas);
+        Ok(function)
+    }
+
+    pub fn class(&self, id: ClassId) -> &ClassInfo {
+        &self.classes[id.0 as usize]
+    }
+
+    pub fn function(&self, id: FunctionId) -> &Function {
+        &self.functions[id.0 as usize]
+    }
+
+    pub fn is_subclass(&self, mut class: ClassId, of: ClassId) -> bool {
+        if self.class(of).is_interface {
+            return class == of || self.class(class).interfaces.contains(&of);
+        }
+        loop {
+            if class == of {
+                return true;
+            }
+            match self.class(class).super_class {
+                Some(super_class) => class = super_class,
+                None => return false,
+            }
+        }
+    }
+
+    /// Assign an id and the vtable slots of `class_name`. Method bodies and
+    /// vtables are compiled once all classes are known.
+    fn declare_class(&mut self, ct: &ClassTable, class_name: &ClassName) {
+        let id = ClassId(self.classes.len() as u32);
+        let info = match ct.inner().get(class_name) {
+            None => ClassInfo {
+                name: class_name.clone(),
+                super_class: None,
+                is_interface: false,
+                interfaces: vec![],
+                field_count: 0,
+                slots: BTreeMap::new(),
+                vtable: vec![],
+                itable: BTreeMap::new(),
+                lambda: None,
+            },
+            Some(class) => {
+                let super_class = self.class_ids[&class.super_type.name];
+                let mut slots = self.class(super_class).slots.clone();
+                let method_names = class
+                    .methods
+                    .iter()
+                    .map(|method| &method.method_name)
+                    .chain(
+                        class
+                            .abstract_methods
+                            .iter()
+                            .map(|method| &method.method_name),
+                    );
+                for method_name in method_names {
+                    if !slots.contains_key(method_name) {
+                        slots.insert(method_name.clone(), slots.len() as u32);
+                    }
+                }
+                ClassInfo {
+                    name: class_name.clone(),
+                    super_class: Some(super_class),
+                    is_interface: false,
+                    interfaces: vec![],
+                    field_count: ct.fields(class_name).unwrap().count() as u32,
+                    slots,
+                    vtable: vec![],
+                    itable: BTreeMap::new(),
+                    lambda: None,
+                }
+            }
+        };
+        self.class_ids.insert(class_name.clone(), id);
+        self.classes.push(info);
+    }
+
+    /// Assign an id to the interface `interface_name`. Interfaces have no
+    /// fields and no vtable, their methods are called by selector.
+    fn declare_interface(&mut self, interface_name: &ClassName) {
+        let id = ClassId(self.classes.len() as u32);
+        self.class_ids.insert(interface_name.clone(), id);
+        self.classes.push(ClassInfo {
+            name: interface_name.clone(),
+            super_class: None,
+            is_interface: true,
+            interfaces: vec![],
+            field_count: 0,
+            slots: BTreeMap::new(),
+            vtable: vec![],
+            itable: BTreeMap::new(),
+            lambda: None,
+        });
+    }
+
+    /// Record the interfaces implemented by `class_name` and build its itable,
+    /// once all interfaces and selectors are known.
+    fn link_interfaces(&mut self, ct: &ClassTable, class_name: &ClassName) {
+        let id = self.class_ids[class_name];
+        let interfaces = ct
+            .interfaces()
+            .keys()
+            .filter(|interface_name| ct.is_subtype(class_name, interface_name) == Some(true))
+            .map(|interface_name| self.class_ids[interface_name])
+            .collect();
+        let class = self.class(id);
+        let itable = self
+            .selectors
+            .iter()
+            .enumerate()
+            .filter_map(|(selector, method_name)| {
+                Some((selector as u32, *class.slots.get(method_name)?))
+            })
+            .collect();
+        let class = &mut self.classes[id.0 as usize];
+        class.interfaces = interfaces;
+        class.itable = itable;
+    }
+
+    /// Compile the methods of `class_name` and build its vtable, which requires
+    /// the vtable of the superclass.
+    fn compile_methods(&mut self, ct: &ClassTable, class_name: &ClassName) -> Result<()> {
+        let class = match ct.inner().get(class_name) {
+            Some(class) => class,
+            None => return Ok(()),
+        };
+        let id = self.class_ids[class_name];
+        let mut own = Vec::new();
+        for method in &class.methods {
+            let locals = std::iter::once((FieldName("this".into()), class.this_type().into_type()))
+                .chain(method.args.iter().map(|(t, x)| (x.clone(), t.clone())))
+                .collect();
+            let delta = Gamma::from_class_method(class, method).delta().clone();
+            let name = format!("{}.{}", class_name, method.method_name);
+            let mut compiler = FunctionCompiler::new(self, ct, &name, locals, delta);
+            compiler.term_against(&method.return_term, &method.return_type)?;
+            let (function, lambdas) = compiler.finish(method.span);
+
+            own.push((
+                self.class(id).slots[&method.method_name],
+                FunctionId(self.functions.len() as u32),
+            ));
+            self.functions.push(function);
+            self.add_lambdas(ct, lambdas);
+        }
+
+        // overriding methods replace the inherited entry, new methods take
+        // their slot, and abstract methods leave it empty
+        let mut vtable = self
+            .class(id)
+            .super_class
+            .map(|super_class| self.class(super_class).vtable.clone())
+            .unwrap_or_default();
+        vtable.resize(self.class(id).slots.len(), None);
+        for method in &class.abstract_methods {
+            vtable[self.class(id).slots[&method.method_name] as usize] = None;
+        }
+        for (slot, function) in own {
+            vtable[slot as usize] = Some(function);
+        }
+        self.classes[id.0 as usize].vtable = vtable;
+        Ok(())
+    }
+
+    /// Add the classes of `lambdas`, in the order their ids were reserved by
+    /// [`FunctionCompiler::lambda`].
+    fn add_lambdas(&mut self, ct: &ClassTable, lambdas: Vec<LambdaClass>) {
+        for LambdaClass {
+            name,
+            info,
+            method_name,
+            function,
+        } in lambdas
+        {
+            // NOTE(unwrap): lambdas are only compiled against interfaces
+            let target = info.lambda.target.clone().unwrap();
+            let interfaces = ct
+                .interfaces()
+                .keys()
+                .filter(|interface_name| ct.is_subtype(&target, interface_name) == Some(true))
+                .map(|interface_name| self.class_ids[interface_name])
+                .collect();
+            // NOTE(unwrap): every method of an interface has a selector
+            let selector = self
+                .selectors
+                .iter()
+                .position(|selector| selector == &method_name)
+                .unwrap();
+            let id = ClassId(self.classes.len() as u32);
+            self.class_ids.insert(name.clone(), id);
+            self.classes.push(ClassInfo {
+                name,
+                super_class: Some(self.class_ids[&ClassName::object()]),
+                is_interface: false,
+                interfaces,
+                field_count: info.captured.len() as u32,
+                slots: BTreeMap::from([(method_name, 0)]),
+                vtable: vec![Some(FunctionId(self.functions.len() as u32))],
+                itable: BTreeMap::from([(selector as u32, 0)]),
+                lambda: Some(info),
+            });
+            self.functions.push(function);
+        }
+    }
+}
+
+struct FunctionCompiler<'a> {
+    program: &'a Program,
+    ct: &'a ClassTable,
+    name: String,
+    /// Names and static types of the locals in scope.
+    locals: Vec<(FieldName, Type)>,
+    /// Number of locals in scope at the same time so far, as local variables
+    /// of blocks reuse the slots of those whose scope has ended.
+    max_locals: usize,
+    /// Names and static types of the variables captured by a lambda, which
+    /// are stored in the fields of the lambda in local 0.
+    captured: Vec<(FieldName, Type)>,
+    /// Bounds of the type variables in scope.
+    delta: Delta,
+    code: Vec<Instr>,
+    spans: Vec<Span>,
+    constants: Vec<Primitive>,
+    /// Lambdas compiled so far, including those of enclosing functions.
+    lambdas: Vec<LambdaClass>,
+}
+
+impl<'a> FunctionCompiler<'a> {
+    fn new(
+        program: &'a Program,
+        ct: &'a ClassTable,
+        name: &str,
+        locals: Vec<(FieldName, Type)>,
+        delta: Delta,
+    ) -> Self {
+        FunctionCompiler {
+            program,
+            ct,
+            name: name.into(),
+            max_locals: locals.len(),
+            locals,
+            captured: vec![],
+            delta,
+            code: vec![],
+            spans: vec![],
+            constants: vec![],
+            lambdas: vec![],
+        }
+    }
+
+    fn emit(&mut self, instr: Instr, span: Span) {
+        self.code.push(instr);
+        self.spans.push(span);
+    }
+
+    /// Emit the jump `instr` to a target set later by [`FunctionCompiler::patch`],
+    /// returning its index.
+    fn emit_jump(&mut self, instr: Instr, span: Span) -> usize {
+        self.emit(instr, span);
+        self.code.len() - 1
+    }
+
+    /// Let the jump at `at` continue at the next instruction emitted.
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len() as u32;
+        self.code[at] = match self.code[at] {
+            Instr::JumpIfFalse(_) => Instr::JumpIfFalse(target),
+            Instr::Jump(_) => Instr::Jump(target),
+            Instr::Try(_) => Instr::Try(target),
+            Instr::Catch { class, local, .. } => Instr::Catch {
+                class,
+                local,
+                next: target,
+            },
+            instr => instr,
+        };
+    }
+
+    /// Emit code pushing the primitive `value`.
+    fn constant(&mut self, value: &Primitive, span: Span) {
+        let i = match self.constants.iter().position(|constant| constant == value) {
+            Some(i) => i,
+            None => {
+                self.constants.push(value.clone());
+                self.constants.len() - 1
+            }
+        };
+        self.emit(Instr::Const(i as u32), span);
+    }
+
+    fn finish(mut self, span: Span) -> (Function, Vec<LambdaClass>) {
+        self.emit(Instr::Return, span);
+        let function = Function {
+            name: self.name,
+            locals: self.max_locals as u32,
+            code: self.code,
+            spans: self.spans,
+            constants: self.constants,
+        };
+        (function, self.lambdas)
+    }
+
+    /// The context of the typechecker for the variables in scope.
+    fn gamma(&self) -> Gamma {
+        Gamma::from_vars(
+            self.captured.iter().chain(&self.locals).cloned(),
+            self.delta.clone(),
+        )
+    }
+
+    fn class_id(&self, class_name: &ClassName, span: Span) -> Result<ClassId> {
+        self.program
+            .class_ids
+            .get(class_name)
+            .copied()
+            .ok_or_else(|| TypingError::UndefinedClass(class_name.clone(), span).into())
+    }
+
+    /// The class of the bound of `ty`, which determines field indices and vtable slots.
+    fn class_of(&self, ty: &Type, span: Span) -> Result<ClassId> {
+        // NOTE(unwrap): only type variables in scope occur in typechecked terms
+        self.class_id(&self.delta.bound(ty).unwrap().name, span)
+    }
+
+    /// Emit code for `term`, returning its static type.
+    fn term(&mut self, term: &Term) -> Result<Type> {
+        match term {
+            Term::Variable(Variable { name, span }) => {
+                let local = self
+                    .locals
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find(|(_, (local, _))| local == name);
+                if let Some((i, (_, ty))) = local {
+                    let ty = ty.clone();
+                    self.emit(Instr::Load(i as u32), *span);
+                    return Ok(ty);
+                }
+                let (i, (_, ty)) = self
+                    .captured
+                    .iter()
+                    .enumerate()
+                    .find(|(_, (captured, _))| captured == name)
+                    .ok_or_else(|| TypingError::VariableNotInGamma(name.clone(), *span))?;
+                let ty = ty.clone();
+                self.emit(Instr::LoadCaptured(i as u32), *span);
+                Ok(ty)
+            }
+            Term::FieldAccess(FieldAccess {
+                object_term,
+                field,
+                span,
+            }) => {
+                let object_type = self.term(object_term)?;
+                let field_type =
+                    typecheck::field_type(self.ct, &self.delta, &object_type, field, *span)?;
+                let class = self.program.class(self.class_of(&object_type, *span)?);
+                // NOTE(unwrap): the field has a type, so it is defined
+                let i = self.ct.field_index(&class.name, field).unwrap();
+                self.emit(Instr::GetField(i as u32), *span);
+                Ok(field_type)
+            }
+            Term::MethodCall(method_call) => {
+                let MethodCall {
+                    object_term,
+                    method_name,
+                    arg_terms,
+                    span,
+                    ..
+                } = method_call;
+                let object_type = self.term(object_term)?;
+                // the parameter types lambdas are compiled against are only
+                // known once the type arguments are inferred
+                let method_type = if arg_terms
+                    .iter()
+                    .any(|arg_term| matches!(**arg_term, Term::Lambda(_)))
+                {
+                    let arg_types = typecheck::arg_types(
+                        self.ct,
+                        &TypecheckConfig::default(),
+                        &self.gamma(),
+                        arg_terms,
+                    )?;
+                    let method_type = typecheck::method_call_type(
+                        self.ct,
+                        &self.delta,
+                        &object_type,
+                        method_call,
+                        &arg_types,
+                    )?;
+                    for (arg_term, param_type) in arg_terms.iter().zip(&method_type.arg_types) {
+                        self.term_against(arg_term, param_type)?;
+                    }
+                    method_type
+                } else {
+                    let arg_types = arg_terms
+                        .iter()
+                        .map(|arg_term| Ok(ArgType::Term(self.term(arg_term)?)))
+                        .collect::<Result<Vec<_>>>()?;
+                    typecheck::method_call_type(
+                        self.ct,
+                        &self.delta,
+                        &object_type,
+                        method_call,
+                        &arg_types,
+                    )?
+                };
+                let class = self.program.class(self.class_of(&object_type, *span)?);
+                let argc = arg_terms.len() as u32;
+                // abstract classes may leave the methods of their interfaces to subclasses
+                let instr = if class.is_interface || !class.slots.contains_key(method_name) {
+                    let selector = self
+                        .program
+                        .selectors
+                        .iter()
+                        .position(|selector| selector == method_name)
+                        .ok_or_else(|| {
+                            TypingError::UndefinedMethod(
+                                method_name.clone(),
+                                class.name.clone(),
+                                *span,
+                            )
+                        })?;
+                    Instr::InvokeInterface {
+                        selector: selector as u32,
+                        argc,
+                    }
+                } else {
+                    let slot = *class.slots.get(method_name).ok_or_else(|| {
+                        TypingError::UndefinedMethod(method_name.clone(), class.name.clone(), *span)
+                    })?;
+                    Instr::InvokeVirtual { slot, argc }
+                };
+                self.emit(instr, *span);
+                Ok(method_type.ret_type)
+            }
+            Term::NewCall(new_call) => {
+                let fields =
+                    typecheck::fields_of(self.ct, &new_call.class_type()).ok_or_else(|| {
+                        TypingError::UndefinedClass(new_call.class_name.clone(), new_call.span)
+                    })?;
+                if new_call.arg_terms.len() != fields.len() {
+                    Err(TypingError::ConstructorArgumentCount(
+                        new_call.class_name.clone(),
+                        fields.len(),
+                        new_call.arg_terms.len(),
+                        new_call.span,
+                    ))?;
+                }
+                for (arg_term, (field_type, _)) in new_call.arg_terms.iter().zip(&fields) {
+                    self.term_against(arg_term, field_type)?;
+                }
+                let class = self.class_id(&new_call.class_name, new_call.span)?;
+                self.emit(
+                    Instr::New {
+                        class,
+                        argc: new_call.arg_terms.len() as u32,
+                    },
+                    new_call.span,
+                );
+                Ok(new_call.class_type().into_type())
+            }
+            Term::Cast(cast) => {
+                let to_type = cast.to_type().into_type();
+                self.term_against(&cast.term, &to_type)?;
+                let class = self.class_id(&cast.to_class_name, cast.span)?;
+                self.emit(Instr::CheckCast(class), cast.span);
+                Ok(to_type)
+            }
+            Term::Lambda(Lambda { span, .. }) => Err(TypingError::LambdaWithoutTarget(*span))?,
+            Term::Literal(Literal { value, span }) => {
+                self.constant(value, *span);
+                Ok(value.primitive_type().into_type())
+            }
+            // `lhs && rhs` is `if (lhs) rhs else false`, `lhs || rhs` is
+            // `if (lhs) true else rhs`
+            Term::BinaryOp(BinaryOp { op, lhs, rhs, span })
+                if matches!(op, BinaryOperator::And | BinaryOperator::Or) =>
+            {
+                self.term(lhs)?;
+                let to_else = self.emit_jump(Instr::JumpIfFalse(0), *span);
+                if *op == BinaryOperator::And {
+                    self.term(rhs)?;
+                } else {
+                    self.constant(&Primitive::Boolean(true), *span);
+                }
+                let to_end = self.emit_jump(Instr::Jump(0), *span);
+                self.patch(to_else);
+                if *op == BinaryOperator::And {
+                    self.constant(&Primitive::Boolean(false), *span);
+                } else {
+                    self.term(rhs)?;
+                }
+                self.patch(to_end);
+                Ok(PrimitiveType::Boolean.into_type())
+            }
+            Term::BinaryOp(BinaryOp { op, lhs, rhs, span }) => {
+                let lhs_type = self.term(lhs)?;
+                let rhs_type = self.term(rhs)?;
+                let ty = typecheck::binary_op_type(*op, &lhs_type, &rhs_type).ok_or_else(|| {
+                    TypingError::InvalidBinaryOperands(*op, lhs_type, rhs_type, *span)
+                })?;
+                self.emit(Instr::BinaryOp(*op), *span);
+                Ok(ty.into_type())
+            }
+            Term::UnaryOp(UnaryOp { op, term, span }) => {
+                let term_type = self.term(term)?;
+                let ty = typecheck::unary_op_type(*op, &term_type)
+                    .ok_or_else(|| TypingError::InvalidUnaryOperand(*op, term_type, *span))?;
+                self.emit(Instr::UnaryOp(*op), *span);
+                Ok(ty.into_type())
+            }
+            Term::If(if_term) => self.if_term(if_term, None),
+            Term::Let(let_term) => self.let_term(let_term, None),
+            Term::Seq(seq) => self.seq(seq, None),
+            Term::FieldAssign(FieldAssign { span, .. }) => {
+                Err(EvalError::AssignmentWithoutHeap(*span))?
+            }
+            Term::Location(Location { id, span }) => Err(TypingError::HeapLocation(*id, *span))?,
+            Term::Throw(Throw {
+                term: exception,
+                span,
+            }) => {
+                let ty = self.term(exception)?;
+                self.emit(Instr::Throw, *span);
+                Ok(ty)
+            }
+            Term::Try(try_term) => self.try_term(try_term, None),
+        }
+    }
+
+    /// Emit code for `term` where a term of type `expected` is required, which
+    /// is the type a lambda is compiled against.
+    fn term_against(&mut self, term: &Term, expected: &Type) -> Result<Type> {
+        match term {
+            Term::Lambda(lambda) => {
+                self.lambda(lambda, expected)?;
+                Ok(expected.clone())
+            }
+            Term::If(if_term) => self.if_term(if_term, Some(expected)),
+            Term::Let(let_term) => self.let_term(let_term, Some(expected)),
+            Term::Seq(seq) => self.seq(seq, Some(expected)),
+            Term::Throw(_) => {
+                self.term(term)?;
+                Ok(expected.clone())
+            }
+            Term::Try(try_term) => self.try_term(try_term, Some(expected)),
+            term => self.term(term),
+        }
+    }
+
+    /// Emit code for `if_term`, jumping over the branch not taken. With
+    /// `expected`, the branches are compiled against it.
+    fn if_term(&mut self, if_term: &If, expected: Option<&Type>) -> Result<Type> {
+        let If {
+            condition,
+            then_term,
+            else_term,
+            span,
+        } = if_term;
+        self.term(condition)?;
+        let to_else = self.emit_jump(Instr::JumpIfFalse(0), *span);
+        let then_type = match expected {
+            Some(expected) => self.term_against(then_term, expected)?,
+            None => self.term(then_term)?,
+        };
+        let to_end = self.emit_jump(Instr::Jump(0), *span);
+        self.patch(to_else);
+        let else_type = match expected {
+            Some(expected) => self.term_against(else_term, expected)?,
+            None => self.term(else_term)?,
+        };
+        self.patch(to_end);
+        // a branch throwing an exception takes the type of the other one
+        Ok(match (&**then_term, &**else_term) {
+            (Term::Throw(_), _) => else_type,
+            (_, Term::Throw(_)) => then_type,
+            _ => typecheck::join(self.ct, &self.delta, &then_type, &else_type),
+        })
+    }
+
+    /// Emit code for `let_term`, storing the value in a new local, which is in
+    /// scope for the body. With `expected`, the body is compiled against it.
+    fn let_term(&mut self, let_term: &Let, expected: Option<&Type>) -> Result<Type> {
+        let Let {
+            ty,
+            name,
+            value_term,
+            body,
+            span,
+        } = let_term;
+        self.term_against(value_term, ty)?;
+        let i = self.locals.len();
+        self.emit(Instr::Store(i as u32), *span);
+        self.locals.push((name.clone(), ty.clone()));
+        self.max_locals = self.max_locals.max(self.locals.len());
+        let body_type = match expected {
+            Some(expected) => self.term_against(body, expected),
+            None => self.term(body),
+        };
+        self.locals.truncate(i);
+        body_type
+    }
+
+    /// Emit code for `try_term`: the handler of the body tries the `CATCH` of
+    /// every clause in order and throws the exception again if none matches.
+    /// With `expected`, the blocks are compiled against it. Like for an `if`,
+    /// blocks throwing an exception do not contribute to the type.
+    fn try_term(&mut self, try_term: &Try, expected: Option<&Type>) -> Result<Type> {
+        let Try {
+            body,
+            catches,
+            span,
+        } = try_term;
+        let to_handler = self.emit_jump(Instr::Try(0), *span);
+        let body_type = match expected {
+            Some(expected) => self.term_against(body, expected)?,
+            None => self.term(body)?,
+        };
+        let mut ty = (!matches!(**body, Term::Throw(_))).then(|| body_type.clone());
+        self.emit(Instr::EndTry, *span);
+        let mut to_end = vec![self.emit_jump(Instr::Jump(0), *span)];
+        self.patch(to_handler);
+        for Catch {
+            class_name,
+            name,
+            body,
+            span,
+        } in catches
+        {
+            let class = self.class_id(class_name, *span)?;
+            let i = self.locals.len();
+            let to_next = self.emit_jump(
+                Instr::Catch {
+                    class,
+                    local: i as u32,
+                    next: 0,
+                },
+                *span,
+            );
+            self.locals
+                .push((name.clone(), ClassType::new(class_name.clone()).into_type()));
+            self.max_locals = self.max_locals.max(self.locals.len());
+            let catch_type = match expected {
+                Some(expected) => self.term_against(body, expected),
+                None => self.term(body),
+            };
+            self.locals.truncate(i);
+            let catch_type = catch_type?;
+            ty = match ty {
+                _ if matches!(**body, Term::Throw(_)) => ty,
+                None => Some(catch_type),
+                Some(ty) => Some(typecheck::join(self.ct, &self.delta, &ty, &catch_type)),
+            };
+            to_end.push(self.emit_jump(Instr::Jump(0), *span));
+            self.patch(to_next);
+        }
+        self.emit(Instr::Rethrow, *span);
+        for at in to_end {
+            self.patch(at);
+        }
+        Ok(ty.unwrap_or(body_type))
+    }
+
+    /// Emit code for `seq`, discarding the value of its first term. With
+    /// `expected`, the second term is compiled against it.
+    fn seq(&mut self, seq: &Seq, expected: Option<&Type>) -> Result<Type> {
+        let Seq {
+            first,
+            second,
+            span,
+        } = seq;
+        self.term(first)?;
+        self.emit(Instr::Pop, *span);
+        match expected {
+            Some(expected) => self.term_against(second, expected),
+            None => self.term(second),
+        }
+    }
+
+    /// Compile the body of `lambda` against the functional interface `target`
+    /// and emit code creating an instance of its class, storing the captured
+    /// variables in its fields.
+    fn lambda(&mut self, lambda: &Lambda, target: &Type) -> Result<()> {
+        let Lambda {
+            args, body, span, ..
+        } = lambda;
+        let class_type = self
+            .delta
+            .bound(target)
+            .ok_or_else(|| TypingError::NotAFunctionalInterface(target.clone(), *span))?;
+        let (method_name, method_type) = typecheck::functional_method(self.ct, &class_type)
+            .ok_or_else(|| TypingError::NotAFunctionalInterface(target.clone(), *span))?;
+        let mut captured = Vec::new();
+        free_variables(
+            body,
+            &mut args.iter().map(|(_, x)| x.clone()).collect(),
+            &mut captured,
+        );
+        let captured = captured
+            .into_iter()
+            .map(|name| {
+                let ty = self
+                    .gamma()
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| TypingError::VariableNotInGamma(name.clone(), *span))?;
+                Ok((name, ty))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // local 0 is the lambda itself, which can not be referred to by name
+        let locals = std::iter::once((FieldName("<lambda>".into()), target.clone()))
+            .chain(args.iter().map(|(t, x)| (x.clone(), t.clone())))
+            .collect();
+        let mut compiler =
+            FunctionCompiler::new(self.program, self.ct, "", locals, self.delta.clone());
+        compiler.captured = captured.clone();
+        compiler.lambdas = std::mem::take(&mut self.lambdas);
+        compiler.term_against(body, &method_type.ret_type)?;
+        let (mut function, lambdas) = compiler.finish(*span);
+        self.lambdas = lambdas;
+
+        // the id follows those of the lambdas in the body
+        let class = ClassId((self.program.classes.len() + self.lambdas.len()) as u32);
+        let class_name = ClassName(format!("{}$lambda{}", class_type.name, class.0));
+        function.name = format!("{}.{}", class_name, method_name);
+        for (name, _) in &captured {
+            self.term(
+                &Variable {
+                    name: name.clone(),
+                    span: *span,
+                }
+                .into_term(),
+            )?;
+        }
+        self.emit(
+            Instr::New {
+                class,
+                argc: captured.len() as u32,
+            },
+            *span,
+        );
+        self.lambdas.push(LambdaClass {
+            name: class_name,
+            info: LambdaInfo {
+                lambda: Lambda {
+                    target: Some(class_type.name),
+                    ..lambda.clone()
+                },
+                captured: captured.into_iter().map(|(name, _)| name).collect(),
+            },
+            method_name,
+            function,
+        });
+        Ok(())
+    }
+}
+
+/// Add the variables occurring free in `term` to `free`, in the order of their
+/// first occurrence, except those in `bound`.
+fn free_variables(term: &Term, bound: &mut Vec<FieldName>, free: &mut Vec<FieldName>) {
+    match term {
+        Term::Variable(Variable { name, .. }) => {
+            if !bound.contains(name) && !free.contains(name) {
+                free.push(name.clone());
+            }
+        }
+        Term::FieldAccess(FieldAccess { object_term, .. }) => {
+            free_variables(object_term, bound, free)
+        }
+        Term::MethodCall(MethodCall {
+            object_term,
+            arg_terms,
+            ..
+        }) => {
+            free_variables(object_term, bound, free);
+            for arg_term in arg_terms {
+                free_variables(arg_term, bound, free);
+            }
+        }
+        Term::NewCall(NewCall { arg_terms, .. }) => {
+            for arg_term in arg_terms {
+                free_variables(arg_term, bound, free);
+            }
+        }
+        Term::Cast(Cast { term, .. }) => free_variables(term, bound, free),
+        Term::Lambda(Lambda { args, body, .. }) => {
+            let len = bound.len();
+            bound.extend(args.iter().map(|(_, arg_name)| arg_name.clone()));
+            free_variables(body, bound, free);
+            bound.truncate(len);
+        }
+        Term::Literal(_) => {}
+        Term::BinaryOp(BinaryOp { lhs, rhs, .. }) => {
+            free_variables(lhs, bound, free);
+            free_variables(rhs, bound, free);
+        }
+        Term::UnaryOp(UnaryOp { term, .. }) => free_variables(term, bound, free),
+        Term::If(If {
+            condition,
+            then_term,
+            else_term,
+            ..
+        }) => {
+            free_variables(condition, bound, free);
+            free_variables(then_term, bound, free);
+            free_variables(else_term, bound, free);
+        }
+        Term::FieldAssign(FieldAssign {
+            object_term,
+            value_term,
+            ..
+        }) => {
+            free_variables(object_term, bound, free);
+            free_variables(value_term, bound, free);
+        }
+        Term::Let(Let {
+            name,
+            value_term,
+            body,
+            ..
+        }) => {
+            free_variables(value_term, bound, free);
+            bound.push(name.clone());
+            free_variables(body, bound, free);
+            bound.pop();
+        }
+        Term::Seq(Seq { first, second, .. }) => {
+            free_variables(first, bound, free);
+            free_variables(second, bound, free);
+        }
+        Term::Location(_) => {}
+        Term::Throw(Throw { term, .. }) => free_variables(term, bound, free),
+        Term::Try(Try { body, catches, .. }) => {
+            free_variables(body, bound, free);
+            for Catch { name, body, .. } in catches {
+                bound.push(name.clone());
+                free_variables(body, bound, free);
+                bound.pop();
+            }
+        }
+    }
+}
+
+impl Program {
+    /// Disassemble `function`, resolving class and method names.
+    pub fn display_function<'a>(&'a self, function: &'a Function) -> impl Display + 'a {
+        DisplayFunction {
+            program: self,
+            function,
+        }
+    }
+}
+
+struct DisplayFunction<'a> {
+    program: &'a Program,
+    function: &'a Function,
+}
+
+impl Display for DisplayFunction<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let program = self.program;
+        writeln!(
+            f,
+            "{} ({} locals):",
+            &self.function.name, self.function.locals
+        )?;
+        for (pc, instr) in self.function.code.iter().enumerate() {
+            write!(f, "  {:4}  ", pc)?;
+            match instr {
+                Instr::Load(i) => writeln!(f, "LOAD {}", i)?,
+                Instr::Store(i) => writeln!(f, "STORE {}", i)?,
+                Instr::Pop => writeln!(f, "POP")?,
+                Instr::New { class, argc } => {
+                    writeln!(f, "NEW {} {}", &program.class(*class).name, argc)?
+                }
+                Instr::GetField(i) => writeln!(f, "GETFIELD {}", i)?,
+                Instr::LoadCaptured(i) => writeln!(f, "LOADCAPTURED {}", i)?,
+                Instr::InvokeVirtual { slot, argc } => {
+                    writeln!(f, "INVOKEVIRTUAL {} {}", slot, argc)?
+                }
+                Instr::InvokeInterface { selector, argc } => writeln!(
+                    f,
+                    "INVOKEINTERFACE {} {}",
+                    &program.selectors[*selector as usize], argc
+                )?,
+                Instr::CheckCast(class) => {
+                    writeln!(f, "CHECKCAST {}", &program.class(*class).name)?
+                }
+                Instr::Const(i) => writeln!(f, "CONST {}", &self.function.constants[*i as usize])?,
+                Instr::BinaryOp(op) => writeln!(f, "BINOP {}", op)?,
+                Instr::UnaryOp(op) => writeln!(f, "UNOP {}", op)?,
+                Instr::JumpIfFalse(target) => writeln!(f, "JUMPIFFALSE {}", target)?,
+                Instr::Jump(target) => writeln!(f, "JUMP {}", target)?,
+                Instr::Return => writeln!(f, "RETURN")?,
+                Instr::Throw => writeln!(f, "THROW")?,
+                Instr::Rethrow => writeln!(f, "RETHROW")?,
+                Instr::Try(target) => writeln!(f, "TRY {}", target)?,
+                Instr::EndTry => writeln!(f, "ENDTRY")?,
+                Instr::Catch { class, local, next } => writeln!(
+                    f,
+                    "CATCH {} {} {}",
+                    &program.class(*class).name,
+                    local,
+                    next
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for class in &self.classes {
+            if class.is_interface {
+                writeln!(f, "interface {}", &class.name)?;
+                continue;
+            }
+            write!(f, "class {}", &class.name)?;
+            if let Some(super_class) = class.super_class {
+                write!(f, " extends {}", &self.class(super_class).name)?;
+            }
+            if !class.interfaces.is_empty() {
+                let interfaces: Vec<_> = class
+                    .interfaces
+                    .iter()
+                    .map(|interface| self.class(*interface).name.to_string())
+                    .collect();
+                write!(f, " implements {}", interfaces.join(", "))?;
+            }
+            writeln!(f, " ({} fields)", class.field_count)?;
+            let mut slots = class.slots.iter().collect::<Vec<_>>();
+            slots.sort_by_key(|(_, slot)| **slot);
+            for (method_name, slot) in slots {
+                match class.vtable[*slot as usize] {
+                    Some(function) => writeln!(
+                        f,
+                        "  vtable {:3}  {} -> {}",
+                        slot,
+                        method_name,
+                        &self.function(function).name
+                    )?,
+                    None => writeln!(f, "  vtable {:3}  {} abstract", slot, method_name)?,
+                }
+            }
+        }
+        for function in &self.functions {
+            writeln!(f)?;
+            write!(f, "{}", self.display_function(function))?;
+        }
+        Ok(())
+    }
+}
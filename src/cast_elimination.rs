@@ -0,0 +1,634 @@
+//! Static elimination of casts that can never fail.
+//!
+//! The evaluators check every cast `(C) e` at runtime. Such a check can be
+//! dropped if every object `e` may evaluate to is an instance of a subclass
+//! of `C`. The classes of these objects are approximated by a flow analysis
+//! of the whole program, i.e. the class table together with all terms that
+//! are evaluated against it. Starting from the terms `new D(ē)`, it follows
+//! objects into the fields they are stored in, into the arguments of the
+//! methods they are passed to and out of the methods returning them. Calls
+//! are resolved using the class hierarchy, each receiver class running the
+//! method it inherits or overrides, until no more classes flow anywhere.
+//!
+//! A lambda is represented by the interface it is typed against, which it is
+//! decorated with in the rewritten program. As the analysis does not follow
+//! lambdas into the calls of their method, their parameters and the results
+//! of these calls may be of any class. Neither are exceptions followed from
+//! `throw` to `catch`, so the variable of a `catch` clause may be of any
+//! subclass of its class.
+//!
+//! Removing a downcast weakens the static type of a term, so the rewritten
+//! program is in general no longer well-typed. It can still be run by the
+//! evaluators working on terms, which only look at the runtime classes of
+//! objects, but not be compiled by [`crate::bytecode`].
+
+use crate::ast::*;
+use crate::class_table::ClassTable;
+use crate::error::{TypingError, TypingWarning};
+use crate::typecheck::{self, ArgType, Gamma, TypecheckConfig};
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Classes a term may evaluate to an instance of, and the interfaces of the
+/// lambdas it may evaluate to.
+type Classes = BTreeSet<ClassName>;
+
+/// Classes of `this`, the method arguments and the local variables, the
+/// innermost first.
+type Env = Vec<(FieldName, Classes)>;
+
+/// A rewritten term with its static type and the classes it may evaluate to.
+type Rewritten = (Term, Type, Classes);
+
+/// A method `m` defined in class `D`, as `(D, m)`.
+type MethodKey = (ClassName, MethodName);
+
+pub struct CastElimination<'a> {
+    ct: &'a ClassTable,
+    /// Classes of the objects stored in field `f` of an instance of class
+    /// `D`, by `(D, f)`.
+    fields: BTreeMap<(ClassName, FieldName), Classes>,
+    /// Classes of `this` and the arguments a method is called with.
+    args: BTreeMap<MethodKey, Vec<Classes>>,
+    /// Classes of the objects a method may return.
+    returns: BTreeMap<MethodKey, Classes>,
+    /// Whether any of the above grew during the current pass.
+    changed: bool,
+}
+
+/// Outcome of eliminating the casts of a class table or term.
+#[derive(Debug, Clone, Default)]
+pub struct CastReport {
+    /// Number of casts removed.
+    pub eliminated: usize,
+    /// Every cast that was kept, as it may fail at runtime.
+    pub remaining: Vec<TypingWarning>,
+}
+
+impl<'a> CastElimination<'a> {
+    /// Analyse the program made of the typechecked classes of `ct` and the
+    /// typechecked terms `terms` evaluated against them.
+    ///
+    /// The result is only valid for these terms, so every term later passed
+    /// to [`CastElimination::term`] must be among `terms`.
+    pub fn new<'t>(ct: &'a ClassTable, terms: impl IntoIterator<Item = &'t Term>) -> Result<Self> {
+        let terms: Vec<_> = terms.into_iter().collect();
+        let mut elimination = CastElimination {
+            ct,
+            fields: BTreeMap::new(),
+            args: BTreeMap::new(),
+            returns: BTreeMap::new(),
+            changed: true,
+        };
+        while elimination.changed {
+            elimination.changed = false;
+            let report = &mut CastReport::default();
+            for class in ct.inner().values() {
+                for method in class.methods.iter() {
+                    elimination.method_body(class, method, report)?;
+                }
+            }
+            for term in terms.iter() {
+                elimination.term_in(&Gamma::empty(), &Env::new(), term, report)?;
+            }
+        }
+        Ok(elimination)
+    }
+
+    /// The class table with the casts that can never fail removed from all
+    /// method bodies.
+    pub fn class_table(&mut self) -> Result<(ClassTable, CastReport)> {
+        let mut report = CastReport::default();
+        let ct = self
+            .ct
+            .try_map_method_bodies(|class, method| self.method_body(class, method, &mut report))?;
+        Ok((ct, report))
+    }
+
+    /// The closed term `term` with the casts that can never fail removed.
+    pub fn term(&mut self, term: &Term) -> Result<(Term, CastReport)> {
+        let mut report = CastReport::default();
+        let (term, _, _) = self.term_in(&Gamma::empty(), &Env::new(), term, &mut report)?;
+        Ok((term, report))
+    }
+
+    fn method_body(
+        &mut self,
+        class: &ClassDefinition,
+        method: &MethodDefinition,
+        report: &mut CastReport,
+    ) -> Result<Term> {
+        let key = (class.name.clone(), method.method_name.clone());
+        let args = self
+            .args
+            .get(&key)
+            .cloned()
+            .unwrap_or_else(|| vec![Classes::new(); method.args.len() + 1]);
+        let env = std::iter::once(FieldName("this".into()))
+            .chain(method.args.iter().map(|(_, arg_name)| arg_name.clone()))
+            .zip(args)
+            .collect();
+        let gamma = Gamma::from_class_method(class, method);
+        let (term, _, classes) = self.term_against(
+            &gamma,
+            &env,
+            &method.return_term,
+            &method.return_type,
+            report,
+        )?;
+        self.changed |= join(self.returns.entry(key).or_default(), &classes);
+        Ok(term)
+    }
+
+    /// Rewrite `term`, compute its static type and the classes it may
+    /// evaluate to, and record where its subterms flow.
+    fn term_in(
+        &mut self,
+        gamma: &Gamma,
+        env: &Env,
+        term: &Term,
+        report: &mut CastReport,
+    ) -> Result<Rewritten> {
+        let delta = gamma.delta();
+        match term {
+            Term::Variable(Variable { name, span }) => {
+                let ty = gamma
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| TypingError::VariableNotInGamma(name.clone(), *span))?;
+                let classes = env
+                    .iter()
+                    .find(|(var, _)| var == name)
+                    .map(|(_, classes)| classes.clone())
+                    .unwrap_or_default();
+                Ok((term.clone(), ty, classes))
+            }
+            Term::FieldAccess(FieldAccess {
+                object_term,
+                field,
+                span,
+            }) => {
+                let (object_term, object_type, objects) =
+                    self.term_in(gamma, env, object_term, report)?;
+                let ty = typecheck::field_type(self.ct, delta, &object_type, field, *span)?;
+                let classes = objects
+                    .into_iter()
+                    .filter_map(|class_name| self.fields.get(&(class_name, field.clone())))
+                    .flatten()
+                    .cloned()
+                    .collect();
+                let term = FieldAccess {
+                    object_term: object_term.boxed(),
+                    field: field.clone(),
+                    span: *span,
+                };
+                Ok((term.into_term(), ty, classes))
+            }
+            Term::MethodCall(method_call) => {
+                let (object_term, object_type, receivers) =
+                    self.term_in(gamma, env, &method_call.object_term, report)?;
+                let arg_terms = &method_call.arg_terms;
+                // the parameter types lambdas are typed against are only
+                // known once the type arguments are inferred
+                let (ty, args) = if arg_terms.iter().any(|arg| matches!(**arg, Term::Lambda(_))) {
+                    let arg_types = typecheck::arg_types(
+                        self.ct,
+                        &TypecheckConfig::default(),
+                        gamma,
+                        arg_terms,
+                    )?;
+                    let method_type = typecheck::method_call_type(
+                        self.ct,
+                        delta,
+                        &object_type,
+                        method_call,
+                        &arg_types,
+                    )?;
+                    let args = arg_terms
+                        .iter()
+                        .zip(&method_type.arg_types)
+                        .map(|(arg_term, param_type)| {
+                            self.term_against(gamma, env, arg_term, param_type, report)
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    (method_type.ret_type, args)
+                } else {
+                    let args = self.terms_in(gamma, env, arg_terms, report)?;
+                    let arg_types: Vec<_> = args
+                        .iter()
+                        .map(|(_, ty, _)| ArgType::Term(ty.clone()))
+                        .collect();
+                    let method_type = typecheck::method_call_type(
+                        self.ct,
+                        delta,
+                        &object_type,
+                        method_call,
+                        &arg_types,
+                    )?;
+                    (method_type.ret_type, args)
+                };
+                let mut classes = Classes::new();
+                for receiver in receivers {
+                    if self.ct.is_interface(&receiver) {
+                        classes.extend(self.all_classes());
+                        continue;
+                    }
+                    let Some(defining_class) =
+                        self.defining_class(&method_call.method_name, &receiver)
+                    else {
+                        continue;
+                    };
+                    let key = (defining_class, method_call.method_name.clone());
+                    let method_args = self
+                        .args
+                        .entry(key.clone())
+                        .or_insert_with(|| vec![Classes::new(); args.len() + 1]);
+                    self.changed |= join(&mut method_args[0], &Classes::from([receiver]));
+                    for (method_arg, (_, _, classes)) in method_args[1..].iter_mut().zip(&args) {
+                        self.changed |= join(method_arg, classes);
+                    }
+                    classes.extend(self.returns.get(&key).into_iter().flatten().cloned());
+                }
+                let term = MethodCall {
+                    object_term: object_term.boxed(),
+                    arg_terms: args.into_iter().map(|(term, _, _)| term.boxed()).collect(),
+                    ..method_call.clone()
+                };
+                Ok((term.into_term(), ty, classes))
+            }
+            Term::NewCall(new_call) => {
+                let NewCall {
+                    class_name, span, ..
+                } = new_call;
+                let fields = typecheck::fields_of(self.ct, &new_call.class_type())
+                    .ok_or_else(|| TypingError::UndefinedClass(class_name.clone(), *span))?;
+                let args = new_call
+                    .arg_terms
+                    .iter()
+                    .zip(&fields)
+                    .map(|(arg_term, (field_type, _))| {
+                        self.term_against(gamma, env, arg_term, field_type, report)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let field_names = fields.into_iter().map(|(_, field_name)| field_name);
+                for (field_name, (_, _, classes)) in field_names.zip(&args) {
+                    let field = self
+                        .fields
+                        .entry((class_name.clone(), field_name))
+                        .or_default();
+                    self.changed |= join(field, classes);
+                }
+                let term = NewCall {
+                    arg_terms: args.into_iter().map(|(term, _, _)| term.boxed()).collect(),
+                    ..new_call.clone()
+                };
+                Ok((
+                    term.into_term(),
+                    new_call.class_type().into_type(),
+                    Classes::from([class_name.clone()]),
+                ))
+            }
+            Term::Cast(cast) => {
+                let to = cast.to_type();
+                let (object_term, object_type, objects) =
+                    self.term_against(gamma, env, &cast.term, &to.clone().into_type(), report)?;
+                let (passing, failing): (Classes, Classes) = objects
+                    .into_iter()
+                    .partition(|class_name| self.ct.is_subtype(class_name, &to.name) == Some(true));
+                if failing.is_empty() {
+                    report.eliminated += 1;
+                    return Ok((object_term, to.into_type(), passing));
+                }
+                report.remaining.push(TypingWarning::CastMayFail {
+                    from: object_type,
+                    to: to.clone(),
+                    failing: failing.into_iter().collect(),
+                    span: cast.span,
+                });
+                let term = Cast {
+                    term: object_term.boxed(),
+                    ..cast.clone()
+                };
+                Ok((term.into_term(), to.into_type(), passing))
+            }
+            Term::Lambda(Lambda { span, .. }) => Err(TypingError::LambdaWithoutTarget(*span))?,
+            // primitive values are not objects of any class
+            Term::Literal(Literal { value, .. }) => Ok((
+                term.clone(),
+                value.primitive_type().into_type(),
+                Classes::new(),
+            )),
+            Term::BinaryOp(binary_op) => {
+                let (lhs, lhs_type, _) = self.term_in(gamma, env, &binary_op.lhs, report)?;
+                let (rhs, rhs_type, _) = self.term_in(gamma, env, &binary_op.rhs, report)?;
+                let ty = typecheck::binary_op_type(binary_op.op, &lhs_type, &rhs_type).ok_or_else(
+                    || {
+                        TypingError::InvalidBinaryOperands(
+                            binary_op.op,
+                            lhs_type,
+                            rhs_type,
+                            binary_op.span,
+                        )
+                    },
+                )?;
+                let term = BinaryOp {
+                    lhs: lhs.boxed(),
+                    rhs: rhs.boxed(),
+                    ..binary_op.clone()
+                };
+                Ok((term.into_term(), ty.into_type(), Classes::new()))
+            }
+            Term::UnaryOp(unary_op) => {
+                let (arg, arg_type, _) = self.term_in(gamma, env, &unary_op.term, report)?;
+                let ty = typecheck::unary_op_type(unary_op.op, &arg_type).ok_or_else(|| {
+                    TypingError::InvalidUnaryOperand(unary_op.op, arg_type, unary_op.span)
+                })?;
+                let term = UnaryOp {
+                    term: arg.boxed(),
+                    ..unary_op.clone()
+                };
+                Ok((term.into_term(), ty.into_type(), Classes::new()))
+            }
+            Term::If(if_term) => self.if_in(gamma, env, if_term, None, report),
+            Term::FieldAssign(field_assign) => {
+                let FieldAssign {
+                    object_term,
+                    field,
+                    value_term,
+                    span,
+                } = field_assign;
+                let (object_term, object_type, objects) =
+                    self.term_in(gamma, env, object_term, report)?;
+                let ty = typecheck::field_type(self.ct, delta, &object_type, field, *span)?;
+                let (value_term, _, classes) =
+                    self.term_against(gamma, env, value_term, &ty, report)?;
+                for class_name in objects {
+                    let field = self.fields.entry((class_name, field.clone())).or_default();
+                    self.changed |= join(field, &classes);
+                }
+                let term = FieldAssign {
+                    object_term: object_term.boxed(),
+                    value_term: value_term.boxed(),
+                    ..field_assign.clone()
+                };
+                Ok((term.into_term(), ty, classes))
+            }
+            Term::Let(let_term) => self.let_in(gamma, env, let_term, None, report),
+            Term::Seq(seq) => self.seq_in(gamma, env, seq, None, report),
+            Term::Location(Location { id, span }) => Err(TypingError::HeapLocation(*id, *span))?,
+            // `throw t` does not evaluate to any object
+            Term::Throw(throw) => {
+                let (exception, ty, _) = self.term_in(gamma, env, &throw.term, report)?;
+                let term = Throw {
+                    term: exception.boxed(),
+                    span: throw.span,
+                };
+                Ok((term.into_term(), ty, Classes::new()))
+            }
+            Term::Try(try_term) => self.try_in(gamma, env, try_term, None, report),
+        }
+    }
+
+    /// Rewrite the value and body of `let_term`, binding the local variable to
+    /// the classes of the value in the body. With `expected`, the body is
+    /// rewritten against it.
+    fn let_in(
+        &mut self,
+        gamma: &Gamma,
+        env: &Env,
+        let_term: &Let,
+        expected: Option<&Type>,
+        report: &mut CastReport,
+    ) -> Result<Rewritten> {
+        let (value_term, _, classes) =
+            self.term_against(gamma, env, &let_term.value_term, &let_term.ty, report)?;
+        let gamma = gamma.with_args(&[(let_term.ty.clone(), let_term.name.clone())]);
+        let env: Env = std::iter::once((let_term.name.clone(), classes))
+            .chain(env.iter().cloned())
+            .collect();
+        let (body, ty, classes) = match expected {
+            Some(expected) => self.term_against(&gamma, &env, &let_term.body, expected, report)?,
+            None => self.term_in(&gamma, &env, &let_term.body, report)?,
+        };
+        let term = Let {
+            value_term: value_term.boxed(),
+            body: body.boxed(),
+            ..let_term.clone()
+        };
+        Ok((term.into_term(), ty, classes))
+    }
+
+    /// Rewrite both terms of `seq`, which evaluates to the classes of the
+    /// second one. With `expected`, that is rewritten against it.
+    fn seq_in(
+        &mut self,
+        gamma: &Gamma,
+        env: &Env,
+        seq: &Seq,
+        expected: Option<&Type>,
+        report: &mut CastReport,
+    ) -> Result<Rewritten> {
+        let (first, _, _) = self.term_in(gamma, env, &seq.first, report)?;
+        let (second, ty, classes) = match expected {
+            Some(expected) => self.term_against(gamma, env, &seq.second, expected, report)?,
+            None => self.term_in(gamma, env, &seq.second, report)?,
+        };
+        let term = Seq {
+            first: first.boxed(),
+            second: second.boxed(),
+            span: seq.span,
+        };
+        Ok((term.into_term(), ty, classes))
+    }
+
+    /// Rewrite the condition and both branches of `if_term`, which may
+    /// evaluate to the classes of either branch. With `expected`, the
+    /// branches are rewritten against it as in [`CastElimination::term_against`].
+    fn if_in(
+        &mut self,
+        gamma: &Gamma,
+        env: &Env,
+        if_term: &If,
+        expected: Option<&Type>,
+        report: &mut CastReport,
+    ) -> Result<Rewritten> {
+        let (condition, _, _) = self.term_in(gamma, env, &if_term.condition, report)?;
+        let mut branch = |term: &Term| match expected {
+            Some(expected) => self.term_against(gamma, env, term, expected, report),
+            None => self.term_in(gamma, env, term, report),
+        };
+        let (then_term, then_type, mut classes) = branch(&if_term.then_term)?;
+        let (else_term, else_type, else_classes) = branch(&if_term.else_term)?;
+        classes.extend(else_classes);
+        let term = If {
+            condition: condition.boxed(),
+            then_term: then_term.boxed(),
+            else_term: else_term.boxed(),
+            span: if_term.span,
+        };
+        // a branch throwing an exception takes the type of the other one
+        let ty = match (&*if_term.then_term, &*if_term.else_term) {
+            (Term::Throw(_), _) => else_type,
+            (_, Term::Throw(_)) => then_type,
+            _ => typecheck::join(self.ct, gamma.delta(), &then_type, &else_type),
+        };
+        Ok((term.into_term(), ty, classes))
+    }
+
+    /// Rewrite the body and all `catch` clauses of `try_term`, which may
+    /// evaluate to the classes of any of them. With `expected`, they are
+    /// rewritten against it. Blocks throwing an exception do not contribute
+    /// to the type, as for an `if`.
+    fn try_in(
+        &mut self,
+        gamma: &Gamma,
+        env: &Env,
+        try_term: &Try,
+        expected: Option<&Type>,
+        report: &mut CastReport,
+    ) -> Result<Rewritten> {
+        let ct = self.ct;
+        let mut block = |gamma: &Gamma, env: &Env, term: &Term| match expected {
+            Some(expected) => self.term_against(gamma, env, term, expected, report),
+            None => self.term_in(gamma, env, term, report),
+        };
+        let (body, body_type, mut classes) = block(gamma, env, &try_term.body)?;
+        let mut ty = (!matches!(*try_term.body, Term::Throw(_))).then(|| body_type.clone());
+        let mut catches = Vec::new();
+        for catch in &try_term.catches {
+            let class_type = ClassType::new(catch.class_name.clone()).into_type();
+            let exceptions = std::iter::once(ClassName::object())
+                .chain(ct.inner().keys().cloned())
+                .filter(|class_name| ct.is_subtype(class_name, &catch.class_name) == Some(true))
+                .collect();
+            let gamma = gamma.with_args(&[(class_type, catch.name.clone())]);
+            let env: Env = std::iter::once((catch.name.clone(), exceptions))
+                .chain(env.iter().cloned())
+                .collect();
+            let (body, catch_type, catch_classes) = block(&gamma, &env, &catch.body)?;
+            ty = match ty {
+                _ if matches!(*catch.body, Term::Throw(_)) => ty,
+                None => Some(catch_type),
+                Some(ty) => Some(typecheck::join(ct, gamma.delta(), &ty, &catch_type)),
+            };
+            classes.extend(catch_classes);
+            catches.push(Catch {
+                body: body.boxed(),
+                ..catch.clone()
+            });
+        }
+        let term = Try {
+            body: body.boxed(),
+            catches,
+            span: try_term.span,
+        };
+        Ok((term.into_term(), ty.unwrap_or(body_type), classes))
+    }
+
+    /// Like [`CastElimination::term_in`] where a term of type `expected` is
+    /// required, which is the type a lambda is typed against.
+    fn term_against(
+        &mut self,
+        gamma: &Gamma,
+        env: &Env,
+        term: &Term,
+        expected: &Type,
+        report: &mut CastReport,
+    ) -> Result<Rewritten> {
+        let lambda = match term {
+            Term::Lambda(lambda) => lambda,
+            Term::If(if_term) => return self.if_in(gamma, env, if_term, Some(expected), report),
+            Term::Let(let_term) => {
+                return self.let_in(gamma, env, let_term, Some(expected), report)
+            }
+            Term::Seq(seq) => return self.seq_in(gamma, env, seq, Some(expected), report),
+            Term::Try(try_term) => {
+                return self.try_in(gamma, env, try_term, Some(expected), report)
+            }
+            Term::Throw(_) => {
+                let (term, _, classes) = self.term_in(gamma, env, term, report)?;
+                return Ok((term, expected.clone(), classes));
+            }
+            term => return self.term_in(gamma, env, term, report),
+        };
+        let Lambda {
+            args, body, span, ..
+        } = lambda;
+        let class_type = gamma
+            .delta()
+            .bound(expected)
+            .ok_or_else(|| TypingError::NotAFunctionalInterface(expected.clone(), *span))?;
+        let (_, method_type) = typecheck::functional_method(self.ct, &class_type)
+            .ok_or_else(|| TypingError::NotAFunctionalInterface(expected.clone(), *span))?;
+        let env = args
+            .iter()
+            .map(|(_, arg_name)| (arg_name.clone(), self.all_classes()))
+            .chain(env.iter().cloned())
+            .collect();
+        let (body, _, _) = self.term_against(
+            &gamma.with_args(args),
+            &env,
+            body,
+            &method_type.ret_type,
+            report,
+        )?;
+        let term = Lambda {
+            body: body.boxed(),
+            target: Some(class_type.name.clone()),
+            ..lambda.clone()
+        };
+        Ok((
+            term.into_term(),
+            expected.clone(),
+            Classes::from([class_type.name]),
+        ))
+    }
+
+    fn terms_in(
+        &mut self,
+        gamma: &Gamma,
+        env: &Env,
+        terms: &[Box<Term>],
+        report: &mut CastReport,
+    ) -> Result<Vec<Rewritten>> {
+        terms
+            .iter()
+            .map(|term| self.term_in(gamma, env, term, report))
+            .collect()
+    }
+
+    /// Every class and interface, which a term may evaluate to if the analysis
+    /// can not tell.
+    fn all_classes(&self) -> Classes {
+        std::iter::once(ClassName::object())
+            .chain(self.ct.inner().keys().cloned())
+            .chain(self.ct.interfaces().keys().cloned())
+            .collect()
+    }
+
+    /// The class defining the method `method_name` run by instances of `class_name`.
+    fn defining_class(
+        &self,
+        method_name: &MethodName,
+        class_name: &ClassName,
+    ) -> Option<ClassName> {
+        std::iter::once(class_name)
+            .chain(self.ct.super_type_chain(class_name)?)
+            .find(|class_name| {
+                self.ct.inner().get(*class_name).is_some_and(|class| {
+                    class
+                        .methods
+                        .iter()
+                        .any(|method| &method.method_name == method_name)
+                })
+            })
+            .cloned()
+    }
+}
+
+/// Add `classes` to `into`, returning whether it grew.
+fn join(into: &mut Classes, classes: &Classes) -> bool {
+    let len = into.len();
+    into.extend(classes.iter().cloned());
+    into.len() > len
+}
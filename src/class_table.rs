@@ -1,134 +1,411 @@
 use crate::{ast::*, error::ClassTableError};
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
 
 #[derive(Debug, Clone)]
-pub struct ClassTable(BTreeMap<ClassName, ClassDefinition>);
+pub struct ClassTable {
+    classes: BTreeMap<ClassName, ClassDefinition>,
+    interfaces: BTreeMap<ClassName, InterfaceDefinition>,
+    /// What is known about `Object` and every class and interface once the
+    /// table is well-formed, see [`precompute`].
+    types: HashMap<ClassName, TypeInfo>,
+}
+
+/// The subtype relation, field layout and methods of a type, computed once
+/// when the class table is built so that subtype checks, field accesses and
+/// method dispatch need not walk the supertype chain.
+///
+/// The sets, fields and maps are persistent: a class shares them with its
+/// superclass and only stores what it adds, so deep hierarchies take memory
+/// linear in the number of members instead of quadratic in their depth.
+#[derive(Debug, Clone, Default)]
+struct TypeInfo {
+    /// For classes, the first and last position of the class and its
+    /// subclasses in a preorder traversal of the class tree, so `C <: D` iff
+    /// the interval of `C` lies within the interval of `D`.
+    interval: Option<(usize, usize)>,
+    /// The interfaces implemented by a class or its superclasses, or
+    /// extended by an interface, including their superinterfaces. An
+    /// interface is in its own set.
+    interfaces: im::HashSet<ClassName>,
+    /// Whether the class or one of its superclasses has type parameters, so
+    /// the types of its inherited members depend on the type arguments.
+    generic: bool,
+    direct_subclasses: Vec<ClassName>,
+    /// All fields, inherited fields first.
+    fields: im::Vector<ArgPair>,
+    field_indices: im::HashMap<FieldName, usize>,
+    /// For every method name, the class with the closest declaration of the
+    /// method and its index in the class' methods, or `None` if that
+    /// declaration is abstract.
+    vtable: im::HashMap<MethodName, (ClassName, Option<usize>)>,
+}
 
 impl ClassTable {
     pub fn try_from_ast(ast: Ast) -> Result<Self, ClassTableError> {
-        let mut map = BTreeMap::new();
+        Self::try_from_ast_all(ast).map_err(|errors| errors.into_iter().next().unwrap())
+    }
+
+    /// Like [`ClassTable::try_from_ast`], but reports every well-formedness
+    /// violation instead of stopping at the first one.
+    pub fn try_from_ast_all(ast: Ast) -> Result<Self, Vec<ClassTableError>> {
+        let mut errors = Vec::new();
+        let mut map: BTreeMap<ClassName, ClassDefinition> = builtin_classes()
+            .into_iter()
+            .map(|class| (class.name.clone(), class))
+            .collect();
+        let mut interfaces: BTreeMap<ClassName, InterfaceDefinition> = BTreeMap::new();
 
         // transform ast to class table
-        // and check that no class is defined more than once
+        // and check that no class or interface is defined more than once
         for class in ast.class_definitions.into_iter() {
             if class.name.is_object() {
-                Err(ClassTableError::ClassNamedObject)?;
+                errors.push(ClassTableError::ClassNamedObject(class.name_span));
+                continue;
             }
-            if let Some(c) = map.insert(class.name.clone(), class) {
-                Err(ClassTableError::ClassDefinedTwice(c.name))?;
+            if class.name.is_builtin() {
+                errors.push(ClassTableError::ClassNamedBuiltin(
+                    class.name.clone(),
+                    class.name_span,
+                ));
+                continue;
+            }
+            if let Some(primitive_type) = PrimitiveType::from_name(&class.name.0) {
+                errors.push(ClassTableError::ClassNamedPrimitive(
+                    primitive_type,
+                    class.name_span,
+                ));
+                continue;
+            }
+            match map.get(&class.name) {
+                Some(c) => errors.push(ClassTableError::ClassDefinedTwice(
+                    class.name.clone(),
+                    class.name_span,
+                    c.name_span,
+                )),
+                None => {
+                    map.insert(class.name.clone(), class);
+                }
+            }
+        }
+        for interface in ast.interface_definitions.into_iter() {
+            if interface.name.is_object() {
+                errors.push(ClassTableError::ClassNamedObject(interface.name_span));
+                continue;
+            }
+            if interface.name.is_builtin() {
+                errors.push(ClassTableError::ClassNamedBuiltin(
+                    interface.name.clone(),
+                    interface.name_span,
+                ));
+                continue;
+            }
+            if let Some(primitive_type) = PrimitiveType::from_name(&interface.name.0) {
+                errors.push(ClassTableError::ClassNamedPrimitive(
+                    primitive_type,
+                    interface.name_span,
+                ));
+                continue;
+            }
+            let first = map
+                .get(&interface.name)
+                .map(|c| c.name_span)
+                .or_else(|| interfaces.get(&interface.name).map(|i| i.name_span));
+            match first {
+                Some(first) => errors.push(ClassTableError::ClassDefinedTwice(
+                    interface.name.clone(),
+                    interface.name_span,
+                    first,
+                )),
+                None => {
+                    interfaces.insert(interface.name.clone(), interface);
+                }
             }
         }
 
-        let ct = ClassTable(map);
+        let mut ct = ClassTable {
+            classes: map,
+            interfaces,
+            types: HashMap::new(),
+        };
 
-        // check that each supertype is defined
+        // check that each supertype is defined, and is a class after `extends`
+        // and an interface after `implements`
+        let errors_before = errors.len();
         for class in ct.inner().values() {
-            let supertype = &class.super_type;
-            if !(ct.inner().contains_key(supertype) || supertype.is_object()) {
-                Err(ClassTableError::SupertypeUndefined(
+            let supertype = &class.super_type.name;
+            if ct.is_interface(supertype) {
+                errors.push(ClassTableError::ExtendsInterface(
+                    supertype.clone(),
+                    class.name.clone(),
+                    class.name_span,
+                ));
+            } else if !ct.contains_class(supertype) {
+                errors.push(ClassTableError::SupertypeUndefined(
                     supertype.clone(),
                     class.name.clone(),
-                ))?;
+                    class.name_span,
+                ));
+            }
+            for interface in &class.interfaces {
+                let error = if ct.is_interface(&interface.name) {
+                    continue;
+                } else if ct.contains_class(&interface.name) {
+                    ClassTableError::ImplementsClass(
+                        interface.name.clone(),
+                        class.name.clone(),
+                        class.name_span,
+                    )
+                } else {
+                    ClassTableError::SupertypeUndefined(
+                        interface.name.clone(),
+                        class.name.clone(),
+                        class.name_span,
+                    )
+                };
+                errors.push(error);
+            }
+        }
+        let supertypes_defined = errors.len() == errors_before;
+        let errors_before = errors.len();
+        for interface in ct.interfaces().values() {
+            for super_type in &interface.super_types {
+                let error = if ct.is_interface(&super_type.name) {
+                    continue;
+                } else if ct.contains_class(&super_type.name) {
+                    ClassTableError::InterfaceExtendsClass(
+                        super_type.name.clone(),
+                        interface.name.clone(),
+                        interface.name_span,
+                    )
+                } else {
+                    ClassTableError::SupertypeUndefined(
+                        super_type.name.clone(),
+                        interface.name.clone(),
+                        interface.name_span,
+                    )
+                };
+                errors.push(error);
+            }
+        }
+        if errors.len() == errors_before {
+            // the interfaces form a graph, a cycle can be reached from
+            // any interface on it
+            for interface in ct.interfaces().values() {
+                if ct.interface_reaches(&interface.name, &interface.name, &mut BTreeSet::new()) {
+                    errors.push(ClassTableError::CyclicInterface(
+                        interface.name.clone(),
+                        interface.name_span,
+                    ));
+                }
+            }
+        }
+        for interface in ct.interfaces().values() {
+            if !interface.has_unique_method_names() {
+                errors.push(ClassTableError::NonUniqueInterfaceMethodNames(
+                    interface.name.clone(),
+                    interface.name_span,
+                ));
+            }
+            for method in interface.methods.iter() {
+                if !method.has_unique_argument_names() {
+                    errors.push(ClassTableError::NonUniqueMethodArgumentNames(
+                        method.method_name.clone(),
+                        interface.name.clone(),
+                        method.name_span,
+                    ));
+                }
+                if !method.has_only_valid_argument_names() {
+                    errors.push(ClassTableError::MethodArgumentNamedThis(
+                        method.method_name.clone(),
+                        interface.name.clone(),
+                        method.name_span,
+                    ));
+                }
             }
         }
 
         // TODO: maybe there checks should be part of typechecking
 
         // - check that the class table is acyclic
-        // (this must be checked after the previous check)
+        // (this must be checked after the previous check,
+        // as the supertype chain can only be walked if it is complete)
         // - check that no field is defined twice in a class
         // - check that each class has a contructor with the correct name
-        // - check that a class assigns all fields in the ctor
         // - check that no method is defined twice in a class
         // - check that methods have unique argument names
         // - check that class fields are not named `this`
         // - check that method/ctor args are not named `this`
+        let acyclic = ct.classes_below_object();
         for class in ct.inner().values() {
-            if ct.super_type_chain(&class.name).unwrap().is_cyclic() {
-                Err(ClassTableError::CyclicSupertype(class.name.clone()))?;
+            if supertypes_defined && !acyclic.contains(&class.name) {
+                errors.push(ClassTableError::CyclicSupertype(
+                    class.name.clone(),
+                    class.name_span,
+                ));
             }
             if !class.has_correct_ctor_name() {
-                Err(ClassTableError::IncorrectConstructorName(
+                errors.push(ClassTableError::IncorrectConstructorName(
                     class.name.clone(),
                     class.constructor.name.clone(),
-                ))?;
-            }
-            if !class.has_correct_ctor_init() {
-                Err(ClassTableError::IncorrectContstructorInit(
-                    class.name.clone(),
-                ))?;
+                    class.constructor.span,
+                ));
             }
             if !class.has_unique_field_names() {
-                Err(ClassTableError::NonUniqueFields(class.name.clone()))?;
+                errors.push(ClassTableError::NonUniqueFields(
+                    class.name.clone(),
+                    class.name_span,
+                ));
             }
             if !class.has_unique_method_names() {
-                Err(ClassTableError::NonUniqueMethodNames(class.name.clone()))?;
+                errors.push(ClassTableError::NonUniqueMethodNames(
+                    class.name.clone(),
+                    class.name_span,
+                ));
             }
             if !class.has_only_valid_field_names() {
-                Err(ClassTableError::FieldNamedThis(class.name.clone()))?;
+                errors.push(ClassTableError::FieldNamedThis(
+                    class.name.clone(),
+                    class.name_span,
+                ));
             }
             if !class.constructor.has_only_valid_argument_names() {
-                Err(ClassTableError::ConstructorArgumentNamedThis(
+                errors.push(ClassTableError::ConstructorArgumentNamedThis(
                     class.name.clone(),
-                ))?;
+                    class.constructor.span,
+                ));
             }
 
             for method in class.methods.iter() {
                 if !method.has_unique_argument_names() {
-                    Err(ClassTableError::NonUniqueMethodArgumentNames(
+                    errors.push(ClassTableError::NonUniqueMethodArgumentNames(
                         method.method_name.clone(),
                         class.name.clone(),
-                    ))?;
+                        method.name_span,
+                    ));
                 }
                 if !method.has_only_valid_argument_names() {
-                    Err(ClassTableError::MethodArgumentNamedThis(
+                    errors.push(ClassTableError::MethodArgumentNamedThis(
                         method.method_name.clone(),
                         class.name.clone(),
-                    ))?;
+                        method.name_span,
+                    ));
+                }
+            }
+            for method in class.abstract_methods.iter() {
+                if !method.has_unique_argument_names() {
+                    errors.push(ClassTableError::NonUniqueMethodArgumentNames(
+                        method.method_name.clone(),
+                        class.name.clone(),
+                        method.name_span,
+                    ));
+                }
+                if !method.has_only_valid_argument_names() {
+                    errors.push(ClassTableError::MethodArgumentNamedThis(
+                        method.method_name.clone(),
+                        class.name.clone(),
+                        method.name_span,
+                    ));
                 }
             }
         }
 
-        // FIXME: check of correct override is not working as intended
-        // return type is ignored
-
-        Ok(ct)
+        if errors.is_empty() {
+            ct.types = precompute(&ct.classes, &ct.interfaces);
+            Ok(ct)
+        } else {
+            Err(errors)
+        }
     }
 
     pub fn inner(&self) -> &BTreeMap<ClassName, ClassDefinition> {
-        &self.0
+        &self.classes
+    }
+
+    pub fn interfaces(&self) -> &BTreeMap<ClassName, InterfaceDefinition> {
+        &self.interfaces
+    }
+
+    pub fn is_interface(&self, name: &ClassName) -> bool {
+        self.interfaces.contains_key(name)
+    }
+
+    /// Whether `from` extends `to`, directly or through other interfaces,
+    /// without visiting the interfaces in `seen` again.
+    fn interface_reaches(
+        &self,
+        from: &ClassName,
+        to: &ClassName,
+        seen: &mut BTreeSet<ClassName>,
+    ) -> bool {
+        if !seen.insert(from.clone()) {
+            return false;
+        }
+        self.interfaces.get(from).is_some_and(|interface| {
+            interface.super_types.iter().any(|super_type| {
+                &super_type.name == to || self.interface_reaches(&super_type.name, to, seen)
+            })
+        })
+    }
+
+    /// The classes whose superclass chain ends at `Object`, searched from
+    /// `Object` down so that it takes linear time. The superclass chains of
+    /// the other classes contain a cycle.
+    fn classes_below_object(&self) -> HashSet<&ClassName> {
+        let mut subclasses: HashMap<&ClassName, Vec<&ClassName>> = HashMap::new();
+        for class in self.classes.values() {
+            subclasses
+                .entry(&class.super_type.name)
+                .or_default()
+                .push(&class.name);
+        }
+        let mut below = HashSet::new();
+        let mut pending = subclasses
+            .get(&ClassName::object())
+            .cloned()
+            .unwrap_or_default();
+        while let Some(class_name) = pending.pop() {
+            if below.insert(class_name) {
+                pending.extend(subclasses.get(class_name).into_iter().flatten());
+            }
+        }
+        below
     }
 
     pub fn super_type(&self, class_name: &ClassName) -> Option<&ClassName> {
-        self.inner().get(class_name).map(|class| &class.super_type)
+        self.inner()
+            .get(class_name)
+            .map(|class| &class.super_type.name)
     }
 
     pub fn super_type_chain<'a>(&'a self, class_name: &'a ClassName) -> Option<SuperTypeChain<'a>> {
         if class_name.is_object() {
             return Some(SuperTypeChain {
-                ct: &self,
+                ct: self,
                 last: class_name,
             });
         }
         self.inner().get(class_name).map(|class| SuperTypeChain {
-            ct: &self,
+            ct: self,
             last: &class.name,
         })
     }
 
+    /// Whether `lhs` is a subclass of `rhs` or implements it, `None` if `lhs`
+    /// is not defined. Takes constant time.
     pub fn is_subtype(&self, lhs: &ClassName, rhs: &ClassName) -> Option<bool> {
-        if lhs.is_object() && rhs.is_object() {
-            return Some(true);
-        }
-        if lhs == rhs && self.contains_class(lhs) {
-            return Some(true);
-        }
-        if rhs.is_object() && self.contains_class(lhs) {
-            return Some(true);
-        }
-        // dbg!(lhs, rhs);
-        self.super_type_chain(lhs)
-            .map(|mut super_types| super_types.any(|s| s == rhs))
+        let lhs = self.types.get(lhs)?;
+        let within = |(first, _): (usize, usize), (rhs_first, rhs_last): (usize, usize)| {
+            rhs_first <= first && first <= rhs_last
+        };
+        Some(
+            rhs.is_object()
+                || lhs.interfaces.contains(rhs)
+                || lhs
+                    .interval
+                    .zip(self.types.get(rhs).and_then(|rhs| rhs.interval))
+                    .is_some_and(|(lhs, rhs)| within(lhs, rhs)),
+        )
     }
 
     pub fn contains_class(&self, class_name: &ClassName) -> bool {
@@ -139,12 +416,13 @@ impl ClassTable {
         &'a self,
         class_name: &'a ClassName,
     ) -> Option<impl Iterator<Item = &'a ClassName>> {
-        if !(self.contains_class(class_name)) {
+        if !(self.contains_class(class_name) || self.is_interface(class_name)) {
             return None;
         }
         Some(
             self.inner()
                 .keys()
+                .chain(self.interfaces.keys())
                 .filter(|&t| self.is_subtype(t, class_name).unwrap()),
         )
     }
@@ -156,98 +434,268 @@ impl ClassTable {
         if !(self.contains_class(class_name)) {
             return None;
         }
-        Some(
-            self.inner()
-                .values()
-                .filter(move |&c| &c.super_type == class_name && &c.name != class_name)
-                .map(|c| &c.name),
-        )
+        Some(self.types.get(class_name)?.direct_subclasses.iter())
+    }
+
+    /// The fields of `class_name`, inherited fields first, matching the order
+    /// of the constructor arguments.
+    pub fn fields(&self, class_name: &ClassName) -> Option<im::vector::Iter<'_, ArgPair>> {
+        if self.is_interface(class_name) {
+            return None;
+        }
+        Some(self.types.get(class_name)?.fields.iter())
+    }
+
+    /// The field at position `i` among all fields of `class_name`, as
+    /// returned by [`ClassTable::fields`].
+    pub fn field(&self, class_name: &ClassName, i: usize) -> Option<&ArgPair> {
+        if self.is_interface(class_name) {
+            return None;
+        }
+        self.types.get(class_name)?.fields.get(i)
     }
 
-    pub fn fields(
+    /// Whether `class_name` or one of its superclasses has type parameters,
+    /// so its inherited fields and methods must be instantiated.
+    pub fn is_generic(&self, class_name: &ClassName) -> bool {
+        self.types.get(class_name).is_some_and(|info| info.generic)
+    }
+
+    pub fn method_body(
         &self,
+        method_name: &MethodName,
         class_name: &ClassName,
-    ) -> Option<Box<dyn Iterator<Item = &ArgPair> + '_>> {
-        if class_name.is_object() {
-            return Some(Box::new(std::iter::empty()));
-        }
-        self.inner().get(class_name).map(|class| {
-            Box::new(
-                class
-                    .fields
-                    .iter()
-                    .chain(self.fields(self.super_type(class_name).unwrap()).unwrap()),
-            ) as Box<_>
-        })
+    ) -> Option<MethodBody> {
+        self.method_definition(method_name, class_name)
+            .map(MethodBody::from_method)
     }
 
-    pub fn method_type(
+    /// The definition of `method_name` in `class_name` or the closest supertype defining it.
+    ///
+    /// `None` if the closest declaration of the method is abstract, as there
+    /// is no body to run. Looks the method up in the vtable of `class_name`.
+    pub fn method_definition(
         &self,
         method_name: &MethodName,
         class_name: &ClassName,
-    ) -> Option<MethodType> {
-        self.inner().get(class_name).and_then(|class| {
-            match class
-                .methods
-                .iter()
-                .find(|method| &method.method_name == method_name)
-            {
-                Some(method) => Some(MethodType::from_method(method)),
-                None => self.method_type(method_name, self.super_type(class_name).unwrap()),
-            }
-        })
+    ) -> Option<&MethodDefinition> {
+        let (owner, i) = self.types.get(class_name)?.vtable.get(method_name)?;
+        self.classes.get(owner)?.methods.get((*i)?)
     }
 
-    pub fn method_body(
+    /// The class with the closest declaration of `method_name` in
+    /// `class_name` or its superclasses, abstract or not. `None` if only an
+    /// interface declares it.
+    pub fn method_owner(
         &self,
         method_name: &MethodName,
         class_name: &ClassName,
-    ) -> Option<MethodBody> {
-        self.inner().get(class_name).and_then(|class| {
-            match class
-                .methods
+    ) -> Option<&ClassName> {
+        let (owner, _) = self.types.get(class_name)?.vtable.get(method_name)?;
+        Some(owner)
+    }
+
+    /// Whether `class_name` has a method without a body: an abstract method
+    /// it declares or inherits, or a method of one of its interfaces that it
+    /// does not define. Looks at the vtable only, not at the supertypes.
+    pub fn has_methods_without_body(&self, class_name: &ClassName) -> bool {
+        let Some(info) = self.types.get(class_name) else {
+            return false;
+        };
+        info.vtable.values().any(|(_, i)| i.is_none())
+            || info
+                .interfaces
                 .iter()
-                .find(|method| &method.method_name == method_name)
-            {
-                Some(method) => Some(MethodBody::from_method(method)),
-                None => self.method_body(method_name, self.super_type(class_name).unwrap()),
+                .filter_map(|interface| self.interfaces.get(interface))
+                .flat_map(|interface| &interface.methods)
+                .any(|method| !matches!(info.vtable.get(&method.method_name), Some((_, Some(_)))))
+    }
+
+    /// Whether `class_name` is declared `abstract`.
+    pub fn is_abstract(&self, class_name: &ClassName) -> bool {
+        self.inner()
+            .get(class_name)
+            .is_some_and(|class| class.is_abstract)
+    }
+
+    /// A copy of the class table with the body of every method replaced by
+    /// `f(class, method)`, e.g. to optimize it.
+    pub fn try_map_method_bodies<E>(
+        &self,
+        mut f: impl FnMut(&ClassDefinition, &MethodDefinition) -> Result<Term, E>,
+    ) -> Result<Self, E> {
+        let mut map = self.classes.clone();
+        for (class, mapped) in self.inner().values().zip(map.values_mut()) {
+            for (method, mapped) in class.methods.iter().zip(mapped.methods.iter_mut()) {
+                mapped.return_term = f(class, method)?.boxed();
             }
+        }
+        Ok(ClassTable {
+            classes: map,
+            interfaces: self.interfaces.clone(),
+            types: self.types.clone(),
         })
     }
 
-    pub fn is_correct_method_override(
-        &self,
-        method_name: &MethodName,
-        class_name: &ClassName,
-        method_type: &MethodType,
-    ) -> Option<bool> {
-        let expected_method_type = self.method_type(method_name, class_name)?;
-        Some(
-            expected_method_type.arg_types == method_type.arg_types
-                && expected_method_type.ret_type == method_type.ret_type,
-        )
+    /// Position of `field_name` among all fields of `class_name`, as returned by [`ClassTable::fields`].
+    pub fn field_index(&self, class_name: &ClassName, field_name: &FieldName) -> Option<usize> {
+        if self.is_interface(class_name) {
+            return None;
+        }
+        self.types
+            .get(class_name)?
+            .field_indices
+            .get(field_name)
+            .copied()
     }
 }
 
-#[derive(Debug, Clone)]
+/// The [`TypeInfo`] of `Object` and every class and interface of a
+/// well-formed class table.
+///
+/// Classes are visited in preorder from `Object`, so the information of a
+/// superclass is complete before its subclasses share and extend it.
+fn precompute(
+    classes: &BTreeMap<ClassName, ClassDefinition>,
+    interfaces: &BTreeMap<ClassName, InterfaceDefinition>,
+) -> HashMap<ClassName, TypeInfo> {
+    let mut types: HashMap<ClassName, TypeInfo> = HashMap::new();
+    for interface in interfaces.keys() {
+        super_interfaces(interfaces, interface, &mut types);
+    }
+    for class in classes.values() {
+        types
+            .entry(class.super_type.name.clone())
+            .or_default()
+            .direct_subclasses
+            .push(class.name.clone());
+    }
+
+    let mut position = 0;
+    // classes whose subclasses have all been visited are popped again
+    let mut stack = vec![(ClassName::object(), false)];
+    while let Some((class_name, visited)) = stack.pop() {
+        if visited {
+            // NOTE(unwrap): the interval was set when the class was pushed back
+            let info = types.get_mut(&class_name).unwrap();
+            info.interval.as_mut().unwrap().1 = position - 1;
+            continue;
+        }
+        let mut info = types.remove(&class_name).unwrap_or_default();
+        if let Some(class) = classes.get(&class_name) {
+            let super_info = &types[&class.super_type.name];
+            info.interfaces = super_info.interfaces.clone();
+            info.fields = super_info.fields.clone();
+            info.field_indices = super_info.field_indices.clone();
+            info.vtable = super_info.vtable.clone();
+            info.generic = super_info.generic || !class.type_params.is_empty();
+            for interface in &class.interfaces {
+                info.interfaces
+                    .extend(types[&interface.name].interfaces.iter().cloned());
+            }
+            for field in &class.fields {
+                info.field_indices
+                    .entry(field.1.clone())
+                    .or_insert(info.fields.len());
+                info.fields.push_back(field.clone());
+            }
+            for method in &class.abstract_methods {
+                info.vtable
+                    .insert(method.method_name.clone(), (class.name.clone(), None));
+            }
+            for (i, method) in class.methods.iter().enumerate() {
+                info.vtable
+                    .insert(method.method_name.clone(), (class.name.clone(), Some(i)));
+            }
+        }
+        info.interval = Some((position, position));
+        position += 1;
+        stack.push((class_name.clone(), true));
+        stack.extend(
+            info.direct_subclasses
+                .iter()
+                .rev()
+                .map(|subclass| (subclass.clone(), false)),
+        );
+        types.insert(class_name, info);
+    }
+    types
+}
+
+/// The interfaces `interface` extends, directly or indirectly, and itself,
+/// memoized in `types`.
+fn super_interfaces(
+    interfaces: &BTreeMap<ClassName, InterfaceDefinition>,
+    interface: &ClassName,
+    types: &mut HashMap<ClassName, TypeInfo>,
+) -> im::HashSet<ClassName> {
+    if let Some(info) = types.get(interface) {
+        return info.interfaces.clone();
+    }
+    let mut super_interfaces = im::HashSet::unit(interface.clone());
+    for super_type in &interfaces[interface].super_types {
+        super_interfaces.extend(self::super_interfaces(interfaces, &super_type.name, types));
+    }
+    types.entry(interface.clone()).or_default().interfaces = super_interfaces.clone();
+    super_interfaces
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The type `<Ȳ ◁ P̄> Ū → U` of a method.
 pub struct MethodType {
-    pub arg_types: Vec<ClassName>,
-    pub ret_type: ClassName,
+    pub type_params: Vec<TypeParam>,
+    pub arg_types: Vec<Type>,
+    pub ret_type: Type,
 }
 
 impl MethodType {
     pub fn from_method(method: &MethodDefinition) -> Self {
         MethodType {
+            type_params: method.type_params.clone(),
+            arg_types: method
+                .args
+                .iter()
+                .map(|(arg_type, _)| arg_type.clone())
+                .collect(),
+            ret_type: method.return_type.clone(),
+        }
+    }
+    pub fn from_signature(method: &MethodSignature) -> Self {
+        MethodType {
+            type_params: method.type_params.clone(),
             arg_types: method
                 .args
                 .iter()
-                .map(|(class_name, _)| class_name.clone())
+                .map(|(arg_type, _)| arg_type.clone())
                 .collect(),
             ret_type: method.return_type.clone(),
         }
     }
 }
 
+impl Display for MethodType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.type_params.is_empty() {
+            write!(f, "<")?;
+            for (i, param) in self.type_params.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", param)?;
+            }
+            write!(f, "> ")?;
+        }
+        write!(f, "(")?;
+        for (i, arg_type) in self.arg_types.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg_type)?;
+        }
+        write!(f, ") -> {}", &self.ret_type)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MethodBody {
     pub args: Vec<FieldName>,
@@ -271,6 +719,45 @@ impl ClassName {
     pub fn is_object(&self) -> bool {
         self.0 == "Object"
     }
+    pub fn throwable() -> Self {
+        ClassName("Throwable".into())
+    }
+    pub fn class_cast_exception() -> Self {
+        ClassName("ClassCastException".into())
+    }
+    /// Whether this names one of the [`builtin_classes`].
+    pub fn is_builtin(&self) -> bool {
+        *self == Self::throwable() || *self == Self::class_cast_exception()
+    }
+}
+
+/// The classes every class table defines besides `Object`: `Throwable`, the
+/// superclass of all exceptions, and `ClassCastException`, which a failing
+/// cast throws. Neither has fields or methods.
+pub fn builtin_classes() -> Vec<ClassDefinition> {
+    let class = |name: ClassName, super_class: ClassName| ClassDefinition {
+        is_abstract: false,
+        name: name.clone(),
+        type_params: vec![],
+        super_type: ClassType::new(super_class),
+        interfaces: vec![],
+        fields: vec![],
+        constructor: Constructor {
+            name,
+            args: vec![],
+            super_call: vec![],
+            assignments: vec![],
+            span: Span::default(),
+        },
+        methods: vec![],
+        abstract_methods: vec![],
+        span: Span::default(),
+        name_span: Span::default(),
+    };
+    vec![
+        class(ClassName::throwable(), ClassName::object()),
+        class(ClassName::class_cast_exception(), ClassName::throwable()),
+    ]
 }
 
 impl FieldName {
@@ -284,18 +771,6 @@ pub struct SuperTypeChain<'a> {
     last: &'a ClassName,
 }
 
-impl<'a> SuperTypeChain<'a> {
-    fn is_cyclic(self) -> bool {
-        let mut seen = BTreeSet::new();
-        for class_name in self {
-            if !seen.insert(class_name) {
-                return true;
-            }
-        }
-        false
-    }
-}
-
 impl<'a> Iterator for SuperTypeChain<'a> {
     type Item = &'a ClassName;
 
@@ -313,35 +788,49 @@ impl ClassDefinition {
     fn has_correct_ctor_name(&self) -> bool {
         self.name == self.constructor.name
     }
-    fn has_correct_ctor_init(&self) -> bool {
-        // check that left and right side of assignments have the same field name
-        self.constructor
-            .assignments
-            .iter()
-            .all(|(lhs, rhs)| lhs == rhs);
-
-        let mut init_fields = BTreeSet::new();
-        // check no double init
-        for field_name in self
-            .constructor
-            .assignments
-            .iter()
-            .map(|(field_name, _)| field_name)
-        {
-            if !init_fields.insert(field_name) {
-                return false;
-            }
-        }
-        let init_fields = init_fields;
-        // check for doubles in below method
+    /// Check that the constructor assigns every own field exactly once,
+    /// from the parameter of the same name (`this.f = f;`).
+    pub(crate) fn ctor_init_errors(&self) -> Vec<ClassTableError> {
+        let mut errors = Vec::new();
+        let span = self.constructor.span;
         let class_fields: BTreeSet<_> = self
             .fields
             .iter()
             .map(|(_, field_name)| field_name)
             .collect();
-        let all_inited = init_fields.difference(&class_fields).count() == 0;
 
-        all_inited
+        let mut init_fields = BTreeSet::new();
+        for (lhs, rhs) in self.constructor.assignments.iter() {
+            // check that left and right side of assignments have the same field name
+            if lhs != rhs {
+                errors.push(ClassTableError::FieldAssignmentMismatch(
+                    self.name.clone(),
+                    lhs.clone(),
+                    rhs.clone(),
+                    span,
+                ));
+            }
+            if !class_fields.contains(lhs) {
+                errors.push(ClassTableError::AssignmentToUnknownField(
+                    self.name.clone(),
+                    lhs.clone(),
+                    span,
+                ));
+            }
+            // check no double init
+            else if !init_fields.insert(lhs) {
+                errors.push(ClassTableError::FieldAssignedTwice(
+                    self.name.clone(),
+                    lhs.clone(),
+                    span,
+                ));
+            }
+        }
+        // check that all fields are initialized
+        errors.extend(class_fields.difference(&init_fields).map(|field_name| {
+            ClassTableError::FieldNotAssigned(self.name.clone(), (*field_name).clone(), span)
+        }));
+        errors
     }
     fn has_unique_field_names(&self) -> bool {
         let mut seen = BTreeSet::new();
@@ -354,7 +843,11 @@ impl ClassDefinition {
     }
     fn has_unique_method_names(&self) -> bool {
         let mut seen = BTreeSet::new();
-        for method_name in self.methods.iter().map(|method| &method.method_name) {
+        for method_name in self.methods.iter().map(|method| &method.method_name).chain(
+            self.abstract_methods
+                .iter()
+                .map(|method| &method.method_name),
+        ) {
             if !seen.insert(method_name) {
                 return false;
             }
@@ -388,6 +881,36 @@ impl MethodDefinition {
     }
 }
 
+impl InterfaceDefinition {
+    fn has_unique_method_names(&self) -> bool {
+        let mut seen = BTreeSet::new();
+        for method_name in self.methods.iter().map(|method| &method.method_name) {
+            if !seen.insert(method_name) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl MethodSignature {
+    fn has_unique_argument_names(&self) -> bool {
+        let mut seen = BTreeSet::new();
+        for arg_name in self.args.iter().map(|(_, arg_name)| arg_name) {
+            if !seen.insert(arg_name) {
+                return false;
+            }
+        }
+        true
+    }
+    fn has_only_valid_argument_names(&self) -> bool {
+        self.args
+            .iter()
+            .map(|(_, arg_name)| arg_name)
+            .all(|arg_name| !arg_name.is_this())
+    }
+}
+
 impl Constructor {
     fn has_only_valid_argument_names(&self) -> bool {
         self.args
@@ -17,7 +17,7 @@ impl ClassTree {
             .unwrap()
             .map(|s| Self::new_for(ct, s).0)
             .fold(BTreeMap::default(), |mut acc, m| {
-                acc.extend(m.into_iter());
+                acc.extend(m);
                 acc
             });
         map.insert(c.clone(), Box::new(ClassTree(subtypes)));
@@ -45,9 +45,9 @@ impl<'a> Display for Printer<'a> {
         for (k, v) in self.tree.0.iter() {
             let sub_printer = Printer {
                 indent: self.indent + 2,
-                tree: &*v,
+                tree: v,
             };
-            for i in 0..self.indent {
+            for _ in 0..self.indent {
                 write!(f, " ")?;
             }
             writeln!(f, "└{}", k)?;
@@ -0,0 +1,127 @@
+use crate::ast::{FileId, Span};
+use crate::error::{
+    ClassTableError, ErrorList, EvalError, LoadError, ParseError, TypingError, TypingWarning,
+};
+use crate::source::SourceMap;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::term::{
+    self,
+    termcolor::{ColorChoice, StandardStream},
+};
+use std::error::Error;
+
+/// Location and additional labels of a single error of the error chain.
+fn error_spans(error: &(dyn Error + 'static)) -> Option<(Span, Vec<(Span, String)>)> {
+    if let Some(e) = error.downcast_ref::<ParseError>() {
+        return Some((e.span(), vec![]));
+    }
+    if let Some(e) = error.downcast_ref::<LoadError>() {
+        return Some((e.span(), vec![]));
+    }
+    if let Some(e) = error.downcast_ref::<ClassTableError>() {
+        return e.span().map(|span| (span, e.secondary_spans()));
+    }
+    if let Some(e) = error.downcast_ref::<TypingError>() {
+        return e.span().map(|span| (span, vec![]));
+    }
+    if let Some(e) = error.downcast_ref::<EvalError>() {
+        return e.span().map(|span| (span, vec![]));
+    }
+    None
+}
+
+/// Turn an error chain into a diagnostic.
+///
+/// The innermost cause becomes the headline and, if it is located, the
+/// primary label. The surrounding context errors (e.g. the method and class
+/// being checked) become notes, outermost first.
+pub fn from_error(error: &anyhow::Error) -> Diagnostic<FileId> {
+    let root = error.root_cause();
+    let mut labels = Vec::new();
+    if let Some((span, secondary)) = error_spans(root) {
+        labels.push(Label::primary(span.file, span.range()));
+        labels.extend(
+            secondary
+                .into_iter()
+                .map(|(span, m)| Label::secondary(span.file, span.range()).with_message(m)),
+        );
+    }
+    let notes = error
+        .chain()
+        .take_while(|e| !std::ptr::eq(*e, root))
+        .map(|e| e.to_string())
+        .collect();
+    Diagnostic::error()
+        .with_message(root.to_string())
+        .with_labels(labels)
+        .with_notes(notes)
+}
+
+/// Turn a typechecker warning into a diagnostic.
+pub fn from_warning(warning: &TypingWarning) -> Diagnostic<FileId> {
+    let span = warning.span();
+    let diagnostic = Diagnostic::warning()
+        .with_message(warning.to_string())
+        .with_labels(vec![Label::primary(span.file, span.range())]);
+    match warning {
+        TypingWarning::StupidCast { .. } => diagnostic.with_notes(vec![
+            "use `--stupid-casts allow` to accept or `--stupid-casts deny` to reject such casts"
+                .into(),
+        ]),
+        TypingWarning::UncheckedCast { .. } => diagnostic.with_notes(vec![
+            "the type arguments of the target type are not implied by its class".into(),
+        ]),
+        TypingWarning::CastMayFail { failing, .. } => diagnostic.with_notes(vec![format!(
+            "the term may evaluate to an instance of {}",
+            failing
+                .iter()
+                .map(|class_name| format!("`{}`", class_name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )]),
+    }
+}
+
+/// Render `diagnostic` with source snippets to stderr.
+pub fn emit(sources: &SourceMap, diagnostic: &Diagnostic<FileId>) -> anyhow::Result<()> {
+    let writer = StandardStream::stderr(ColorChoice::Auto);
+    let config = term::Config::default();
+    term::emit(&mut writer.lock(), &config, sources, diagnostic)?;
+    Ok(())
+}
+
+/// Render every error of `error` to stderr.
+///
+/// An [`ErrorList`] in the chain is expanded into one diagnostic per error,
+/// followed by a summary with the total count.
+pub fn emit_error(sources: &SourceMap, error: &anyhow::Error) -> anyhow::Result<()> {
+    match error.downcast_ref::<ErrorList>() {
+        Some(ErrorList(errors)) => {
+            for error in errors {
+                emit(sources, &from_error(error))?;
+            }
+            let summary = error
+                .chain()
+                .take_while(|e| e.downcast_ref::<ErrorList>().is_none())
+                .map(|e| e.to_string())
+                .collect();
+            emit(
+                sources,
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "aborting due to {} previous error(s)",
+                        errors.len()
+                    ))
+                    .with_notes(summary),
+            )
+        }
+        None => emit(sources, &from_error(error)),
+    }
+}
+
+/// Render every warning of `warnings` to stderr.
+pub fn emit_warnings(sources: &SourceMap, warnings: &[TypingWarning]) -> anyhow::Result<()> {
+    warnings
+        .iter()
+        .try_for_each(|warning| emit(sources, &from_warning(warning)))
+}
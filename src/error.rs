@@ -1,118 +1,473 @@
-use crate::ast::{ClassName, FieldName, MethodName, Term};
+use crate::ast::{
+    BinaryOperator, ClassName, ClassType, FieldName, FileId, MethodName, PrimitiveType, Span, Term,
+    Type, TypeVariable, UnaryOperator,
+};
+use crate::class_table::MethodType;
+use crate::parser::Rule;
+use std::time::Duration;
 use thiserror::Error;
 
+#[derive(Debug, Error)]
+#[error("{}", .inner.variant.message())]
+pub struct ParseError {
+    pub file: FileId,
+    pub inner: Box<pest::error::Error<Rule>>,
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self.inner.location {
+            pest::error::InputLocation::Pos(pos) => Span::new(self.file, pos, pos),
+            pest::error::InputLocation::Span((start, end)) => Span::new(self.file, start, end),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("Could not read the imported file `{0}`: {1}")]
+    ImportNotFound(String, std::io::Error, Span),
+
+    #[error("Importing `{0}` forms a cycle: {}.", .1.join(" -> "))]
+    ImportCycle(String, Vec<String>, Span),
+}
+
+impl LoadError {
+    /// Location of the offending `import`.
+    pub fn span(&self) -> Span {
+        match self {
+            LoadError::ImportNotFound(_, _, span) | LoadError::ImportCycle(_, _, span) => *span,
+        }
+    }
+}
+
+/// Several independent errors, which are reported together.
+#[derive(Debug, Error)]
+#[error("{} error(s) occurred", .0.len())]
+pub struct ErrorList(pub Vec<anyhow::Error>);
+
 #[derive(Debug, Error)]
 pub enum ClassTableError {
-    #[error("Classes may not be named `Object`.")]
-    ClassNamedObject,
+    #[error("Classes and interfaces may not be named `Object`.")]
+    ClassNamedObject(Span),
+
+    #[error("Classes and interfaces may not be named after the primitive type `{0}`.")]
+    ClassNamedPrimitive(PrimitiveType, Span),
 
-    #[error("Class `{0}` is defined twice.")]
-    ClassDefinedTwice(ClassName),
+    #[error("Classes and interfaces may not be named after the built-in class `{0}`.")]
+    ClassNamedBuiltin(ClassName, Span),
 
-    #[error("The supertype `{0}` of class `{1}` is not defined.")]
-    SupertypeUndefined(ClassName, ClassName),
+    #[error("Class or interface `{0}` is defined twice.")]
+    ClassDefinedTwice(ClassName, Span, Span),
+
+    #[error("The supertype `{0}` of `{1}` is not defined.")]
+    SupertypeUndefined(ClassName, ClassName, Span),
 
     #[error("The supertype chain of class `{0}` contains a cycle.")]
-    CyclicSupertype(ClassName),
+    CyclicSupertype(ClassName, Span),
+
+    #[error("Class `{1}` can not extend interface `{0}`, it has to implement it.")]
+    ExtendsInterface(ClassName, ClassName, Span),
+
+    #[error("Class `{1}` can not implement `{0}`, which is not an interface.")]
+    ImplementsClass(ClassName, ClassName, Span),
+
+    #[error("Interface `{1}` can not extend `{0}`, which is not an interface.")]
+    InterfaceExtendsClass(ClassName, ClassName, Span),
+
+    #[error("Interface `{0}` is its own supertype.")]
+    CyclicInterface(ClassName, Span),
+
+    #[error("Interface `{0}` does not have unique method names.")]
+    NonUniqueInterfaceMethodNames(ClassName, Span),
 
     #[error("Contructor of class `{0}` is named `{1}`, but should be `{0}`.")]
-    IncorrectConstructorName(ClassName, ClassName),
+    IncorrectConstructorName(ClassName, ClassName, Span),
+
+    #[error("Contructor of class `{0}` assigns `{2}` to field `{1}`, but must assign the parameter `{1}`.")]
+    FieldAssignmentMismatch(ClassName, FieldName, FieldName, Span),
 
-    #[error("Contructor of class `{0}` does not correctly initialize all fields.")]
-    IncorrectContstructorInit(ClassName),
+    #[error("Contructor of class `{0}` assigns `{1}`, which is not a field of the class.")]
+    AssignmentToUnknownField(ClassName, FieldName, Span),
+
+    #[error("Contructor of class `{0}` assigns field `{1}` more than once.")]
+    FieldAssignedTwice(ClassName, FieldName, Span),
+
+    #[error("Contructor of class `{0}` does not assign field `{1}`.")]
+    FieldNotAssigned(ClassName, FieldName, Span),
 
     #[error("Class `{0}` does not have unique field names.")]
-    NonUniqueFields(ClassName),
+    NonUniqueFields(ClassName, Span),
 
     #[error("Class `{0}` does not have unique method names.")]
-    NonUniqueMethodNames(ClassName),
+    NonUniqueMethodNames(ClassName, Span),
 
     #[error("Class `{0}` may not contain `this` as a field.")]
-    FieldNamedThis(ClassName),
+    FieldNamedThis(ClassName, Span),
 
     #[error("Contructor of class `{0}` may not contain `this` as an argument.")]
-    ConstructorArgumentNamedThis(ClassName),
+    ConstructorArgumentNamedThis(ClassName, Span),
 
-    #[error("Method `{0}` in class `{1}` does not have unique argument names.")]
-    NonUniqueMethodArgumentNames(MethodName, ClassName),
+    #[error("Method `{0}` in `{1}` does not have unique argument names.")]
+    NonUniqueMethodArgumentNames(MethodName, ClassName, Span),
 
-    #[error("Method `{0}` in class `{1}` may not contain `this` as an argument.")]
-    MethodArgumentNamedThis(MethodName, ClassName),
+    #[error("Method `{0}` in `{1}` may not contain `this` as an argument.")]
+    MethodArgumentNamedThis(MethodName, ClassName, Span),
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl ClassTableError {
+    /// Location of the offending definition, if known.
+    pub fn span(&self) -> Option<Span> {
+        use ClassTableError::*;
+        match self {
+            ClassNamedObject(span)
+            | ClassNamedPrimitive(_, span)
+            | ClassNamedBuiltin(_, span)
+            | ClassDefinedTwice(_, span, _)
+            | SupertypeUndefined(_, _, span)
+            | CyclicSupertype(_, span)
+            | ExtendsInterface(_, _, span)
+            | ImplementsClass(_, _, span)
+            | InterfaceExtendsClass(_, _, span)
+            | CyclicInterface(_, span)
+            | NonUniqueInterfaceMethodNames(_, span)
+            | IncorrectConstructorName(_, _, span)
+            | FieldAssignmentMismatch(_, _, _, span)
+            | AssignmentToUnknownField(_, _, span)
+            | FieldAssignedTwice(_, _, span)
+            | FieldNotAssigned(_, _, span)
+            | NonUniqueFields(_, span)
+            | NonUniqueMethodNames(_, span)
+            | FieldNamedThis(_, span)
+            | ConstructorArgumentNamedThis(_, span)
+            | NonUniqueMethodArgumentNames(_, _, span)
+            | MethodArgumentNamedThis(_, _, span) => Some(*span),
+            Other(_) => None,
+        }
+    }
+
+    /// Further locations that help explaining the error.
+    pub fn secondary_spans(&self) -> Vec<(Span, String)> {
+        match self {
+            ClassTableError::ClassDefinedTwice(name, _, first) => {
+                vec![(*first, format!("`{}` first defined here", name))]
+            }
+            _ => vec![],
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum TypingError {
     #[error("Typechecking for class `{0}` failed.")]
-    InvalidClass(ClassName),
+    InvalidClass(ClassName, Span),
 
-    #[error("Typechecking for method `{0}` in class `{1}` failed.")]
-    InvalidMethod(MethodName, ClassName),
+    #[error("Typechecking for interface `{0}` failed.")]
+    InvalidInterface(ClassName, Span),
+
+    #[error("Typechecking for method `{0}` in `{1}` failed.")]
+    InvalidMethod(MethodName, ClassName, Span),
 
     #[error("Typechecking for term failed : `{0}`")]
     InvalidTerm(Term),
 
     #[error("Class `{0}` not defined in class table.")]
-    UndefinedClass(ClassName),
+    UndefinedClass(ClassName, Span),
 
     #[error("One or more of the following classes are not defined by the class table: {0:?}")]
-    UndefinedClasses(Vec<ClassName>),
+    UndefinedClasses(Vec<ClassName>, Span),
 
     #[error("Method `{0}` in class `{1}` not defined.")]
-    UndefinedMethod(MethodName, ClassName),
+    UndefinedMethod(MethodName, ClassName, Span),
 
     #[error("Field `{0}` not defined in class `{1}`.")]
-    UndefinedField(FieldName, ClassName),
+    UndefinedField(FieldName, ClassName, Span),
+
+    #[error("Interface `{0}` can not be instantiated.")]
+    InterfaceInstantiated(ClassName, Span),
+
+    #[error(
+        "A lambda expression is only allowed where a functional interface is expected: as an argument, in a cast or as the result of a method."
+    )]
+    LambdaWithoutTarget(Span),
+
+    #[error(
+        "`{0}` is not a functional interface, i.e. an interface with exactly one method, which is not generic, so it can not be the type of a lambda expression."
+    )]
+    NotAFunctionalInterface(Type, Span),
+
+    #[error("Lambda parameter `{0}` is named `this` or declared more than once.")]
+    InvalidLambdaParameter(FieldName, Span),
+
+    #[error("A lambda of type `{0}` must take {1} parameter(s), but takes {2}.")]
+    LambdaParameterCount(Type, usize, usize, Span),
+
+    #[error("Parameter {1} of a lambda of type `{0}` must have type `{2}`, but has type `{3}`.")]
+    LambdaParameterMismatch(Type, usize, Type, Type, Span),
+
+    #[error(
+        "Body of a lambda of type `{2}` has type `{0}`, which is not a subtype of the return type `{1}`."
+    )]
+    LambdaBodyNotSubtype(Type, Type, Type, Span),
+
+    #[error("Abstract class `{0}` can not be instantiated.")]
+    AbstractClassInstantiated(ClassName, Span),
+
+    #[error("`{0}` is not available in pure FJ.")]
+    NotInPureFj(String, Span),
+
+    #[error("Values of primitive type `{0}` have no fields or methods and can not be cast.")]
+    PrimitiveHasNoMembers(Type, Span),
+
+    #[error("Operator `{0}` can not be applied to operands of types `{1}` and `{2}`.")]
+    InvalidBinaryOperands(BinaryOperator, Type, Type, Span),
+
+    #[error("Operator `{0}` can not be applied to an operand of type `{1}`.")]
+    InvalidUnaryOperand(UnaryOperator, Type, Span),
+
+    #[error("Condition of `if` has type `{0}`, but must be a `boolean`.")]
+    ConditionNotBoolean(Type, Span),
 
     #[error(
-        "Method `{0}` in class `{1}` does not override the method
-        defined in the supertype correctly."
+        "Branches of `if` have types `{0}` and `{1}`, which must be equal if one of them is primitive."
     )]
-    IncorrectMethodOverride(MethodName, ClassName),
+    IfBranchMismatch(Type, Type, Span),
+
+    #[error("Value of type `{0}` can not be assigned to field `{2}` of type `{1}`.")]
+    AssignmentNotSubtype(Type, Type, FieldName, Span),
 
     #[error(
-        "No rule to cast term of type `{from}` to type `{to}`.
-        This is likely an implementation error."
+        "Local variable `{2}` of type `{1}` can not be initialized with a value of type `{0}`."
     )]
-    InvalidCast { from: ClassName, to: ClassName },
+    LocalVariableNotSubtype(Type, Type, FieldName, Span),
+
+    #[error("A local variable can not be named `this`.")]
+    InvalidLocalVariable(Span),
+
+    #[error("Type `{0}` is not a subclass of `Throwable`, so it can not be thrown or caught.")]
+    NotThrowable(Type, Span),
+
+    #[error(
+        "Blocks of `try` have types `{0}` and `{1}`, which must be equal if one of them is primitive."
+    )]
+    CatchBranchMismatch(Type, Type, Span),
+
+    #[error("`@{0}` is a heap location, which only occurs during evaluation.")]
+    HeapLocation(usize, Span),
+
+    #[error(
+        "Class `{1}` is not abstract and does not define or inherit a body for method `{0}` of `{2}`."
+    )]
+    MissingMethodBody(MethodName, ClassName, ClassType, Span),
+
+    #[error(
+        "Method `{0}` in `{1}` has type `{3}`, which does not correctly override the type `{2}` defined in the supertype."
+    )]
+    IncorrectMethodOverride(MethodName, ClassName, MethodType, MethodType, Span),
+
+    #[error(
+        "Cast of term of type `{from}` to type `{to}` is not allowed, as the type arguments of the two types do not match."
+    )]
+    IncompatibleCast { from: Type, to: Type, span: Span },
+
+    #[error("Cast of term of type `{from}` to unrelated type `{to}` can never succeed.")]
+    StupidCast { from: Type, to: Type, span: Span },
 
     #[error(
         "Argument type `{0}` is not subtype of declared type `{1}` in constructor of class `{2}`."
     )]
-    ConstructorArgumentNotSubtype(ClassName, ClassName, ClassName),
+    ConstructorArgumentNotSubtype(Type, Type, ClassName, Span),
 
     #[error(
         "Argument type `{0}` is not subtype of declared type `{1}` in method `{2}` of class `{3}`."
     )]
-    MethodArgumentNotSubtype(ClassName, ClassName, MethodName, ClassName),
+    MethodArgumentNotSubtype(Type, Type, MethodName, ClassName, Span),
+
+    #[error("Class `{0}` expects {1} type argument(s), but {2} were given.")]
+    TypeArgumentCount(ClassName, usize, usize, Span),
+
+    #[error("Method `{0}` of class `{1}` expects {2} type argument(s), but {3} were given.")]
+    MethodTypeArgumentCount(MethodName, ClassName, usize, usize, Span),
+
+    #[error("Type argument `{0}` for type parameter `{1}` is not a subtype of its bound `{2}`.")]
+    TypeArgumentNotWithinBound(Type, TypeVariable, ClassType, Span),
+
+    #[error(
+        "Could not infer the type arguments of method `{0}` of class `{1}`, they must be given explicitly."
+    )]
+    CannotInferTypeArguments(MethodName, ClassName, Span),
+
+    #[error("Type parameter `{0}` is declared more than once.")]
+    NonUniqueTypeParameters(TypeVariable, Span),
+
+    #[error("Type variable `{0}` can not be instantiated or be the target of a cast.")]
+    TypeVariableAsClass(TypeVariable, Span),
+
+    #[error("Constructor of class `{0}` expects {1} argument(s), but {2} were given.")]
+    ConstructorArgumentCount(ClassName, usize, usize, Span),
+
+    #[error("Method `{0}` of class `{1}` expects {2} argument(s), but {3} were given.")]
+    MethodArgumentCount(MethodName, ClassName, usize, usize, Span),
+
+    #[error(
+        "Constructor of class `{0}` must take {1} parameter(s), one for each inherited and own field, but takes {2}."
+    )]
+    ConstructorParameterCount(ClassName, usize, usize, Span),
+
+    #[error(
+        "Parameter {1} of the constructor of class `{0}` is `{4} {5}`, but should be `{2} {3}`."
+    )]
+    ConstructorParameterMismatch(ClassName, usize, Type, FieldName, Type, FieldName, Span),
+
+    #[error(
+        "`super(...)` in the constructor of class `{0}` must pass {1} argument(s), one for each inherited field, but passes {2}."
+    )]
+    SuperCallArgumentCount(ClassName, usize, usize, Span),
+
+    #[error(
+        "Argument {1} of `super(...)` in the constructor of class `{0}` is `{3}`, but should be the inherited field `{2}`."
+    )]
+    SuperCallArgumentMismatch(ClassName, usize, FieldName, FieldName, Span),
 
     #[error("Variable `{0}` not typed by gamma")]
-    VariableNotInGamma(FieldName),
+    VariableNotInGamma(FieldName, Span),
 
     #[error("Return type of method `{0}` in class `{1}` not found")]
-    UndefinedReturnType(MethodName, ClassName),
+    UndefinedReturnType(MethodName, ClassName, Span),
 
     #[error(
         "Return type `{0}` is not subtype of declared return type `{1}` of method `{2}` of class `{3}`.",
     )]
-    ReturnTypeNotSubtype(ClassName, ClassName, MethodName, ClassName),
+    ReturnTypeNotSubtype(Type, Type, MethodName, ClassName, Span),
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
-#[derive(Debug, Error)]
-pub enum EvalError {
+impl TypingError {
+    /// Location of the offending class, method or term, if known.
+    pub fn span(&self) -> Option<Span> {
+        use TypingError::*;
+        match self {
+            InvalidTerm(term) => Some(term.span()),
+            InvalidClass(_, span)
+            | InvalidInterface(_, span)
+            | InvalidMethod(_, _, span)
+            | UndefinedClass(_, span)
+            | UndefinedClasses(_, span)
+            | UndefinedMethod(_, _, span)
+            | UndefinedField(_, _, span)
+            | InterfaceInstantiated(_, span)
+            | LambdaWithoutTarget(span)
+            | NotAFunctionalInterface(_, span)
+            | InvalidLambdaParameter(_, span)
+            | LambdaParameterCount(_, _, _, span)
+            | LambdaParameterMismatch(_, _, _, _, span)
+            | LambdaBodyNotSubtype(_, _, _, span)
+            | AbstractClassInstantiated(_, span)
+            | NotInPureFj(_, span)
+            | PrimitiveHasNoMembers(_, span)
+            | InvalidBinaryOperands(_, _, _, span)
+            | InvalidUnaryOperand(_, _, span)
+            | ConditionNotBoolean(_, span)
+            | IfBranchMismatch(_, _, span)
+            | AssignmentNotSubtype(_, _, _, span)
+            | LocalVariableNotSubtype(_, _, _, span)
+            | InvalidLocalVariable(span)
+            | NotThrowable(_, span)
+            | CatchBranchMismatch(_, _, span)
+            | HeapLocation(_, span)
+            | MissingMethodBody(_, _, _, span)
+            | IncorrectMethodOverride(_, _, _, _, span)
+            | IncompatibleCast { span, .. }
+            | StupidCast { span, .. }
+            | ConstructorArgumentNotSubtype(_, _, _, span)
+            | MethodArgumentNotSubtype(_, _, _, _, span)
+            | TypeArgumentCount(_, _, _, span)
+            | MethodTypeArgumentCount(_, _, _, _, span)
+            | TypeArgumentNotWithinBound(_, _, _, span)
+            | CannotInferTypeArguments(_, _, span)
+            | NonUniqueTypeParameters(_, span)
+            | TypeVariableAsClass(_, span)
+            | ConstructorArgumentCount(_, _, _, span)
+            | MethodArgumentCount(_, _, _, _, span)
+            | ConstructorParameterCount(_, _, _, span)
+            | ConstructorParameterMismatch(_, _, _, _, _, _, span)
+            | SuperCallArgumentCount(_, _, _, span)
+            | SuperCallArgumentMismatch(_, _, _, _, span)
+            | VariableNotInGamma(_, span)
+            | UndefinedReturnType(_, _, span)
+            | ReturnTypeNotSubtype(_, _, _, _, span) => Some(*span),
+            Other(_) => None,
+        }
+    }
+}
+
+/// Findings of the typechecker, and of static analyses of typechecked
+/// programs, that do not make a program ill-typed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TypingWarning {
+    #[error("Cast of term of type `{from}` to unrelated type `{to}` can never succeed.")]
+    StupidCast { from: Type, to: Type, span: Span },
+
     #[error(
-        "Could not cast class `{from}` to class `{to}`.
-        There is no subtyping relation between them."
+        "Cast of term of type `{from}` to type `{to}` is unchecked, only the class `{}` is checked at runtime.",
+        .to.name
     )]
-    CastFailed { from: ClassName, to: ClassName },
+    UncheckedCast {
+        from: Type,
+        to: ClassType,
+        span: Span,
+    },
+
+    #[error("Cast of term of type `{from}` to type `{to}` may fail at runtime.")]
+    CastMayFail {
+        from: Type,
+        to: ClassType,
+        /// Classes of objects the term may evaluate to which fail the cast.
+        failing: Vec<ClassName>,
+        span: Span,
+    },
+}
+
+impl TypingWarning {
+    /// Location of the offending term.
+    pub fn span(&self) -> Span {
+        match self {
+            TypingWarning::StupidCast { span, .. }
+            | TypingWarning::UncheckedCast { span, .. }
+            | TypingWarning::CastMayFail { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum EvalError {
+    #[error("Uncaught exception `{0}`.")]
+    UncaughtException(Box<Term>, Span),
 
     #[error("Evaluation is stuck. Could not matching current term: `{0}`")]
-    Stuck(Term),
+    Stuck(Box<Term>),
+
+    /// The term is the one evaluation stopped at: the whole term for the
+    /// small-step evaluators and the current redex, with the values of its
+    /// variables substituted, for the big-step interpreter. The vm does not
+    /// keep terms and reports the term it was started on.
+    #[error("Evaluation did not finish within {0} steps. Last term: `{1}`")]
+    StepLimitExceeded(usize, Box<Term>),
+
+    /// The term is reported as for [`EvalError::StepLimitExceeded`].
+    #[error("Evaluation did not finish within {0:?} ({1} steps). Last term: `{2}`")]
+    TimeLimitExceeded(Duration, usize, Box<Term>),
+
+    #[error("Evaluation diverges: term `{0}` recurs every {1} step(s).")]
+    Diverges(Box<Term>, usize),
+
+    #[error("Method calls nested deeper than {0} levels. Last term: `{1}`")]
+    CallDepthExceeded(usize, Box<Term>),
 
     #[error("Class `{0}` not defined in class table")]
     UndefinedClass(ClassName),
@@ -129,6 +484,45 @@ pub enum EvalError {
     #[error("Could not get constructor argument at position {0} in class `{1}`.")]
     ConstructorArgNotFound(usize, ClassName),
 
+    #[error("Constructor of class `{0}` expects {1} argument(s), but {2} were given.")]
+    ConstructorArgumentCount(ClassName, usize, usize, Span),
+
+    #[error("Method `{0}` of class `{1}` expects {2} argument(s), but {3} were given.")]
+    MethodArgumentCount(MethodName, ClassName, usize, usize, Span),
+
+    #[error("Lambda expects {0} argument(s), but {1} were given.")]
+    LambdaArgumentCount(usize, usize, Span),
+
+    #[error("Division by zero.")]
+    DivisionByZero(Span),
+
+    #[error("Integer overflow.")]
+    IntegerOverflow(Span),
+
+    #[error("Field assignment is only supported by the heap evaluator.")]
+    AssignmentWithoutHeap(Span),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+impl EvalError {
+    /// Location of the term at which evaluation failed, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EvalError::Stuck(term)
+            | EvalError::StepLimitExceeded(_, term)
+            | EvalError::TimeLimitExceeded(_, _, term)
+            | EvalError::Diverges(term, _)
+            | EvalError::CallDepthExceeded(_, term) => Some(term.span()),
+            EvalError::ConstructorArgumentCount(_, _, _, span)
+            | EvalError::MethodArgumentCount(_, _, _, _, span)
+            | EvalError::LambdaArgumentCount(_, _, span)
+            | EvalError::DivisionByZero(span)
+            | EvalError::IntegerOverflow(span)
+            | EvalError::AssignmentWithoutHeap(span)
+            | EvalError::UncaughtException(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+}
@@ -1,21 +1,281 @@
 use crate::error::EvalError;
-use crate::{ast::*, class_table::ClassTable};
+use crate::{ast::*, class_table::ClassTable, interpreter, typecheck};
 use std::collections::BTreeMap;
+use std::fmt::Display;
 use std::iter;
+use std::time::{Duration, Instant};
 
+/// A reduction rule of the FJ operational semantics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvalRule {
+    ProjNew,
+    InvkNew,
+    CastNew,
+    InvkLambda,
+    CastLambda,
+    BinOp,
+    UnOp,
+    IfTrue,
+    IfFalse,
+    Let,
+    Seq,
+    New,
+    ProjLoc,
+    InvkLoc,
+    CastLoc,
+    Assign,
+    TryValue,
+    Catch,
+    Rethrow,
+    Field,
+    InvkRecv,
+    InvkArg,
+    NewArg,
+    Cast,
+    BinOpLeft,
+    BinOpRight,
+    UnOpArg,
+    IfCond,
+    LetInit,
+    SeqFirst,
+    AssignObj,
+    AssignVal,
+    Throw,
+    Try,
+}
+
+impl EvalRule {
+    /// Whether this is a congruence rule, which only reduces a subterm.
+    pub fn is_congruence(&self) -> bool {
+        !matches!(
+            self,
+            EvalRule::ProjNew
+                | EvalRule::InvkNew
+                | EvalRule::CastNew
+                | EvalRule::InvkLambda
+                | EvalRule::CastLambda
+                | EvalRule::BinOp
+                | EvalRule::UnOp
+                | EvalRule::IfTrue
+                | EvalRule::IfFalse
+                | EvalRule::Let
+                | EvalRule::Seq
+                | EvalRule::New
+                | EvalRule::ProjLoc
+                | EvalRule::InvkLoc
+                | EvalRule::CastLoc
+                | EvalRule::Assign
+                | EvalRule::TryValue
+                | EvalRule::Catch
+                | EvalRule::Rethrow
+        )
+    }
+}
+
+impl Display for EvalRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            EvalRule::ProjNew => "E-ProjNew",
+            EvalRule::InvkNew => "E-InvkNew",
+            EvalRule::CastNew => "E-CastNew",
+            EvalRule::InvkLambda => "E-InvkLambda",
+            EvalRule::CastLambda => "E-CastLambda",
+            EvalRule::BinOp => "E-BinOp",
+            EvalRule::UnOp => "E-UnOp",
+            EvalRule::IfTrue => "E-IfTrue",
+            EvalRule::IfFalse => "E-IfFalse",
+            EvalRule::Let => "E-Let",
+            EvalRule::Seq => "E-Seq",
+            EvalRule::New => "E-New",
+            EvalRule::ProjLoc => "E-ProjLoc",
+            EvalRule::InvkLoc => "E-InvkLoc",
+            EvalRule::CastLoc => "E-CastLoc",
+            EvalRule::Assign => "E-Assign",
+            EvalRule::TryValue => "E-TryValue",
+            EvalRule::Catch => "E-Catch",
+            EvalRule::Rethrow => "E-Rethrow",
+            EvalRule::Field => "E-Field",
+            EvalRule::InvkRecv => "E-Invk-Recv",
+            EvalRule::InvkArg => "E-Invk-Arg",
+            EvalRule::NewArg => "E-New-Arg",
+            EvalRule::Cast => "E-Cast",
+            EvalRule::BinOpLeft => "E-BinOp-Left",
+            EvalRule::BinOpRight => "E-BinOp-Right",
+            EvalRule::UnOpArg => "E-UnOp-Arg",
+            EvalRule::IfCond => "E-If-Cond",
+            EvalRule::LetInit => "E-Let-Init",
+            EvalRule::SeqFirst => "E-Seq-First",
+            EvalRule::AssignObj => "E-Assign-Obj",
+            EvalRule::AssignVal => "E-Assign-Val",
+            EvalRule::Throw => "E-Throw",
+            EvalRule::Try => "E-Try",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The rules used by a single reduction step, outermost first: zero or more
+/// congruence rules followed by the computation rule that did the reduction.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Derivation(pub Vec<EvalRule>);
+
+impl Display for Derivation {
+    /// Prints the derivation nested like `E-Invk-Recv(E-ProjNew)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, rule) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "(")?;
+            }
+            write!(f, "{}", rule)?;
+        }
+        for _ in 1..self.0.len() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single reduction step, resulting in `term`.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub term: Term,
+    pub derivation: Derivation,
+}
+
+/// Bounds on the evaluation of a term, which might not terminate.
+#[derive(Debug, Clone)]
+pub struct EvalLimits {
+    /// Maximum number of reduction steps.
+    pub max_steps: Option<usize>,
+    /// Maximum wall-clock time.
+    pub timeout: Option<Duration>,
+    /// Detect when a term recurs exactly, which means evaluation diverges.
+    pub detect_cycles: bool,
+}
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        EvalLimits {
+            max_steps: None,
+            timeout: None,
+            detect_cycles: true,
+        }
+    }
+}
+
+/// Evaluate `term` to a value.
 pub fn eval_full(ct: &ClassTable, term: Term) -> Result<Term, EvalError> {
+    eval_full_with(ct, term, &EvalLimits::default(), |_| {})
+}
+
+/// Evaluate `term` to a value within `limits`, calling `observer` after every
+/// reduction step.
+///
+/// Cycles are detected with Brent's algorithm, which compares the current
+/// term with a single saved one and saves a new term after 1, 2, 4, ... steps.
+///
+/// Type arguments are erased before evaluation, see [`erase`].
+///
+/// As every reduction step recurses over the term, evaluation runs on a
+/// separate thread with a stack of [`interpreter::STACK_SIZE`], so growing
+/// terms are stopped by the limits before they run out of stack.
+pub fn eval_full_with(
+    ct: &ClassTable,
+    term: Term,
+    limits: &EvalLimits,
+    observer: impl FnMut(&Step) + Send,
+) -> Result<Term, EvalError> {
+    eval_with(ct, term, None, limits, observer)
+}
+
+/// Evaluate `term` to a value within `limits` like [`eval_full_with`], but
+/// with the heap semantics of imperative FJ: `new` allocates an object on a
+/// heap and reduces to its location `@n`, so objects can be shared and their
+/// fields assigned. Returns the value together with the final heap.
+///
+/// A term only diverges if it recurs together with the same heap.
+pub fn eval_heap_with(
+    ct: &ClassTable,
+    term: Term,
+    limits: &EvalLimits,
+    observer: impl FnMut(&Step) + Send,
+) -> Result<(Term, Heap), EvalError> {
+    let mut heap = Heap::new();
+    let value = eval_with(ct, term, Some(&mut heap), limits, observer)?;
+    Ok((value, heap))
+}
+
+fn eval_with(
+    ct: &ClassTable,
+    term: Term,
+    heap: Option<&mut Heap>,
+    limits: &EvalLimits,
+    observer: impl FnMut(&Step) + Send,
+) -> Result<Term, EvalError> {
+    interpreter::on_large_stack(|| eval_on_stack(ct, term, heap, limits, observer))
+}
+
+fn eval_on_stack(
+    ct: &ClassTable,
+    term: Term,
+    mut heap: Option<&mut Heap>,
+    limits: &EvalLimits,
+    mut observer: impl FnMut(&Step),
+) -> Result<Term, EvalError> {
+    let term = erase(term);
+    let with_heap = heap.is_some();
+    let start = Instant::now();
+    let mut steps = 0;
+    let mut saved = limits
+        .detect_cycles
+        .then(|| (term.clone(), heap.as_deref().cloned()));
+    let (mut power, mut period) = (1, 1);
     let mut current = term;
-    while !current.is_value() {
-        println!("eval_full current term: {}", &current);
-        current = eval_step(ct, current)?;
+    while !is_value(&current, with_heap) {
+        // an uncaught exception is reported by `step` without taking a step
+        let uncaught = thrown(&current, with_heap).is_some();
+        if !uncaught && limits.max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            return Err(EvalError::StepLimitExceeded(steps, current.boxed()));
+        }
+        if let Some(timeout) = limits.timeout.filter(|_| !uncaught) {
+            if start.elapsed() >= timeout {
+                return Err(EvalError::TimeLimitExceeded(
+                    timeout,
+                    steps,
+                    current.boxed(),
+                ));
+            }
+        }
+        let mut rules = Vec::new();
+        let step = Step {
+            term: step(ct, current, heap.as_deref_mut(), &mut rules)?,
+            derivation: Derivation(rules),
+        };
+        observer(&step);
+        current = step.term;
+        steps += 1;
+
+        if let Some((saved_term, saved_heap)) = &mut saved {
+            if *saved_term == current && saved_heap.as_ref() == heap.as_deref() {
+                return Err(EvalError::Diverges(current.boxed(), period));
+            }
+            if power == period {
+                *saved_term = current.clone();
+                *saved_heap = heap.as_deref().cloned();
+                power *= 2;
+                period = 0;
+            }
+            period += 1;
+        }
     }
     Ok(current)
 }
 
 impl Term {
-    fn is_value(&self) -> bool {
+    pub fn is_value(&self) -> bool {
         match self {
             Term::NewCall(nc) => nc.has_only_value_args(),
+            Term::Lambda(_) | Term::Literal(_) => true,
             _ => false,
         }
     }
@@ -27,10 +287,80 @@ impl NewCall {
     }
 }
 
-fn substitute(in_term: Term, to_replace: &FieldName, with_term: Term) -> Term {
+impl Term {
+    /// Whether this is a value of the heap semantics, see [`eval_heap_with`],
+    /// where objects are only values as their locations.
+    pub fn is_heap_value(&self) -> bool {
+        matches!(self, Term::Location(_) | Term::Lambda(_) | Term::Literal(_))
+    }
+}
+
+fn is_value(term: &Term, with_heap: bool) -> bool {
+    if with_heap {
+        term.is_heap_value()
+    } else {
+        term.is_value()
+    }
+}
+
+/// The heap of imperative FJ, holding the object allocated at each location
+/// `@n`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Heap {
+    objects: Vec<HeapObject>,
+}
+
+/// An object on the heap, with the values of its fields in the order of the
+/// constructor parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapObject {
+    pub class_name: ClassName,
+    pub fields: Vec<Box<Term>>,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+    pub fn get(&self, location: usize) -> Option<&HeapObject> {
+        self.objects.get(location)
+    }
+    /// The locations and objects on the heap, in order of allocation.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &HeapObject)> {
+        self.objects.iter().enumerate()
+    }
+    fn alloc(&mut self, object: HeapObject) -> usize {
+        self.objects.push(object);
+        self.objects.len() - 1
+    }
+}
+
+impl Display for Heap {
+    /// Prints one line `@n = new C(v̄)` per object.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (location, object) in self.iter() {
+            write!(f, "@{} = new {}(", location, object.class_name)?;
+            for (i, value) in object.fields.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", value)?;
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// `[with_term/to_replace] in_term`, the substitution of the closed term
+/// `with_term` for the variable `to_replace`.
+pub fn substitute(in_term: Term, to_replace: &FieldName, with_term: Term) -> Term {
     match in_term {
         Term::Variable(v) => {
-            if &v == to_replace {
+            if &v.name == to_replace {
                 with_term
             } else {
                 Term::Variable(v)
@@ -39,39 +369,457 @@ fn substitute(in_term: Term, to_replace: &FieldName, with_term: Term) -> Term {
         Term::Cast(Cast {
             term,
             to_class_name,
+            type_args,
+            span,
         }) => Term::Cast(Cast {
             term: substitute(*term, to_replace, with_term).boxed(),
             to_class_name,
+            type_args,
+            span,
         }),
-        Term::FieldAccess(FieldAccess { field, object_term }) => Term::FieldAccess(FieldAccess {
+        Term::FieldAccess(FieldAccess {
+            field,
+            object_term,
+            span,
+        }) => Term::FieldAccess(FieldAccess {
             field,
             object_term: substitute(*object_term, to_replace, with_term).boxed(),
+            span,
         }),
         Term::MethodCall(MethodCall {
             arg_terms,
             method_name,
+            type_args,
             object_term,
+            span,
         }) => Term::MethodCall(MethodCall {
             arg_terms: arg_terms
                 .into_iter()
                 .map(|term| substitute(*term, to_replace, with_term.clone()).boxed())
                 .collect(),
             method_name,
-            object_term: substitute(*object_term, to_replace, with_term.clone()).boxed(),
+            type_args,
+            object_term: substitute(*object_term, to_replace, with_term).boxed(),
+            span,
+        }),
+        // the parameters of a lambda shadow variables of the same name
+        Term::Lambda(lambda) if lambda.args.iter().any(|(_, arg)| arg == to_replace) => {
+            Term::Lambda(lambda)
+        }
+        Term::Lambda(Lambda {
+            args,
+            body,
+            target,
+            span,
+        }) => Term::Lambda(Lambda {
+            args,
+            body: substitute(*body, to_replace, with_term).boxed(),
+            target,
+            span,
         }),
         Term::NewCall(NewCall {
             arg_terms,
             class_name,
+            type_args,
+            span,
         }) => Term::NewCall(NewCall {
             arg_terms: arg_terms
                 .into_iter()
                 .map(|term| substitute(*term, to_replace, with_term.clone()).boxed())
                 .collect(),
             class_name,
+            type_args,
+            span,
         }),
+        Term::Literal(literal) => Term::Literal(literal),
+        Term::BinaryOp(BinaryOp { op, lhs, rhs, span }) => Term::BinaryOp(BinaryOp {
+            op,
+            lhs: substitute(*lhs, to_replace, with_term.clone()).boxed(),
+            rhs: substitute(*rhs, to_replace, with_term).boxed(),
+            span,
+        }),
+        Term::UnaryOp(UnaryOp { op, term, span }) => Term::UnaryOp(UnaryOp {
+            op,
+            term: substitute(*term, to_replace, with_term).boxed(),
+            span,
+        }),
+        Term::If(If {
+            condition,
+            then_term,
+            else_term,
+            span,
+        }) => Term::If(If {
+            condition: substitute(*condition, to_replace, with_term.clone()).boxed(),
+            then_term: substitute(*then_term, to_replace, with_term.clone()).boxed(),
+            else_term: substitute(*else_term, to_replace, with_term).boxed(),
+            span,
+        }),
+        Term::FieldAssign(FieldAssign {
+            object_term,
+            field,
+            value_term,
+            span,
+        }) => Term::FieldAssign(FieldAssign {
+            object_term: substitute(*object_term, to_replace, with_term.clone()).boxed(),
+            field,
+            value_term: substitute(*value_term, to_replace, with_term).boxed(),
+            span,
+        }),
+        // a local variable shadows variables of the same name in its body
+        Term::Let(Let {
+            ty,
+            name,
+            value_term,
+            body,
+            span,
+        }) => {
+            let body = if &name == to_replace {
+                body
+            } else {
+                substitute(*body, to_replace, with_term.clone()).boxed()
+            };
+            Term::Let(Let {
+                ty,
+                name,
+                value_term: substitute(*value_term, to_replace, with_term).boxed(),
+                body,
+                span,
+            })
+        }
+        Term::Seq(Seq {
+            first,
+            second,
+            span,
+        }) => Term::Seq(Seq {
+            first: substitute(*first, to_replace, with_term.clone()).boxed(),
+            second: substitute(*second, to_replace, with_term).boxed(),
+            span,
+        }),
+        Term::Location(location) => Term::Location(location),
+        Term::Throw(Throw { term, span }) => Term::Throw(Throw {
+            term: substitute(*term, to_replace, with_term).boxed(),
+            span,
+        }),
+        // the variable of a `catch` clause shadows variables of the same name
+        Term::Try(Try {
+            body,
+            catches,
+            span,
+        }) => Term::Try(Try {
+            body: substitute(*body, to_replace, with_term.clone()).boxed(),
+            catches: catches
+                .into_iter()
+                .map(|catch| {
+                    if &catch.name == to_replace {
+                        catch
+                    } else {
+                        Catch {
+                            body: substitute(*catch.body, to_replace, with_term.clone()).boxed(),
+                            ..catch
+                        }
+                    }
+                })
+                .collect(),
+            span,
+        }),
+    }
+}
+
+/// Remove all type arguments from `term`.
+///
+/// Evaluation only depends on classes, with casts checking the class alone,
+/// so generic programs are evaluated like their erasure to FJ. Method bodies
+/// are erased when invoked, as their type arguments may mention type
+/// variables of the method, which are not substituted.
+pub fn erase(term: Term) -> Term {
+    match term {
+        Term::Variable(v) => Term::Variable(v),
+        Term::FieldAccess(FieldAccess {
+            object_term,
+            field,
+            span,
+        }) => FieldAccess {
+            object_term: erase(*object_term).boxed(),
+            field,
+            span,
+        }
+        .into_term(),
+        Term::MethodCall(MethodCall {
+            object_term,
+            method_name,
+            arg_terms,
+            span,
+            ..
+        }) => MethodCall {
+            object_term: erase(*object_term).boxed(),
+            method_name,
+            type_args: vec![],
+            arg_terms: arg_terms.into_iter().map(|t| erase(*t).boxed()).collect(),
+            span,
+        }
+        .into_term(),
+        Term::NewCall(NewCall {
+            class_name,
+            arg_terms,
+            span,
+            ..
+        }) => NewCall {
+            class_name,
+            type_args: vec![],
+            arg_terms: arg_terms.into_iter().map(|t| erase(*t).boxed()).collect(),
+            span,
+        }
+        .into_term(),
+        Term::Cast(Cast {
+            to_class_name,
+            term,
+            span,
+            ..
+        }) => Cast {
+            to_class_name,
+            type_args: vec![],
+            term: erase(*term).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::Lambda(Lambda {
+            args,
+            body,
+            target,
+            span,
+        }) => Lambda {
+            args,
+            body: erase(*body).boxed(),
+            target,
+            span,
+        }
+        .into_term(),
+        Term::Literal(literal) => Term::Literal(literal),
+        Term::BinaryOp(BinaryOp { op, lhs, rhs, span }) => BinaryOp {
+            op,
+            lhs: erase(*lhs).boxed(),
+            rhs: erase(*rhs).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::UnaryOp(UnaryOp { op, term, span }) => UnaryOp {
+            op,
+            term: erase(*term).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::If(If {
+            condition,
+            then_term,
+            else_term,
+            span,
+        }) => If {
+            condition: erase(*condition).boxed(),
+            then_term: erase(*then_term).boxed(),
+            else_term: erase(*else_term).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::FieldAssign(FieldAssign {
+            object_term,
+            field,
+            value_term,
+            span,
+        }) => FieldAssign {
+            object_term: erase(*object_term).boxed(),
+            field,
+            value_term: erase(*value_term).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::Let(Let {
+            ty,
+            name,
+            value_term,
+            body,
+            span,
+        }) => Let {
+            ty,
+            name,
+            value_term: erase(*value_term).boxed(),
+            body: erase(*body).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::Seq(Seq {
+            first,
+            second,
+            span,
+        }) => Seq {
+            first: erase(*first).boxed(),
+            second: erase(*second).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::Location(location) => Term::Location(location),
+        Term::Throw(Throw { term, span }) => Throw {
+            term: erase(*term).boxed(),
+            span,
+        }
+        .into_term(),
+        Term::Try(Try {
+            body,
+            catches,
+            span,
+        }) => Try {
+            body: erase(*body).boxed(),
+            catches: catches
+                .into_iter()
+                .map(|catch| Catch {
+                    body: erase(*catch.body).boxed(),
+                    ..catch
+                })
+                .collect(),
+            span,
+        }
+        .into_term(),
+    }
+}
+
+/// Record the interface `ty` as the target of `term`, if it is a lambda
+/// without one, as the lambda is passed as an argument, read from a field or
+/// returned where `ty` is expected. Later casts of the lambda check this
+/// interface. The branches of an `if`, the result of a block and the blocks
+/// of a `try` are decorated as well.
+pub fn decorate(ct: &ClassTable, term: Term, ty: &Type) -> Term {
+    match (term, ty) {
+        (Term::If(if_term), _) => If {
+            then_term: decorate(ct, *if_term.then_term, ty).boxed(),
+            else_term: decorate(ct, *if_term.else_term, ty).boxed(),
+            ..if_term
+        }
+        .into_term(),
+        (Term::Let(let_term), _) => Let {
+            body: decorate(ct, *let_term.body, ty).boxed(),
+            ..let_term
+        }
+        .into_term(),
+        (Term::Seq(seq), _) => Seq {
+            second: decorate(ct, *seq.second, ty).boxed(),
+            ..seq
+        }
+        .into_term(),
+        (Term::Try(try_term), _) => Try {
+            body: decorate(ct, *try_term.body, ty).boxed(),
+            catches: try_term
+                .catches
+                .into_iter()
+                .map(|catch| Catch {
+                    body: decorate(ct, *catch.body, ty).boxed(),
+                    ..catch
+                })
+                .collect(),
+            ..try_term
+        }
+        .into_term(),
+        (Term::Lambda(lambda), Type::Class(class_type))
+            if lambda.target.is_none() && ct.is_interface(&class_type.name) =>
+        {
+            Lambda {
+                target: Some(class_type.name.clone()),
+                ..lambda
+            }
+            .into_term()
+        }
+        (term, _) => term,
+    }
+}
+
+/// The return type of the method of the functional interface `target` a
+/// lambda was decorated with, to decorate the result of invoking it.
+pub fn lambda_return_type(ct: &ClassTable, target: &ClassName) -> Option<Type> {
+    let class_type = ClassType {
+        name: target.clone(),
+        args: vec![],
+    };
+    typecheck::functional_method(ct, &class_type).map(|(_, method_type)| method_type.ret_type)
+}
+
+/// `lhs op rhs` on primitive values, as computed by E-BinOp. Integer
+/// arithmetic fails on overflow and division by zero instead of wrapping, and
+/// `+` with a `String` operand concatenates the operands.
+pub fn apply_binary(
+    op: BinaryOperator,
+    lhs: &Primitive,
+    rhs: &Primitive,
+    span: Span,
+) -> Result<Primitive, EvalError> {
+    use BinaryOperator::*;
+    use Primitive::{Boolean, Int};
+    let int = |result: Option<i64>| result.map(Int).ok_or(EvalError::IntegerOverflow(span));
+    Ok(match (op, lhs, rhs) {
+        (Or, Boolean(a), Boolean(b)) => Boolean(*a || *b),
+        (And, Boolean(a), Boolean(b)) => Boolean(*a && *b),
+        (Eq, a, b) if a.primitive_type() == b.primitive_type() => Boolean(a == b),
+        (Ne, a, b) if a.primitive_type() == b.primitive_type() => Boolean(a != b),
+        (Lt, Int(a), Int(b)) => Boolean(a < b),
+        (Le, Int(a), Int(b)) => Boolean(a <= b),
+        (Gt, Int(a), Int(b)) => Boolean(a > b),
+        (Ge, Int(a), Int(b)) => Boolean(a >= b),
+        (Add, Primitive::String(_), _) | (Add, _, Primitive::String(_)) => {
+            Primitive::String(format!("{}{}", unquoted(lhs), unquoted(rhs)))
+        }
+        (Add, Int(a), Int(b)) => int(a.checked_add(*b))?,
+        (Sub, Int(a), Int(b)) => int(a.checked_sub(*b))?,
+        (Mul, Int(a), Int(b)) => int(a.checked_mul(*b))?,
+        (Div | Rem, Int(_), Int(0)) => Err(EvalError::DivisionByZero(span))?,
+        (Div, Int(a), Int(b)) => int(a.checked_div(*b))?,
+        (Rem, Int(a), Int(b)) => int(a.checked_rem(*b))?,
+        _ => Err(EvalError::Stuck(
+            BinaryOp {
+                op,
+                lhs: literal(lhs.clone(), span).boxed(),
+                rhs: literal(rhs.clone(), span).boxed(),
+                span,
+            }
+            .into_term()
+            .boxed(),
+        ))?,
+    })
+}
+
+/// `op value` on a primitive value, as computed by E-UnOp.
+pub fn apply_unary(
+    op: UnaryOperator,
+    value: &Primitive,
+    span: Span,
+) -> Result<Primitive, EvalError> {
+    match (op, value) {
+        (UnaryOperator::Not, Primitive::Boolean(b)) => Ok(Primitive::Boolean(!b)),
+        (UnaryOperator::Neg, Primitive::Int(i)) => i
+            .checked_neg()
+            .map(Primitive::Int)
+            .ok_or(EvalError::IntegerOverflow(span)),
+        _ => Err(EvalError::Stuck(
+            UnaryOp {
+                op,
+                term: literal(value.clone(), span).boxed(),
+                span,
+            }
+            .into_term()
+            .boxed(),
+        )),
+    }
+}
+
+/// A primitive value as it is concatenated to a string, strings without
+/// quotes.
+fn unquoted(value: &Primitive) -> String {
+    match value {
+        Primitive::String(s) => s.clone(),
+        value => value.to_string(),
     }
 }
 
+fn literal(value: Primitive, span: Span) -> Term {
+    Literal { value, span }.into_term()
+}
+
 fn substitute_many(in_term: Term, replacements: BTreeMap<&FieldName, Term>) -> Term {
     let mut current = in_term;
     for (to_replace, with_term) in replacements {
@@ -80,128 +828,755 @@ fn substitute_many(in_term: Term, replacements: BTreeMap<&FieldName, Term>) -> T
     current
 }
 
+/// Perform a single reduction step.
 pub fn eval_step(ct: &ClassTable, term: Term) -> Result<Term, EvalError> {
+    eval_step_derivation(ct, term).map(|step| step.term)
+}
+
+/// Perform a single reduction step, recording the rules used.
+pub fn eval_step_derivation(ct: &ClassTable, term: Term) -> Result<Step, EvalError> {
+    let mut rules = Vec::new();
+    let term = step(ct, term, None, &mut rules)?;
+    Ok(Step {
+        term,
+        derivation: Derivation(rules),
+    })
+}
+
+/// Perform a single reduction step with the heap semantics, see
+/// [`eval_heap_with`], recording the rules used.
+pub fn eval_heap_step_derivation(
+    ct: &ClassTable,
+    term: Term,
+    heap: &mut Heap,
+) -> Result<Step, EvalError> {
+    let mut rules = Vec::new();
+    let term = step(ct, term, Some(heap), &mut rules)?;
+    Ok(Step {
+        term,
+        derivation: Derivation(rules),
+    })
+}
+
+/// E-ProjNew and E-ProjLoc: the value of `field` of an object of class
+/// `class_name` with the field values `values`.
+fn project_field(
+    ct: &ClassTable,
+    class_name: &ClassName,
+    values: &[Box<Term>],
+    field: &FieldName,
+    span: Span,
+) -> Result<Term, EvalError> {
+    let fields = ct
+        .fields(class_name)
+        .ok_or(EvalError::UndefinedClass(class_name.clone()))?
+        .collect::<Vec<_>>();
+    if fields.len() != values.len() {
+        Err(EvalError::ConstructorArgumentCount(
+            class_name.clone(),
+            fields.len(),
+            values.len(),
+            span,
+        ))?;
+    }
+    let (i, field_type) = field_position(ct, class_name, field)?;
+    let value = values
+        .get(i)
+        .ok_or(EvalError::ConstructorArgNotFound(i, class_name.clone()))?;
+    Ok(decorate(ct, *value.clone(), &field_type))
+}
+
+/// The position of `field` among the fields of class `class_name`, and its
+/// type.
+fn field_position(
+    ct: &ClassTable,
+    class_name: &ClassName,
+    field: &FieldName,
+) -> Result<(usize, Type), EvalError> {
+    if !ct.contains_class(class_name) {
+        Err(EvalError::UndefinedClass(class_name.clone()))?;
+    }
+    let i = ct
+        .field_index(class_name, field)
+        .ok_or(EvalError::UndefinedField(field.clone(), class_name.clone()))?;
+    // NOTE(unwrap): the class has a field at index `i`
+    let (field_type, _) = ct.field(class_name, i).unwrap();
+    Ok((i, field_type.clone()))
+}
+
+/// E-InvkNew and E-InvkLoc: the body of method `method_name` of the object
+/// `this` of class `class_name`, with `this` and the parameters substituted.
+fn invoke_method(
+    ct: &ClassTable,
+    class_name: &ClassName,
+    this: Term,
+    method_name: &MethodName,
+    arg_terms: Vec<Box<Term>>,
+    span: Span,
+) -> Result<Term, EvalError> {
+    let method =
+        ct.method_definition(method_name, class_name)
+            .ok_or(EvalError::UndefinedMethod(
+                method_name.clone(),
+                class_name.clone(),
+            ))?;
+    if method.args.len() != arg_terms.len() {
+        Err(EvalError::MethodArgumentCount(
+            method_name.clone(),
+            class_name.clone(),
+            method.args.len(),
+            arg_terms.len(),
+            span,
+        ))?;
+    }
+    let this_field = FieldName("this".into());
+    let replacements = iter::once((&this_field, this))
+        .chain(
+            method
+                .args
+                .iter()
+                .zip(arg_terms)
+                .map(|((arg_type, arg_name), arg_term)| {
+                    (arg_name, decorate(ct, *arg_term, arg_type))
+                }),
+        )
+        .collect();
+    let return_term = decorate(ct, erase(*method.return_term.clone()), &method.return_type);
+    Ok(substitute_many(return_term, replacements))
+}
+
+/// E-CastNew and E-CastLoc: the object `object` of class `class_name`, if it
+/// is a subclass of `to_class_name`, and otherwise `throw new
+/// ClassCastException()`.
+fn cast_object(
+    ct: &ClassTable,
+    class_name: &ClassName,
+    object: Term,
+    to_class_name: &ClassName,
+    span: Span,
+) -> Result<Term, EvalError> {
+    if ct
+        .is_subtype(class_name, to_class_name)
+        .ok_or(EvalError::UndefinedClasses(vec![
+            class_name.clone(),
+            to_class_name.clone(),
+        ]))?
+    {
+        Ok(object)
+    } else {
+        Ok(class_cast_exception(span))
+    }
+}
+
+/// `throw new ClassCastException()`, the result of a failing cast at `span`.
+fn class_cast_exception(span: Span) -> Term {
+    Throw {
+        term: NewCall {
+            class_name: ClassName::class_cast_exception(),
+            type_args: vec![],
+            arg_terms: vec![],
+            span,
+        }
+        .into_term()
+        .boxed(),
+        span,
+    }
+    .into_term()
+}
+
+/// The `throw v` at which `term` is stuck, if `term` is `E[throw v]` for an
+/// evaluation context `E` without a `try`. E-Catch and E-Rethrow handle
+/// such an exception in a single step, whatever `E` is.
+fn thrown(term: &Term, with_heap: bool) -> Option<&Throw> {
+    // the subterm the congruence rules reduce next
+    let next = match term {
+        Term::Throw(throw) if is_value(&throw.term, with_heap) => return Some(throw),
+        Term::Throw(Throw { term, .. })
+        | Term::Cast(Cast { term, .. })
+        | Term::UnaryOp(UnaryOp { term, .. }) => Some(term),
+        Term::FieldAccess(FieldAccess { object_term, .. }) => Some(object_term),
+        Term::MethodCall(MethodCall {
+            object_term,
+            arg_terms,
+            ..
+        }) => iter::once(object_term)
+            .chain(arg_terms)
+            .find(|t| !is_value(t, with_heap)),
+        Term::NewCall(NewCall { arg_terms, .. }) => {
+            arg_terms.iter().find(|t| !is_value(t, with_heap))
+        }
+        // the right operand of `&&` and `||` is not reduced in place
+        Term::BinaryOp(BinaryOp {
+            op: BinaryOperator::And | BinaryOperator::Or,
+            lhs,
+            ..
+        }) => Some(lhs),
+        Term::BinaryOp(BinaryOp { lhs, rhs, .. }) => {
+            [lhs, rhs].into_iter().find(|t| !is_value(t, with_heap))
+        }
+        Term::If(If { condition, .. }) => Some(condition),
+        Term::FieldAssign(FieldAssign {
+            object_term,
+            value_term,
+            ..
+        }) => [object_term, value_term]
+            .into_iter()
+            .find(|t| !is_value(t, with_heap)),
+        Term::Let(Let { value_term, .. }) => Some(value_term),
+        Term::Seq(Seq { first, .. }) => Some(first),
+        Term::Variable(_)
+        | Term::Lambda(_)
+        | Term::Literal(_)
+        | Term::Location(_)
+        | Term::Try(_) => None,
+    };
+    next.and_then(|term| thrown(term, with_heap))
+}
+
+/// The class of the exception `exception`, an object or, with a heap, its
+/// location.
+fn exception_class(heap: Option<&mut Heap>, exception: &Term) -> Result<ClassName, EvalError> {
+    match exception {
+        Term::NewCall(nc) => Ok(nc.class_name.clone()),
+        Term::Location(location) => Ok(heap_object(heap, location)?.class_name.clone()),
+        t => Err(EvalError::Stuck(t.clone().boxed())),
+    }
+}
+
+/// The object at `location` on `heap`, which is `None` without a heap.
+fn heap_object<'h>(
+    heap: Option<&'h mut Heap>,
+    location: &Location,
+) -> Result<&'h mut HeapObject, EvalError> {
+    heap.and_then(|heap| heap.objects.get_mut(location.id))
+        .ok_or_else(|| EvalError::Stuck(location.clone().into_term().boxed()))
+}
+
+/// Reduce `term` by a single step, pushing the rules used to `rules`. With a
+/// heap, `new` allocates objects on it and objects are only values as their
+/// locations.
+fn step(
+    ct: &ClassTable,
+    term: Term,
+    heap: Option<&mut Heap>,
+    rules: &mut Vec<EvalRule>,
+) -> Result<Term, EvalError> {
+    let with_heap = heap.is_some();
+    let value = |term: &Term| is_value(term, with_heap);
     match term {
-        Term::FieldAccess(FieldAccess { field, object_term }) => match *object_term {
+        Term::FieldAccess(FieldAccess {
+            field,
+            object_term,
+            span,
+        }) => match *object_term {
             // E-ProjNew
-            Term::NewCall(nc) if nc.has_only_value_args() => {
-                // check that field in class
-                let (i, _) = ct
-                    .fields(&nc.class_name)
-                    .ok_or(EvalError::UndefinedClass(nc.class_name.clone()))?
-                    .enumerate()
-                    .find(|(_, (_, field_name))| field_name == &field)
-                    .ok_or(EvalError::UndefinedField(
-                        field.clone(),
-                        nc.class_name.clone(),
-                    ))?;
-                Ok(*nc
-                    .arg_terms
-                    .get(i)
-                    .ok_or(EvalError::ConstructorArgNotFound(i, nc.class_name.clone()))?
-                    .clone())
+            Term::NewCall(nc) if !with_heap && nc.has_only_value_args() => {
+                rules.push(EvalRule::ProjNew);
+                project_field(ct, &nc.class_name, &nc.arg_terms, &field, nc.span)
+            }
+            // E-ProjLoc
+            Term::Location(location) if with_heap => {
+                rules.push(EvalRule::ProjLoc);
+                let object = heap_object(heap, &location)?;
+                project_field(
+                    ct,
+                    &object.class_name,
+                    &object.fields,
+                    &field,
+                    location.span,
+                )
             }
             // E-Field
-            t => Ok(FieldAccess {
-                field,
-                object_term: eval_step(ct, t)?.boxed(),
+            t => {
+                rules.push(EvalRule::Field);
+                Ok(FieldAccess {
+                    field,
+                    object_term: step(ct, t, heap, rules)?.boxed(),
+                    span,
+                }
+                .into_term())
             }
-            .into_term()),
         },
         Term::MethodCall(MethodCall {
             mut arg_terms,
             method_name,
+            type_args,
             object_term,
+            span,
         }) => match *object_term {
             // E-Invk-New
             // NOTE: object terms be also only be values here
             Term::NewCall(nc)
-                if nc.has_only_value_args() && arg_terms.iter().all(|u| u.is_value()) =>
+                if !with_heap && nc.has_only_value_args() && arg_terms.iter().all(|u| value(u)) =>
             {
-                let method_body = ct.method_body(&method_name, &nc.class_name).ok_or(
-                    EvalError::UndefinedMethod(method_name.clone(), nc.class_name.clone()),
-                )?;
-                let this_field = FieldName("this".into());
-                let replacements = iter::once((&this_field, nc.into_term()))
-                    .chain(
-                        method_body
-                            .args
-                            .iter()
-                            .zip(arg_terms.into_iter().map(|t| *t)),
-                    )
+                rules.push(EvalRule::InvkNew);
+                let class_name = nc.class_name.clone();
+                invoke_method(
+                    ct,
+                    &class_name,
+                    nc.into_term(),
+                    &method_name,
+                    arg_terms,
+                    span,
+                )
+            }
+            // E-InvkLoc
+            Term::Location(location) if with_heap && arg_terms.iter().all(|u| value(u)) => {
+                rules.push(EvalRule::InvkLoc);
+                let class_name = heap_object(heap, &location)?.class_name.clone();
+                invoke_method(
+                    ct,
+                    &class_name,
+                    location.into_term(),
+                    &method_name,
+                    arg_terms,
+                    span,
+                )
+            }
+            // E-InvkLambda
+            Term::Lambda(lambda) if arg_terms.iter().all(|u| value(u)) => {
+                rules.push(EvalRule::InvkLambda);
+                if lambda.args.len() != arg_terms.len() {
+                    Err(EvalError::LambdaArgumentCount(
+                        lambda.args.len(),
+                        arg_terms.len(),
+                        span,
+                    ))?;
+                }
+                let replacements = lambda
+                    .args
+                    .iter()
+                    .zip(arg_terms)
+                    .map(|((arg_type, arg_name), arg_term)| {
+                        (arg_name, decorate(ct, *arg_term, arg_type))
+                    })
                     .collect();
-                Ok(substitute_many(*method_body.return_term, replacements))
+                let body = match lambda
+                    .target
+                    .and_then(|target| lambda_return_type(ct, &target))
+                {
+                    Some(ret_type) => decorate(ct, *lambda.body, &ret_type),
+                    None => *lambda.body,
+                };
+                Ok(substitute_many(body, replacements))
             }
             // E-InvkArg
-            v if v.is_value() => {
+            v if value(&v) => {
                 // NOTE(unwrap): safe because of previous match arm
                 let (first_non_value, _) = arg_terms
                     .iter()
                     .enumerate()
-                    .find(|(_, t)| !t.is_value())
+                    .find(|(_, t)| !value(t))
                     .unwrap();
+                rules.push(EvalRule::InvkArg);
                 arg_terms[first_non_value] =
-                    eval_step(ct, *arg_terms[first_non_value].clone())?.boxed();
+                    step(ct, *arg_terms[first_non_value].clone(), heap, rules)?.boxed();
                 Ok(MethodCall {
                     arg_terms,
                     method_name,
+                    type_args,
                     object_term: v.boxed(),
+                    span,
                 }
                 .into_term())
             }
             // E-InvkRecv
-            t => Ok(MethodCall {
-                arg_terms,
-                method_name,
-                object_term: eval_step(ct, t)?.boxed(),
+            t => {
+                rules.push(EvalRule::InvkRecv);
+                Ok(MethodCall {
+                    arg_terms,
+                    method_name,
+                    type_args,
+                    object_term: step(ct, t, heap, rules)?.boxed(),
+                    span,
+                }
+                .into_term())
             }
-            .into_term()),
         },
         Term::Cast(Cast {
             term,
             to_class_name,
+            type_args,
+            span,
         }) => match *term {
             // E-CastNew
-            Term::NewCall(nc) if nc.has_only_value_args() => {
-                if ct.is_subtype(&nc.class_name, &to_class_name).ok_or(
-                    EvalError::UndefinedClasses(vec![nc.class_name.clone(), to_class_name.clone()]),
-                )? {
-                    Ok(nc.into_term())
-                } else {
-                    Err(EvalError::CastFailed {
-                        from: nc.class_name.clone(),
-                        to: to_class_name.clone(),
-                    })?
+            Term::NewCall(nc) if !with_heap && nc.has_only_value_args() => {
+                rules.push(EvalRule::CastNew);
+                let class_name = nc.class_name.clone();
+                cast_object(ct, &class_name, nc.into_term(), &to_class_name, span)
+            }
+            // E-CastLoc
+            Term::Location(location) if with_heap => {
+                rules.push(EvalRule::CastLoc);
+                let class_name = heap_object(heap, &location)?.class_name.clone();
+                cast_object(ct, &class_name, location.into_term(), &to_class_name, span)
+            }
+            // E-CastLambda
+            Term::Lambda(lambda) => {
+                rules.push(EvalRule::CastLambda);
+                match &lambda.target {
+                    None => Ok(Lambda {
+                        target: Some(to_class_name),
+                        ..lambda
+                    }
+                    .into_term()),
+                    Some(target) if ct.is_subtype(target, &to_class_name) == Some(true) => {
+                        Ok(lambda.into_term())
+                    }
+                    Some(_) => Ok(class_cast_exception(span)),
                 }
             }
             // E-Cast
-            t => Ok(Cast {
-                to_class_name,
-                term: eval_step(ct, t)?.boxed(),
+            t => {
+                rules.push(EvalRule::Cast);
+                Ok(Cast {
+                    to_class_name,
+                    type_args,
+                    term: step(ct, t, heap, rules)?.boxed(),
+                    span,
+                }
+                .into_term())
+            }
+        },
+        Term::BinaryOp(BinaryOp { op, lhs, rhs, span }) => match (*lhs, *rhs) {
+            // E-BinOp, where `&&` and `||` short-circuit
+            (
+                Term::Literal(Literal {
+                    value: Primitive::Boolean(b),
+                    span: lhs_span,
+                }),
+                rhs,
+            ) if matches!(op, BinaryOperator::And | BinaryOperator::Or) => {
+                rules.push(EvalRule::BinOp);
+                if b == (op == BinaryOperator::Or) {
+                    Ok(literal(Primitive::Boolean(b), lhs_span))
+                } else {
+                    Ok(rhs)
+                }
+            }
+            // E-BinOp
+            (Term::Literal(lhs), Term::Literal(rhs)) => {
+                rules.push(EvalRule::BinOp);
+                Ok(literal(
+                    apply_binary(op, &lhs.value, &rhs.value, span)?,
+                    span,
+                ))
+            }
+            // E-BinOp-Left
+            (lhs, rhs) if !value(&lhs) => {
+                rules.push(EvalRule::BinOpLeft);
+                Ok(BinaryOp {
+                    op,
+                    lhs: step(ct, lhs, heap, rules)?.boxed(),
+                    rhs: rhs.boxed(),
+                    span,
+                }
+                .into_term())
+            }
+            // E-BinOp-Right
+            (lhs, rhs) if !value(&rhs) => {
+                rules.push(EvalRule::BinOpRight);
+                Ok(BinaryOp {
+                    op,
+                    lhs: lhs.boxed(),
+                    rhs: step(ct, rhs, heap, rules)?.boxed(),
+                    span,
+                }
+                .into_term())
             }
-            .into_term()),
+            (lhs, rhs) => Err(EvalError::Stuck(
+                BinaryOp {
+                    op,
+                    lhs: lhs.boxed(),
+                    rhs: rhs.boxed(),
+                    span,
+                }
+                .into_term()
+                .boxed(),
+            ))?,
         },
+        Term::UnaryOp(UnaryOp { op, term, span }) => match *term {
+            // E-UnOp
+            Term::Literal(Literal { value, .. }) => {
+                rules.push(EvalRule::UnOp);
+                Ok(literal(apply_unary(op, &value, span)?, span))
+            }
+            // E-UnOp-Arg
+            t if !value(&t) => {
+                rules.push(EvalRule::UnOpArg);
+                Ok(UnaryOp {
+                    op,
+                    term: step(ct, t, heap, rules)?.boxed(),
+                    span,
+                }
+                .into_term())
+            }
+            t => Err(EvalError::Stuck(
+                UnaryOp {
+                    op,
+                    term: t.boxed(),
+                    span,
+                }
+                .into_term()
+                .boxed(),
+            ))?,
+        },
+        Term::If(If {
+            condition,
+            then_term,
+            else_term,
+            span,
+        }) => match *condition {
+            // E-IfTrue
+            Term::Literal(Literal {
+                value: Primitive::Boolean(true),
+                ..
+            }) => {
+                rules.push(EvalRule::IfTrue);
+                Ok(*then_term)
+            }
+            // E-IfFalse
+            Term::Literal(Literal {
+                value: Primitive::Boolean(false),
+                ..
+            }) => {
+                rules.push(EvalRule::IfFalse);
+                Ok(*else_term)
+            }
+            // E-If-Cond
+            t if !value(&t) => {
+                rules.push(EvalRule::IfCond);
+                Ok(If {
+                    condition: step(ct, t, heap, rules)?.boxed(),
+                    then_term,
+                    else_term,
+                    span,
+                }
+                .into_term())
+            }
+            t => Err(EvalError::Stuck(
+                If {
+                    condition: t.boxed(),
+                    then_term,
+                    else_term,
+                    span,
+                }
+                .into_term()
+                .boxed(),
+            ))?,
+        },
+        Term::Let(Let {
+            ty,
+            name,
+            value_term,
+            body,
+            span,
+        }) => match *value_term {
+            // E-Let
+            v if value(&v) => {
+                rules.push(EvalRule::Let);
+                Ok(substitute(*body, &name, decorate(ct, v, &ty)))
+            }
+            // E-Let-Init
+            t => {
+                rules.push(EvalRule::LetInit);
+                Ok(Let {
+                    ty,
+                    name,
+                    value_term: step(ct, t, heap, rules)?.boxed(),
+                    body,
+                    span,
+                }
+                .into_term())
+            }
+        },
+        Term::Seq(Seq {
+            first,
+            second,
+            span,
+        }) => match *first {
+            // E-Seq
+            v if value(&v) => {
+                rules.push(EvalRule::Seq);
+                Ok(*second)
+            }
+            // E-Seq-First
+            t => {
+                rules.push(EvalRule::SeqFirst);
+                Ok(Seq {
+                    first: step(ct, t, heap, rules)?.boxed(),
+                    second,
+                    span,
+                }
+                .into_term())
+            }
+        },
+        Term::FieldAssign(FieldAssign { span, .. }) if !with_heap => {
+            Err(EvalError::AssignmentWithoutHeap(span))
+        }
+        Term::FieldAssign(FieldAssign {
+            object_term,
+            field,
+            value_term,
+            span,
+        }) => match (*object_term, *value_term) {
+            // E-Assign
+            (Term::Location(location), v) if value(&v) => {
+                rules.push(EvalRule::Assign);
+                let object = heap_object(heap, &location)?;
+                let (i, _) = field_position(ct, &object.class_name, &field)?;
+                let slot = object
+                    .fields
+                    .get_mut(i)
+                    .ok_or(EvalError::ConstructorArgNotFound(
+                        i,
+                        object.class_name.clone(),
+                    ))?;
+                *slot = v.clone().boxed();
+                Ok(v)
+            }
+            // E-Assign-Obj
+            (t, value_term) if !value(&t) => {
+                rules.push(EvalRule::AssignObj);
+                Ok(FieldAssign {
+                    object_term: step(ct, t, heap, rules)?.boxed(),
+                    field,
+                    value_term: value_term.boxed(),
+                    span,
+                }
+                .into_term())
+            }
+            // E-Assign-Val
+            (v, t) if !value(&t) => {
+                rules.push(EvalRule::AssignVal);
+                Ok(FieldAssign {
+                    object_term: v.boxed(),
+                    field,
+                    value_term: step(ct, t, heap, rules)?.boxed(),
+                    span,
+                }
+                .into_term())
+            }
+            (v, w) => Err(EvalError::Stuck(
+                FieldAssign {
+                    object_term: v.boxed(),
+                    field,
+                    value_term: w.boxed(),
+                    span,
+                }
+                .into_term()
+                .boxed(),
+            ))?,
+        },
+        Term::Throw(Throw { term, span }) => match *term {
+            // an exception not handled by any `try`, reported with its
+            // object also with a heap
+            Term::Location(location) if with_heap => {
+                let object = heap_object(heap, &location)?;
+                let exception = NewCall {
+                    class_name: object.class_name.clone(),
+                    type_args: vec![],
+                    arg_terms: object.fields.clone(),
+                    span: location.span,
+                };
+                Err(EvalError::UncaughtException(
+                    exception.into_term().boxed(),
+                    span,
+                ))
+            }
+            v if value(&v) => Err(EvalError::UncaughtException(v.boxed(), span)),
+            // E-Throw
+            t => {
+                rules.push(EvalRule::Throw);
+                Ok(Throw {
+                    term: step(ct, t, heap, rules)?.boxed(),
+                    span,
+                }
+                .into_term())
+            }
+        },
+        Term::Try(Try {
+            body,
+            catches,
+            span,
+        }) => {
+            // E-TryValue
+            if value(&body) {
+                rules.push(EvalRule::TryValue);
+                return Ok(*body);
+            }
+            if let Some(throw) = thrown(&body, with_heap).cloned() {
+                let class_name = exception_class(heap, &throw.term)?;
+                let catch = catches
+                    .into_iter()
+                    .find(|catch| ct.is_subtype(&class_name, &catch.class_name) == Some(true));
+                return match catch {
+                    // E-Catch
+                    Some(catch) => {
+                        rules.push(EvalRule::Catch);
+                        Ok(substitute(*catch.body, &catch.name, *throw.term))
+                    }
+                    // E-Rethrow
+                    None => {
+                        rules.push(EvalRule::Rethrow);
+                        Ok(throw.into_term())
+                    }
+                };
+            }
+            // E-Try
+            rules.push(EvalRule::Try);
+            Ok(Try {
+                body: step(ct, *body, heap, rules)?.boxed(),
+                catches,
+                span,
+            }
+            .into_term())
+        }
         // values evaluate to themself
-        Term::NewCall(nc) if nc.has_only_value_args() => Ok(nc.into_term()),
+        Term::NewCall(nc) if !with_heap && nc.has_only_value_args() => Ok(nc.into_term()),
+        Term::Location(location) if with_heap => Ok(location.into_term()),
+        Term::Lambda(lambda) => Ok(lambda.into_term()),
+        Term::Literal(literal) => Ok(literal.into_term()),
+        // E-New
+        Term::NewCall(NewCall {
+            arg_terms,
+            class_name,
+            span,
+            ..
+        }) if arg_terms.iter().all(|t| value(t)) => {
+            rules.push(EvalRule::New);
+            // NOTE(unwrap): without a heap such a term is a value, matched above
+            let heap = heap.unwrap();
+            let id = heap.alloc(HeapObject {
+                class_name,
+                fields: arg_terms,
+            });
+            Ok(Location { id, span }.into_term())
+        }
         // E-New-Arg
         Term::NewCall(NewCall {
             mut arg_terms,
             class_name,
+            type_args,
+            span,
         }) => {
             // NOTE(unwrap): safe because of previous match arm
             let (first_non_value, _) = arg_terms
                 .iter()
                 .enumerate()
-                .find(|(_, t)| !t.is_value())
+                .find(|(_, t)| !value(t))
                 .unwrap();
+            rules.push(EvalRule::NewArg);
             arg_terms[first_non_value] =
-                eval_step(ct, *arg_terms[first_non_value].clone())?.boxed();
+                step(ct, *arg_terms[first_non_value].clone(), heap, rules)?.boxed();
             Ok(NewCall {
                 arg_terms,
                 class_name,
+                type_args,
+                span,
             }
             .into_term())
         }
-        _ => Err(EvalError::Stuck(term))?,
+        _ => Err(EvalError::Stuck(term.boxed()))?,
     }
 }
@@ -0,0 +1,631 @@
+//! Big-step, environment-based interpreter.
+//!
+//! Instead of rewriting the term tree like [`crate::evaluation::eval_step`],
+//! terms are evaluated directly to shared [`Object`]s, with method arguments
+//! bound in an environment. On well-typed terms this yields the same values
+//! as [`crate::evaluation::eval_full`]. Type arguments are ignored, as in
+//! [`crate::evaluation::erase`].
+
+use crate::ast::*;
+use crate::class_table::ClassTable;
+use crate::error::EvalError;
+use crate::evaluation::{self, EvalLimits};
+use std::fmt::Display;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Size of the stack of the thread the interpreter and the small-step
+/// evaluators run on, see [`on_large_stack`]. Only the pages that are used
+/// are allocated.
+pub const STACK_SIZE: usize = 1 << 30;
+
+/// Stack space kept free for evaluating the body of a method up to its next
+/// method invocation. Evaluation is aborted instead of invoking a method with
+/// less space left, so the nesting of method invocations is only limited by
+/// [`STACK_SIZE`].
+const STACK_RESERVE: usize = 64 << 20;
+
+/// A value the interpreter evaluates to.
+#[derive(Debug)]
+pub enum Object<'a> {
+    /// An object `new C(v̄)`, with one value per field of `C` in constructor
+    /// order.
+    New {
+        class_name: ClassName,
+        fields: Vec<Value<'a>>,
+    },
+    /// A lambda together with the values of the variables it closes over,
+    /// decorated with its target interface as in [`evaluation::decorate`].
+    Closure {
+        lambda: &'a Lambda,
+        env: Env<'a>,
+        target: Option<ClassName>,
+    },
+    /// A value of a primitive type.
+    Primitive(Primitive),
+}
+
+pub type Value<'a> = Rc<Object<'a>>;
+
+impl Object<'_> {
+    /// The value as a term `new C(v̄)`, a lambda, with the variables it
+    /// closes over substituted, or a literal.
+    pub fn to_term(&self) -> Term {
+        match self {
+            Object::New { class_name, fields } => NewCall {
+                class_name: class_name.clone(),
+                type_args: vec![],
+                arg_terms: fields.iter().map(|field| field.to_term().boxed()).collect(),
+                span: Span::default(),
+            }
+            .into_term(),
+            Object::Closure {
+                lambda,
+                env,
+                target,
+            } => {
+                let mut body = evaluation::erase((*lambda.body).clone());
+                for (name, value) in env.iter().rev() {
+                    let var = FieldName(name.to_string());
+                    if lambda.args.iter().all(|(_, arg)| arg != &var) {
+                        body = evaluation::substitute(body, &var, value.to_term());
+                    }
+                }
+                Lambda {
+                    args: lambda.args.clone(),
+                    body: body.boxed(),
+                    target: target.clone(),
+                    span: lambda.span,
+                }
+                .into_term()
+            }
+            Object::Primitive(value) => Literal {
+                value: value.clone(),
+                span: Span::default(),
+            }
+            .into_term(),
+        }
+    }
+}
+
+impl Display for Object<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_term())
+    }
+}
+
+/// Why the evaluation of a term did not result in a value.
+enum Unwind<'a> {
+    /// The exception `value`, thrown at `span`, which is not caught yet.
+    Throw(Value<'a>, Span),
+    Error(EvalError),
+}
+
+impl From<EvalError> for Unwind<'_> {
+    fn from(error: EvalError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+/// Values of `this`, the method and lambda arguments and local variables, the
+/// innermost last.
+pub type Env<'a> = Vec<(&'a str, Value<'a>)>;
+
+struct Interpreter<'a> {
+    ct: &'a ClassTable,
+    limits: &'a EvalLimits,
+    start: Instant,
+    /// Number of computation steps (E-ProjNew, E-InvkNew, E-CastNew,
+    /// E-InvkLambda, E-CastLambda, E-BinOp, E-UnOp, E-IfTrue, E-IfFalse,
+    /// E-Let, E-Seq, E-TryValue, E-Catch, E-Rethrow) so far, equal to the
+    /// number of steps `eval_full` would take.
+    steps: usize,
+    depth: usize,
+    /// Address of the stack when evaluation started, see [`stack_address`].
+    stack_start: usize,
+}
+
+/// Evaluate the closed term `term` to a value within `limits`.
+///
+/// The interpreter runs on a separate thread with a stack of [`STACK_SIZE`],
+/// as every nested method invocation takes a few stack frames. Cycle
+/// detection is not supported, a diverging term is stopped by the step or
+/// time limit or by running out of stack.
+pub fn eval(ct: &ClassTable, term: &Term, limits: &EvalLimits) -> Result<Term, EvalError> {
+    on_large_stack(|| eval_value(ct, term, limits).map(|value| value.to_term()))
+}
+
+/// Run `f` on a separate thread with a stack of [`STACK_SIZE`], passing on
+/// its result or panic.
+pub(crate) fn on_large_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        let evaluator = std::thread::Builder::new()
+            .name("evaluator".into())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            // NOTE(unwrap): only fails if the operating system can not create threads
+            .unwrap();
+        evaluator
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+fn eval_value<'a>(
+    ct: &'a ClassTable,
+    term: &'a Term,
+    limits: &'a EvalLimits,
+) -> Result<Value<'a>, EvalError> {
+    Interpreter {
+        ct,
+        limits,
+        start: Instant::now(),
+        steps: 0,
+        depth: 0,
+        stack_start: stack_address(),
+    }
+    .eval(&Env::new(), term)
+    .map_err(|unwind| match unwind {
+        Unwind::Throw(exception, span) => {
+            EvalError::UncaughtException(exception.to_term().boxed(), span)
+        }
+        Unwind::Error(error) => error,
+    })
+}
+
+impl<'a> Interpreter<'a> {
+    // every rule is a separate method to keep the stack frames of the recursion small
+    fn eval(&mut self, env: &Env<'a>, term: &'a Term) -> Result<Value<'a>, Unwind<'a>> {
+        match term {
+            Term::Variable(Variable { name, .. }) => env
+                .iter()
+                .rev()
+                .find(|(var, _)| *var == name.0)
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| EvalError::Stuck(term.clone().boxed()).into()),
+            Term::FieldAccess(field_access) => self.eval_field_access(env, term, field_access),
+            Term::MethodCall(method_call) => self.eval_method_call(env, term, method_call),
+            Term::NewCall(new_call) => self.eval_new_call(env, new_call),
+            Term::Cast(cast) => self.eval_cast(env, term, cast),
+            Term::Lambda(lambda) => Ok(Rc::new(Object::Closure {
+                lambda,
+                env: env.clone(),
+                target: lambda.target.clone(),
+            })),
+            Term::Literal(Literal { value, .. }) => Ok(Rc::new(Object::Primitive(value.clone()))),
+            Term::BinaryOp(binary_op) => self.eval_binary_op(env, term, binary_op),
+            Term::UnaryOp(unary_op) => self.eval_unary_op(env, term, unary_op),
+            Term::If(if_term) => self.eval_if(env, term, if_term),
+            Term::Let(let_term) => self.eval_let(env, term, let_term),
+            Term::Seq(seq) => self.eval_seq(env, term, seq),
+            Term::FieldAssign(FieldAssign { span, .. }) => {
+                Err(EvalError::AssignmentWithoutHeap(*span))?
+            }
+            Term::Location(_) => Err(EvalError::Stuck(term.clone().boxed()))?,
+            Term::Throw(throw) => self.eval_throw(env, throw),
+            Term::Try(try_term) => self.eval_try(env, term, try_term),
+        }
+    }
+
+    // E-ProjNew
+    #[inline(never)]
+    fn eval_field_access(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        FieldAccess {
+            object_term, field, ..
+        }: &'a FieldAccess,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let object = self.eval(env, object_term)?;
+        self.step(env, term)?;
+        let Object::New { class_name, fields } = &*object else {
+            Err(EvalError::Stuck(term.clone().boxed()))?
+        };
+        let i = self
+            .ct
+            .field_index(class_name, field)
+            .ok_or_else(|| EvalError::UndefinedField(field.clone(), class_name.clone()))?;
+        let value = fields
+            .get(i)
+            .cloned()
+            .ok_or_else(|| EvalError::ConstructorArgNotFound(i, class_name.clone()))?;
+        // NOTE(unwrap): the class has a field at index `i`
+        let (field_type, _) = self.ct.field(class_name, i).unwrap();
+        Ok(self.decorate(value, field_type))
+    }
+
+    // E-InvkNew, E-InvkLambda
+    #[inline(never)]
+    fn eval_method_call(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        MethodCall {
+            object_term,
+            method_name,
+            arg_terms,
+            span,
+            ..
+        }: &'a MethodCall,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let object = self.eval(env, object_term)?;
+        let args = self.eval_all(env, arg_terms)?;
+        self.step(env, term)?;
+        let (method_env, body, ret_type) = match &*object {
+            Object::New { class_name, .. } => {
+                let method = self
+                    .ct
+                    .method_definition(method_name, class_name)
+                    .ok_or_else(|| {
+                        EvalError::UndefinedMethod(method_name.clone(), class_name.clone())
+                    })?;
+                if method.args.len() != args.len() {
+                    Err(EvalError::MethodArgumentCount(
+                        method_name.clone(),
+                        class_name.clone(),
+                        method.args.len(),
+                        args.len(),
+                        *span,
+                    ))?;
+                }
+                let method_env = std::iter::once(("this", object.clone()))
+                    .chain(method.args.iter().zip(args).map(|((arg_type, name), arg)| {
+                        (name.0.as_str(), self.decorate(arg, arg_type))
+                    }))
+                    .collect();
+                (
+                    method_env,
+                    &*method.return_term,
+                    Some(method.return_type.clone()),
+                )
+            }
+            Object::Primitive(_) => Err(EvalError::Stuck(term.clone().boxed()))?,
+            Object::Closure {
+                lambda,
+                env,
+                target,
+            } => {
+                if lambda.args.len() != args.len() {
+                    Err(EvalError::LambdaArgumentCount(
+                        lambda.args.len(),
+                        args.len(),
+                        *span,
+                    ))?;
+                }
+                let mut lambda_env = env.clone();
+                lambda_env.extend(lambda.args.iter().zip(args).map(|((arg_type, name), arg)| {
+                    (name.0.as_str(), self.decorate(arg, arg_type))
+                }));
+                let ret_type = target
+                    .as_ref()
+                    .and_then(|target| evaluation::lambda_return_type(self.ct, target));
+                (lambda_env, &*lambda.body, ret_type)
+            }
+        };
+
+        if self.stack_start.abs_diff(stack_address()) > STACK_SIZE - STACK_RESERVE {
+            Err(EvalError::CallDepthExceeded(
+                self.depth,
+                close(env, term).boxed(),
+            ))?;
+        }
+        self.depth += 1;
+        let result = self.eval(&method_env, body);
+        self.depth -= 1;
+        Ok(match ret_type {
+            Some(ret_type) => self.decorate(result?, &ret_type),
+            None => result?,
+        })
+    }
+
+    #[inline(never)]
+    fn eval_new_call(
+        &mut self,
+        env: &Env<'a>,
+        NewCall {
+            class_name,
+            arg_terms,
+            span,
+            ..
+        }: &'a NewCall,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let fields = self.eval_all(env, arg_terms)?;
+        let field_count = self
+            .ct
+            .fields(class_name)
+            .ok_or_else(|| EvalError::UndefinedClass(class_name.clone()))?
+            .count();
+        if field_count != fields.len() {
+            Err(EvalError::ConstructorArgumentCount(
+                class_name.clone(),
+                field_count,
+                fields.len(),
+                *span,
+            ))?;
+        }
+        Ok(Rc::new(Object::New {
+            class_name: class_name.clone(),
+            fields,
+        }))
+    }
+
+    // E-CastNew, E-CastLambda, throwing a `ClassCastException` if they fail
+    #[inline(never)]
+    fn eval_cast(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        Cast {
+            to_class_name,
+            term: object_term,
+            span,
+            ..
+        }: &'a Cast,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let object = self.eval(env, object_term)?;
+        self.step(env, term)?;
+        let class_name = match &*object {
+            Object::New { class_name, .. } => class_name,
+            Object::Closure {
+                lambda,
+                env,
+                target: None,
+            } => {
+                return Ok(Rc::new(Object::Closure {
+                    lambda,
+                    env: env.clone(),
+                    target: Some(to_class_name.clone()),
+                }))
+            }
+            Object::Closure {
+                target: Some(target),
+                ..
+            } => target,
+            Object::Primitive(_) => Err(EvalError::Stuck(term.clone().boxed()))?,
+        };
+        if self
+            .ct
+            .is_subtype(class_name, to_class_name)
+            .ok_or_else(|| {
+                EvalError::UndefinedClasses(vec![class_name.clone(), to_class_name.clone()])
+            })?
+        {
+            Ok(object)
+        } else {
+            let exception = Object::New {
+                class_name: ClassName::class_cast_exception(),
+                fields: vec![],
+            };
+            Err(Unwind::Throw(Rc::new(exception), *span))
+        }
+    }
+
+    // E-BinOp
+    #[inline(never)]
+    fn eval_binary_op(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        BinaryOp { op, lhs, rhs, span }: &'a BinaryOp,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let lhs = self.eval(env, lhs)?;
+        let Object::Primitive(lhs_value) = &*lhs else {
+            Err(EvalError::Stuck(term.clone().boxed()))?
+        };
+        // `&&` and `||` reduce to their right operand, if their left one
+        // does not determine the result
+        if let (BinaryOperator::And | BinaryOperator::Or, Primitive::Boolean(b)) = (op, lhs_value) {
+            self.step(env, term)?;
+            return if *b == (*op == BinaryOperator::Or) {
+                Ok(lhs)
+            } else {
+                self.eval(env, rhs)
+            };
+        }
+        let rhs = self.eval(env, rhs)?;
+        self.step(env, term)?;
+        let Object::Primitive(rhs_value) = &*rhs else {
+            Err(EvalError::Stuck(term.clone().boxed()))?
+        };
+        Ok(Rc::new(Object::Primitive(evaluation::apply_binary(
+            *op, lhs_value, rhs_value, *span,
+        )?)))
+    }
+
+    // E-UnOp
+    #[inline(never)]
+    fn eval_unary_op(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        UnaryOp {
+            op,
+            term: arg,
+            span,
+        }: &'a UnaryOp,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let value = self.eval(env, arg)?;
+        self.step(env, term)?;
+        let Object::Primitive(value) = &*value else {
+            Err(EvalError::Stuck(term.clone().boxed()))?
+        };
+        Ok(Rc::new(Object::Primitive(evaluation::apply_unary(
+            *op, value, *span,
+        )?)))
+    }
+
+    // E-IfTrue, E-IfFalse
+    #[inline(never)]
+    fn eval_if(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        If {
+            condition,
+            then_term,
+            else_term,
+            ..
+        }: &'a If,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let condition = self.eval(env, condition)?;
+        self.step(env, term)?;
+        match &*condition {
+            Object::Primitive(Primitive::Boolean(true)) => self.eval(env, then_term),
+            Object::Primitive(Primitive::Boolean(false)) => self.eval(env, else_term),
+            _ => Err(EvalError::Stuck(term.clone().boxed()))?,
+        }
+    }
+
+    // E-Let
+    #[inline(never)]
+    fn eval_let(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        Let {
+            ty,
+            name,
+            value_term,
+            body,
+            ..
+        }: &'a Let,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let value = self.eval(env, value_term)?;
+        self.step(env, term)?;
+        let mut body_env = env.clone();
+        body_env.push((name.0.as_str(), self.decorate(value, ty)));
+        self.eval(&body_env, body)
+    }
+
+    // E-Seq
+    #[inline(never)]
+    fn eval_seq(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        Seq { first, second, .. }: &'a Seq,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        self.eval(env, first)?;
+        self.step(env, term)?;
+        self.eval(env, second)
+    }
+
+    #[inline(never)]
+    fn eval_throw(
+        &mut self,
+        env: &Env<'a>,
+        Throw {
+            term: exception,
+            span,
+        }: &'a Throw,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let exception = self.eval(env, exception)?;
+        Err(Unwind::Throw(exception, *span))
+    }
+
+    // E-TryValue, E-Catch, E-Rethrow
+    #[inline(never)]
+    fn eval_try(
+        &mut self,
+        env: &Env<'a>,
+        term: &'a Term,
+        Try { body, catches, .. }: &'a Try,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let (exception, span) = match self.eval(env, body) {
+            Ok(value) => {
+                self.step(env, term)?;
+                return Ok(value);
+            }
+            Err(Unwind::Throw(exception, span)) => (exception, span),
+            Err(error) => return Err(error),
+        };
+        // a single step, however deep in the body the exception was thrown
+        self.step(env, term)?;
+        let Object::New { class_name, .. } = &*exception else {
+            Err(EvalError::Stuck(term.clone().boxed()))?
+        };
+        let catch = catches
+            .iter()
+            .find(|catch| self.ct.is_subtype(class_name, &catch.class_name) == Some(true));
+        match catch {
+            Some(Catch { name, body, .. }) => {
+                let mut catch_env = env.clone();
+                catch_env.push((name.0.as_str(), exception.clone()));
+                self.eval(&catch_env, body)
+            }
+            None => Err(Unwind::Throw(exception, span)),
+        }
+    }
+
+    fn eval_all(
+        &mut self,
+        env: &Env<'a>,
+        terms: &'a [Box<Term>],
+    ) -> Result<Vec<Value<'a>>, Unwind<'a>> {
+        terms.iter().map(|term| self.eval(env, term)).collect()
+    }
+
+    /// Decorate a closure without a target with the interface `ty`, as
+    /// [`evaluation::decorate`] does for lambda terms.
+    fn decorate(&self, value: Value<'a>, ty: &Type) -> Value<'a> {
+        match (&*value, ty) {
+            (
+                Object::Closure {
+                    lambda,
+                    env,
+                    target: None,
+                },
+                Type::Class(class_type),
+            ) if self.ct.is_interface(&class_type.name) => Rc::new(Object::Closure {
+                lambda,
+                env: env.clone(),
+                target: Some(class_type.name.clone()),
+            }),
+            _ => value,
+        }
+    }
+
+    /// Count a computation step on `term` in `env`, checking the limits.
+    fn step(&mut self, env: &Env<'a>, term: &Term) -> Result<(), EvalError> {
+        if self
+            .limits
+            .max_steps
+            .is_some_and(|max_steps| self.steps >= max_steps)
+        {
+            Err(EvalError::StepLimitExceeded(
+                self.steps,
+                close(env, term).boxed(),
+            ))?;
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.start.elapsed() >= timeout {
+                Err(EvalError::TimeLimitExceeded(
+                    timeout,
+                    self.steps,
+                    close(env, term).boxed(),
+                ))?;
+            }
+        }
+        self.steps += 1;
+        Ok(())
+    }
+}
+
+/// `term` with the values of the variables in `env` substituted and type
+/// arguments erased, the closed term reported when a limit is exceeded.
+fn close(env: &Env, term: &Term) -> Term {
+    env.iter()
+        .rev()
+        .fold(evaluation::erase(term.clone()), |term, (name, value)| {
+            evaluation::substitute(term, &FieldName(name.to_string()), value.to_term())
+        })
+}
+
+/// The approximate position of the top of the stack: the address of a local
+/// variable.
+#[inline(never)]
+fn stack_address() -> usize {
+    let local = 0u8;
+    std::hint::black_box(&local) as *const u8 as usize
+}
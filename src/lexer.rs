@@ -0,0 +1,72 @@
+//! A lexer approximating the tokens of the grammar, for tools working on
+//! source text rather than syntax trees: the language server looks up names
+//! without spans in the AST and the formatter keeps comments.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Ident,
+    Punct,
+    /// Literals, whose contents are never names.
+    Literal,
+    /// `// ...` up to the end of the line, or `/* ... */`.
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Token {
+    pub(crate) kind: TokenKind,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+pub(crate) fn text<'s>(source: &'s str, token: &Token) -> &'s str {
+    &source[token.start..token.end]
+}
+
+/// Split `source` into identifiers, punctuation, literals and comments,
+/// skipping whitespace.
+pub(crate) fn tokenize(source: &str) -> Vec<Token> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let rest = &source[i..];
+        let kind = if bytes[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            TokenKind::Comment
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            i += comment.find("*/").map_or(rest.len(), |end| end + 4);
+            TokenKind::Comment
+        } else if bytes[i] == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(bytes.len());
+            TokenKind::Literal
+        } else if bytes[i].is_ascii_alphanumeric() {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            if bytes[start].is_ascii_digit() {
+                TokenKind::Literal
+            } else {
+                TokenKind::Ident
+            }
+        } else {
+            // NOTE(unwrap): `i` is on a char boundary before the end
+            i += rest.chars().next().unwrap().len_utf8();
+            TokenKind::Punct
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+        });
+    }
+    tokens
+}
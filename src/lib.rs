@@ -0,0 +1,88 @@
+//! Parser, typechecker and interpreter for Featherweight Java (FJ), as
+//! described in "Featherweight Java: A Minimal Core Calculus for Java and GJ"
+//! by Igarashi, Pierce and Wadler.
+//!
+//! A program is checked and run in four steps:
+//!
+//! ```
+//! use featherweight_java::{
+//!     build_class_table, eval_full, parse, parse_eval_input, typecheck_term, Gamma, SourceMap,
+//!     TypecheckConfig,
+//! };
+//!
+//! let mut sources = SourceMap::new();
+//! let lib = sources.add(
+//!     "lib.fj",
+//!     "class A extends Object { A() { super(); } A self() { return this; } }",
+//! );
+//! let term = sources.add("term.fje", "new A().self()");
+//!
+//! // parse the library and build its class table, which typechecks all classes
+//! let ast = parse(lib, sources.source(lib))?;
+//! let config = TypecheckConfig::default();
+//! let (ct, _warnings) = build_class_table(&ast, &config)?;
+//!
+//! // typecheck and evaluate a term against it
+//! let term = parse_eval_input(term, sources.source(term))?;
+//! let term_type = typecheck_term(&ct, &config, &Gamma::empty(), &term)?;
+//! assert_eq!(term_type.ty.to_string(), "A");
+//! let value = eval_full(&ct, term)?;
+//! assert_eq!(value.to_string(), "new A()");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! All syntax trees implement [`std::fmt::Display`], printing them as FJ
+//! source code with the [`pretty`] printer. Errors carry [`Span`]s into a [`SourceMap`] and can be
+//! rendered with the [`diagnostics`] module.
+
+#![allow(clippy::vec_box)]
+
+pub mod analysis;
+pub mod ast;
+pub mod bytecode;
+pub mod cast_elimination;
+pub mod class_table;
+pub mod class_tree;
+pub mod diagnostics;
+pub mod error;
+pub mod evaluation;
+pub mod interpreter;
+mod lexer;
+pub mod loader;
+pub mod parser;
+pub mod pretty;
+pub mod source;
+pub mod typecheck;
+pub mod vm;
+
+pub use ast::{Ast, ClassName, ClassType, Script, Term, Type};
+pub use class_table::ClassTable;
+pub use error::{
+    ClassTableError, ErrorList, EvalError, LoadError, ParseError, TypingError, TypingWarning,
+};
+pub use evaluation::{eval_full, eval_step};
+pub use parser::{parse, parse_eval_input, parse_script};
+pub use source::{FileId, SourceMap, Span};
+pub use typecheck::{typecheck_ast, typecheck_term, Gamma, StupidCasts, TypecheckConfig};
+
+use anyhow::Context;
+
+/// Build and typecheck the class table of `ast`, returning it together with
+/// the typing warnings for the library.
+///
+/// All well-formedness and typing errors are reported at once as an
+/// [`ErrorList`].
+pub fn build_class_table(
+    ast: &Ast,
+    config: &TypecheckConfig,
+) -> anyhow::Result<(ClassTable, Vec<TypingWarning>)> {
+    let ct = ClassTable::try_from_ast_all(ast.clone())
+        .map_err(|errors| ErrorList(errors.into_iter().map(From::from).collect()))
+        .context("could not build class table")?;
+
+    let report = typecheck::typecheck_ast_all(&ct, config, ast);
+    if !report.errors.is_empty() {
+        Err(ErrorList(report.errors)).context("typechecking for library failed")?;
+    }
+    Ok((ct, report.warnings))
+}
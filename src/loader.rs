@@ -0,0 +1,151 @@
+//! Loading of programs spread over several files.
+//!
+//! A library or script may start with `import "path";` declarations, where
+//! `path` is relative to the directory of the importing file. The imported
+//! files are libraries, whose imports are followed in turn. Every file is
+//! loaded only once, however often it is imported, and the definitions of all
+//! files are merged into a single [`Ast`] without imports, those of an
+//! imported file before those of the file importing it. A class defined in two files is reported
+//! when the class table is built, with the locations of both definitions.
+
+use crate::ast::{Ast, Script, Span};
+use crate::error::LoadError;
+use crate::parser;
+use crate::source::{FileId, SourceMap};
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Read the file at `path` into `sources`.
+pub fn load_file(sources: &mut SourceMap, path: &Path) -> Result<FileId> {
+    let input = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read file `{}`", path.display()))?;
+    Ok(sources.add(path.display().to_string(), input))
+}
+
+/// Load the libraries `paths` together with all files they import.
+pub fn load_libraries(sources: &mut SourceMap, paths: &[impl AsRef<Path>]) -> Result<Ast> {
+    let mut loader = Loader::new(sources);
+    for path in paths {
+        loader.load_library(path.as_ref())?;
+    }
+    Ok(loader.finish())
+}
+
+/// Load the script `path`, whose definitions are merged with those of all
+/// files it imports.
+pub fn load_script(sources: &mut SourceMap, path: &Path) -> Result<Script> {
+    let mut loader = Loader::new(sources);
+    let file = loader.read(path, None)?;
+    let script =
+        parser::parse_script(file, loader.sources.source(file)).context("parsing failed")?;
+    loader.load_ast(path, script.ast)?;
+    Ok(Script {
+        ast: loader.finish(),
+        eval_terms: script.eval_terms,
+    })
+}
+
+/// Merges the definitions of files and the files they import.
+pub struct Loader<'s> {
+    sources: &'s mut SourceMap,
+    /// Canonical paths of the files loaded so far.
+    loaded: BTreeSet<PathBuf>,
+    /// Canonical and displayed paths of the files whose imports are being
+    /// loaded, the outermost first.
+    loading: Vec<(PathBuf, String)>,
+    ast: Ast,
+}
+
+impl<'s> Loader<'s> {
+    pub fn new(sources: &'s mut SourceMap) -> Self {
+        Loader {
+            sources,
+            loaded: BTreeSet::new(),
+            loading: Vec::new(),
+            ast: Ast::default(),
+        }
+    }
+
+    /// Load the library `path` and the files it imports, unless it is loaded
+    /// already.
+    pub fn load_library(&mut self, path: &Path) -> Result<()> {
+        self.library(path, None)
+    }
+
+    /// Add the definitions of `ast`, the parsed contents of the file `path`,
+    /// after loading the files it imports. `path` need not exist on disk,
+    /// e.g. for a new file being edited.
+    pub fn load_ast(&mut self, path: &Path, ast: Ast) -> Result<()> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.loaded.insert(canonical.clone());
+        self.load_imports(path, canonical, ast)
+    }
+
+    /// All definitions loaded so far.
+    pub fn finish(self) -> Ast {
+        self.ast
+    }
+
+    fn library(&mut self, path: &Path, imported_at: Option<Span>) -> Result<()> {
+        let canonical = path
+            .canonicalize()
+            .map_err(|err| read_error(path, err, imported_at))?;
+        if let Some(i) = self
+            .loading
+            .iter()
+            .position(|(other, _)| other == &canonical)
+        {
+            let cycle = self.loading[i..]
+                .iter()
+                .map(|(_, name)| name.clone())
+                .chain(std::iter::once(path.display().to_string()))
+                .collect();
+            // NOTE(unwrap): only imported files can already be loading
+            Err(LoadError::ImportCycle(
+                path.display().to_string(),
+                cycle,
+                imported_at.unwrap(),
+            ))?;
+        }
+        if !self.loaded.insert(canonical.clone()) {
+            return Ok(());
+        }
+        let file = self.read(path, imported_at)?;
+        let ast = parser::parse(file, self.sources.source(file)).context("parsing failed")?;
+        self.load_imports(path, canonical, ast)
+    }
+
+    /// Read the file at `path`, which is imported at `imported_at`, if not
+    /// given on the command line.
+    fn read(&mut self, path: &Path, imported_at: Option<Span>) -> Result<FileId> {
+        let input =
+            std::fs::read_to_string(path).map_err(|err| read_error(path, err, imported_at))?;
+        Ok(self.sources.add(path.display().to_string(), input))
+    }
+
+    /// Load the files imported by `ast`, the contents of the file `path`
+    /// with the canonical path `canonical`, and then add its definitions.
+    fn load_imports(&mut self, path: &Path, canonical: PathBuf, ast: Ast) -> Result<()> {
+        self.loading.push((canonical, path.display().to_string()));
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for import in &ast.imports {
+            self.library(&dir.join(&import.path), Some(import.span))?;
+        }
+        self.loading.pop();
+        self.ast.class_definitions.extend(ast.class_definitions);
+        self.ast
+            .interface_definitions
+            .extend(ast.interface_definitions);
+        Ok(())
+    }
+}
+
+fn read_error(path: &Path, err: std::io::Error, imported_at: Option<Span>) -> anyhow::Error {
+    match imported_at {
+        Some(span) => LoadError::ImportNotFound(path.display().to_string(), err, span).into(),
+        None => {
+            anyhow::Error::new(err).context(format!("could not read file `{}`", path.display()))
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use anyhow::Context;
+use codespan_reporting::diagnostic::{LabelStyle, Severity};
+use featherweight_java::analysis::{Analysis, CompletionKind};
+use featherweight_java::{FileId, Span, TypecheckConfig};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, References, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse, Diagnostic,
+    DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverProviderCapability, Location, MarkupContent,
+    MarkupKind, OneOf, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// An open file, analyzed again when it is queried after a change.
+struct Document {
+    text: String,
+    analysis: Option<Analysis>,
+}
+
+/// A language server for `.fj` and `.fje` files, speaking LSP over stdio.
+pub struct Server {
+    lib_files: Vec<PathBuf>,
+    config: TypecheckConfig,
+    documents: BTreeMap<Url, Document>,
+    /// The files each document last published diagnostics for, which are
+    /// cleared when it no longer has any there.
+    published: BTreeMap<Url, BTreeSet<Url>>,
+}
+
+impl Server {
+    pub fn new(lib_files: Vec<PathBuf>, config: TypecheckConfig) -> Self {
+        Server {
+            lib_files,
+            config,
+            documents: BTreeMap::new(),
+            published: BTreeMap::new(),
+        }
+    }
+
+    pub fn run(mut self) -> anyhow::Result<()> {
+        let (connection, io_threads) = Connection::stdio();
+        let capabilities = ServerCapabilities {
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::FULL),
+                    save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                    ..Default::default()
+                },
+            )),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            definition_provider: Some(OneOf::Left(true)),
+            references_provider: Some(OneOf::Left(true)),
+            completion_provider: Some(CompletionOptions {
+                trigger_characters: Some(vec![".".into()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        connection.initialize(serde_json::to_value(capabilities)?)?;
+        // the writer thread stops once the connection is dropped
+        self.serve(connection)?;
+        io_threads.join()?;
+        Ok(())
+    }
+
+    /// Answer requests until the client shuts the server down.
+    fn serve(&mut self, connection: Connection) -> anyhow::Result<()> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        break;
+                    }
+                    let response = self.handle_request(request);
+                    connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => {
+                    for notification in self.handle_notification(notification)? {
+                        connection.sender.send(notification.into())?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => params::<HoverRequest>(request).map(|params| {
+                serde_json::to_value(self.hover(params.text_document_position_params))
+            }),
+            GotoDefinition::METHOD => params::<GotoDefinition>(request).map(|params| {
+                serde_json::to_value(self.definition(params.text_document_position_params))
+            }),
+            References::METHOD => params::<References>(request).map(|params| {
+                serde_json::to_value(self.references(
+                    params.text_document_position,
+                    params.context.include_declaration,
+                ))
+            }),
+            Completion::METHOD => params::<Completion>(request)
+                .map(|params| serde_json::to_value(self.completion(params.text_document_position))),
+            _ => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request `{}`", request.method),
+                )
+            }
+        };
+        match result.and_then(|value| Ok(value?)) {
+            Ok(value) => Response::new_ok(id, value),
+            Err(err) => invalid_params(id, err),
+        }
+    }
+
+    /// Handle a change of the open documents, returning the diagnostics to
+    /// publish.
+    fn handle_notification(
+        &mut self,
+        notification: Notification,
+    ) -> anyhow::Result<Vec<Notification>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.insert(
+                    uri.clone(),
+                    Document {
+                        text: params.text_document.text,
+                        analysis: None,
+                    },
+                );
+                Ok(self.publish_diagnostics(&uri))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // with full synchronization the last change is the whole text
+                if let (Some(document), Some(change)) = (
+                    self.documents.get_mut(&params.text_document.uri),
+                    params.content_changes.into_iter().last(),
+                ) {
+                    document.text = change.text;
+                    document.analysis = None;
+                }
+                Ok(vec![])
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                Ok(self.publish_diagnostics(&params.text_document.uri))
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                let cleared = self.published.remove(&uri).unwrap_or_default();
+                Ok(cleared
+                    .into_iter()
+                    .map(|uri| diagnostics_notification(uri, vec![]))
+                    .collect())
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// The analysis of the open document `uri`, `None` if it is not a file.
+    fn analysis(&mut self, uri: &Url) -> Option<&Analysis> {
+        let path = uri.to_file_path().ok()?;
+        let document = self.documents.get_mut(uri)?;
+        if document.analysis.is_none() {
+            document.analysis = Some(Analysis::new(
+                &path,
+                document.text.clone(),
+                &self.lib_files,
+                self.config.clone(),
+            ));
+        }
+        document.analysis.as_ref()
+    }
+
+    /// The diagnostics of `uri` and of all files loaded with it, grouped by
+    /// file. Diagnostics without a location are shown at the start of `uri`.
+    fn publish_diagnostics(&mut self, uri: &Url) -> Vec<Notification> {
+        let Some(analysis) = self.analysis(uri) else {
+            return vec![];
+        };
+        let mut by_file: BTreeMap<Url, Vec<Diagnostic>> = BTreeMap::new();
+        for diagnostic in analysis.diagnostics() {
+            let primary = diagnostic
+                .labels
+                .iter()
+                .find(|label| label.style == LabelStyle::Primary);
+            let primary_location = match primary {
+                Some(label) => location(
+                    uri,
+                    analysis,
+                    Span::new(label.file_id, label.range.start, label.range.end),
+                ),
+                None => Location::new(uri.clone(), Range::default()),
+            };
+            let related_information = diagnostic
+                .labels
+                .iter()
+                .filter(|label| label.style == LabelStyle::Secondary)
+                .map(|label| DiagnosticRelatedInformation {
+                    location: location(
+                        uri,
+                        analysis,
+                        Span::new(label.file_id, label.range.start, label.range.end),
+                    ),
+                    message: label.message.clone(),
+                })
+                .collect::<Vec<_>>();
+            let message = std::iter::once(diagnostic.message.clone())
+                .chain(diagnostic.notes.iter().cloned())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let severity = match diagnostic.severity {
+                Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+                Severity::Note => DiagnosticSeverity::INFORMATION,
+                Severity::Help => DiagnosticSeverity::HINT,
+            };
+            by_file
+                .entry(primary_location.uri)
+                .or_default()
+                .push(Diagnostic {
+                    range: primary_location.range,
+                    severity: Some(severity),
+                    source: Some("fj".into()),
+                    message,
+                    related_information: (!related_information.is_empty())
+                        .then_some(related_information),
+                    ..Default::default()
+                });
+        }
+        // the document itself is always published, to clear old diagnostics
+        by_file.entry(uri.clone()).or_default();
+        let files: BTreeSet<_> = by_file.keys().cloned().collect();
+        let cleared = self
+            .published
+            .insert(uri.clone(), files.clone())
+            .unwrap_or_default();
+        cleared
+            .difference(&files)
+            .map(|uri| diagnostics_notification(uri.clone(), vec![]))
+            .chain(
+                by_file
+                    .into_iter()
+                    .map(|(uri, diagnostics)| diagnostics_notification(uri, diagnostics)),
+            )
+            .collect()
+    }
+
+    fn hover(&mut self, params: TextDocumentPositionParams) -> Option<Hover> {
+        let uri = params.text_document.uri;
+        let analysis = self.analysis(&uri)?;
+        let offset = offset(analysis, params.position);
+        let (span, ty) = analysis.hover(offset)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```fj\n{}\n```", ty),
+            }),
+            range: Some(location(&uri, analysis, span).range),
+        })
+    }
+
+    fn definition(&mut self, params: TextDocumentPositionParams) -> Option<GotoDefinitionResponse> {
+        let uri = params.text_document.uri;
+        let analysis = self.analysis(&uri)?;
+        let span = analysis.definition(offset(analysis, params.position))?;
+        Some(GotoDefinitionResponse::Scalar(location(
+            &uri, analysis, span,
+        )))
+    }
+
+    fn references(
+        &mut self,
+        params: TextDocumentPositionParams,
+        include_declaration: bool,
+    ) -> Option<Vec<Location>> {
+        let uri = params.text_document.uri;
+        let analysis = self.analysis(&uri)?;
+        let spans = analysis.references(offset(analysis, params.position), include_declaration);
+        Some(
+            spans
+                .into_iter()
+                .map(|span| location(&uri, analysis, span))
+                .collect(),
+        )
+    }
+
+    fn completion(&mut self, params: TextDocumentPositionParams) -> Option<CompletionResponse> {
+        let analysis = self.analysis(&params.text_document.uri)?;
+        let completions = analysis.completions(offset(analysis, params.position));
+        Some(CompletionResponse::Array(
+            completions
+                .into_iter()
+                .map(|completion| CompletionItem {
+                    label: completion.name,
+                    kind: Some(match completion.kind {
+                        CompletionKind::Field => CompletionItemKind::FIELD,
+                        CompletionKind::Method => CompletionItemKind::METHOD,
+                    }),
+                    detail: Some(completion.detail),
+                    ..Default::default()
+                })
+                .collect(),
+        ))
+    }
+}
+
+fn params<R: lsp_types::request::Request>(request: Request) -> anyhow::Result<R::Params> {
+    serde_json::from_value(request.params)
+        .with_context(|| format!("invalid parameters for `{}`", request.method))
+}
+
+fn invalid_params(id: RequestId, err: anyhow::Error) -> Response {
+    Response::new_err(id, ErrorCode::InvalidParams as i32, format!("{:#}", err))
+}
+
+fn diagnostics_notification(uri: Url, diagnostics: Vec<Diagnostic>) -> Notification {
+    Notification::new(
+        PublishDiagnostics::METHOD.into(),
+        PublishDiagnosticsParams::new(uri, diagnostics, None),
+    )
+}
+
+/// The location of `span`, which is in the document `uri` or a file loaded
+/// from disk.
+fn location(uri: &Url, analysis: &Analysis, span: Span) -> Location {
+    let file_uri = if span.file == analysis.file() {
+        uri.clone()
+    } else {
+        file_uri(analysis, span.file).unwrap_or_else(|| uri.clone())
+    };
+    let source = analysis.sources().source(span.file);
+    Location::new(
+        file_uri,
+        Range::new(position(source, span.start), position(source, span.end)),
+    )
+}
+
+fn file_uri(analysis: &Analysis, file: FileId) -> Option<Url> {
+    let path = PathBuf::from(analysis.sources().name(file))
+        .canonicalize()
+        .ok()?;
+    Url::from_file_path(path).ok()
+}
+
+/// The byte offset of `position` in the analyzed document, whose characters
+/// are counted in UTF-16 code units.
+fn offset(analysis: &Analysis, position: Position) -> usize {
+    let source = analysis.sources().source(analysis.file());
+    let line_start = source
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum::<usize>();
+    let mut units = 0;
+    for (i, c) in source[line_start..].char_indices() {
+        if units >= position.character as usize || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    source.len()
+}
+
+/// The position of the byte offset `offset` in `source`.
+fn position(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
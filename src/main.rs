@@ -1,60 +1,538 @@
-#![allow(dead_code)]
-
-use crate::evaluation::eval_full;
-use anyhow;
 use anyhow::Context;
-use std::path::PathBuf;
-use typecheck::{typecheck_ast, typecheck_term, Gamma};
+use featherweight_java::vm;
+use featherweight_java::{
+    build_class_table,
+    bytecode::Program,
+    cast_elimination::{CastElimination, CastReport},
+    class_tree, diagnostics,
+    evaluation::{eval_full_with, eval_heap_with, EvalLimits, Heap, Step},
+    interpreter,
+    loader::{self, load_file},
+    parser, pretty, typecheck_term, ClassName, ClassTable, Gamma, SourceMap, StupidCasts, Term,
+    TypecheckConfig,
+};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+mod lsp;
+mod repl;
+
+#[derive(structopt::StructOpt)]
+enum Args {
+    /// Load libraries of classes and evaluate a single term against them
+    Run {
+        /// Libraries, which are loaded with all files they import, followed by the file
+        /// containing the term
+        #[structopt(parse(from_os_str), required = true, min_values = 2)]
+        fj_files: Vec<PathBuf>,
+        #[structopt(flatten)]
+        typecheck: TypecheckArgs,
+        #[structopt(flatten)]
+        eval: EvalArgs,
+        /// Remove casts that can never fail before evaluation, and report the others
+        #[structopt(long)]
+        eliminate_casts: bool,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+    /// Run a script of class definitions and `eval { ... }` blocks
+    Script {
+        #[structopt(parse(from_os_str))]
+        fj_script_file: PathBuf,
+        #[structopt(flatten)]
+        typecheck: TypecheckArgs,
+        #[structopt(flatten)]
+        eval: EvalArgs,
+        /// Remove casts that can never fail before evaluation, and report the others
+        #[structopt(long)]
+        eliminate_casts: bool,
+        #[structopt(flatten)]
+        output: OutputArgs,
+    },
+    /// Start an interactive session, optionally loading libraries of classes
+    Repl {
+        #[structopt(parse(from_os_str))]
+        fj_lib_files: Vec<PathBuf>,
+        #[structopt(flatten)]
+        typecheck: TypecheckArgs,
+        #[structopt(flatten)]
+        eval: EvalArgs,
+    },
+    /// Compile libraries of classes, and optionally a term, and print the bytecode
+    Disassemble {
+        #[structopt(parse(from_os_str), required = true)]
+        fj_lib_files: Vec<PathBuf>,
+        /// File containing a term to compile as well
+        #[structopt(long = "term", parse(from_os_str))]
+        fj_expression_file: Option<PathBuf>,
+        #[structopt(flatten)]
+        typecheck: TypecheckArgs,
+    },
+    /// Format files in place, keeping their comments
+    Fmt {
+        /// Scripts, libraries and `.fje` files containing a term
+        #[structopt(parse(from_os_str), required = true)]
+        fj_files: Vec<PathBuf>,
+        /// Do not write the files, but list those that are not formatted and fail if there are any
+        #[structopt(long)]
+        check: bool,
+        /// Maximum line width, which is exceeded only by lines that can not be broken
+        #[structopt(long, default_value = "100")]
+        width: usize,
+    },
+    /// Run a language server over stdio, for editors
+    Lsp {
+        /// Libraries to check `.fje` files against
+        #[structopt(parse(from_os_str))]
+        fj_lib_files: Vec<PathBuf>,
+        #[structopt(flatten)]
+        typecheck: TypecheckArgs,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+struct TypecheckArgs {
+    /// Allow overriding methods to return a subtype of the overridden method's return type
+    #[structopt(long)]
+    covariant_returns: bool,
+    /// How to treat casts between unrelated classes, which can never succeed
+    #[structopt(long, default_value = "warn", possible_values = &["allow", "warn", "deny"])]
+    stupid_casts: StupidCasts,
+    /// Reject primitive types, literals, operators, `if`, blocks, field assignments, `throw` and `try`
+    #[structopt(long)]
+    pure: bool,
+}
+
+impl TypecheckArgs {
+    fn config(&self) -> TypecheckConfig {
+        TypecheckConfig {
+            covariant_returns: self.covariant_returns,
+            stupid_casts: self.stupid_casts,
+            pure: self.pure,
+        }
+    }
+}
+
+/// Evaluator used to reduce terms to values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Evaluator {
+    /// Small-step reduction by substitution, as in the FJ paper
+    SmallStep,
+    /// Big-step, environment-based interpreter
+    BigStep,
+    /// Compilation to bytecode, run on a stack machine
+    Vm,
+    /// Small-step reduction allocating objects on a heap, allowing field assignment
+    Heap,
+}
+
+impl FromStr for Evaluator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "small-step" => Ok(Evaluator::SmallStep),
+            "big-step" => Ok(Evaluator::BigStep),
+            "vm" => Ok(Evaluator::Vm),
+            "heap" => Ok(Evaluator::Heap),
+            _ => anyhow::bail!(
+                "expected one of `small-step`, `big-step`, `vm` or `heap`, found `{}`",
+                s
+            ),
+        }
+    }
+}
+
+/// The value of a term, with the final heap for `--evaluator heap`.
+type Evaluated = (Term, Option<Heap>);
+
+impl Evaluator {
+    fn eval(self, ct: &ClassTable, term: Term, limits: &EvalLimits) -> anyhow::Result<Evaluated> {
+        match self {
+            Evaluator::SmallStep => Ok((eval_full_with(ct, term, limits, |_| {})?, None)),
+            Evaluator::BigStep => Ok((interpreter::eval(ct, &term, limits)?, None)),
+            Evaluator::Vm => Ok((vm::eval(ct, &term, limits)?, None)),
+            Evaluator::Heap => {
+                let (value, heap) = eval_heap_with(ct, term, limits, |_| {})?;
+                Ok((value, Some(heap)))
+            }
+        }
+    }
+}
+
+#[derive(structopt::StructOpt)]
+struct EvalArgs {
+    /// Evaluator to use, the big-step interpreter and the vm are much faster but can not trace steps
+    #[structopt(
+        long,
+        default_value = "small-step",
+        possible_values = &["small-step", "big-step", "vm", "heap"]
+    )]
+    evaluator: Evaluator,
+    /// Abort evaluation after this many reduction steps
+    #[structopt(long)]
+    max_steps: Option<usize>,
+    /// Abort evaluation after this many seconds
+    #[structopt(long, parse(try_from_str = parse_seconds))]
+    timeout: Option<Duration>,
+    /// Do not check whether a term recurs during evaluation
+    #[structopt(long)]
+    no_cycle_detection: bool,
+}
+
+impl EvalArgs {
+    fn limits(&self) -> EvalLimits {
+        EvalLimits {
+            max_steps: self.max_steps,
+            timeout: self.timeout,
+            detect_cycles: !self.no_cycle_detection,
+        }
+    }
+
+    /// Fail if casts are to be eliminated for the vm, as the bytecode
+    /// compiler relies on the static types the casts provide.
+    fn check_cast_elimination(&self, eliminate_casts: bool) -> anyhow::Result<()> {
+        if eliminate_casts && self.evaluator == Evaluator::Vm {
+            anyhow::bail!(
+                "cast elimination requires `--evaluator small-step`, `--evaluator big-step` or `--evaluator heap`"
+            );
+        }
+        Ok(())
+    }
+}
 
-mod ast;
-mod class_table;
-mod class_tree;
-mod error;
-mod evaluation;
-mod parser;
-mod typecheck;
+fn parse_seconds(s: &str) -> anyhow::Result<Duration> {
+    Ok(Duration::try_from_secs_f64(s.parse()?)?)
+}
 
 #[derive(structopt::StructOpt)]
-struct Args {
-    #[structopt(parse(from_os_str))]
-    fj_lib_file: PathBuf,
-    #[structopt(parse(from_os_str))]
-    fj_expression_file: PathBuf,
+struct OutputArgs {
+    /// Only print the results of evaluation
+    #[structopt(long, short, conflicts_with_all = &["trace", "trace-rules"])]
+    quiet: bool,
+    /// Print every evaluation step
+    #[structopt(long)]
+    trace: bool,
+    /// Print every evaluation step with the reduction rules used
+    #[structopt(long)]
+    trace_rules: bool,
+}
+
+impl OutputArgs {
+    /// Print progress information, unless `--quiet` is given.
+    fn status(&self, message: impl Display) {
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
+
+    fn eval(&self, ct: &ClassTable, term: Term, eval: &EvalArgs) -> anyhow::Result<Evaluated> {
+        if !self.trace && !self.trace_rules {
+            return eval.evaluator.eval(ct, term, &eval.limits());
+        }
+        let observer = |step: &Step| {
+            if self.trace_rules {
+                println!("-> {}    [{}]", &step.term, &step.derivation);
+            } else {
+                println!("-> {}", &step.term);
+            }
+        };
+        match eval.evaluator {
+            Evaluator::SmallStep => Ok((eval_full_with(ct, term, &eval.limits(), observer)?, None)),
+            Evaluator::Heap => {
+                let (value, heap) = eval_heap_with(ct, term, &eval.limits(), observer)?;
+                Ok((value, Some(heap)))
+            }
+            Evaluator::BigStep | Evaluator::Vm => {
+                anyhow::bail!("tracing requires `--evaluator small-step` or `--evaluator heap`")
+            }
+        }
+    }
+
+    /// Print the value of a term after `label`, followed by the objects on
+    /// the heap, if evaluated with one.
+    fn result(&self, label: impl Display, (value, heap): &Evaluated) {
+        if self.quiet {
+            println!("{}", value);
+        } else {
+            println!("{} {}", label, value);
+        }
+        if let Some(heap) = heap {
+            self.status("HEAP");
+            print!("{}", heap);
+        }
+    }
 }
 
 #[paw::main]
 fn main(args: Args) -> anyhow::Result<()> {
-    let input = std::fs::read_to_string(args.fj_lib_file).context("could not read file")?;
-    let ast = parser::parse(&input).context("parsing failed")?;
-    println!("LIBRARY AST PARSED OK");
-    let ct = class_table::ClassTable::try_from_ast(ast.clone())
-        .context("could not build class table")?;
-    println!("CLASS TABLE OK");
+    let mut sources = SourceMap::new();
+    let result = match args {
+        Args::Run {
+            fj_files,
+            typecheck,
+            eval,
+            eliminate_casts,
+            output,
+        } => run(
+            // NOTE(unwrap): at least two files are required
+            &fj_files[..fj_files.len() - 1],
+            fj_files.last().unwrap(),
+            &typecheck.config(),
+            &eval,
+            eliminate_casts,
+            &output,
+            &mut sources,
+        ),
+        Args::Script {
+            fj_script_file,
+            typecheck,
+            eval,
+            eliminate_casts,
+            output,
+        } => run_script(
+            &fj_script_file,
+            &typecheck.config(),
+            &eval,
+            eliminate_casts,
+            &output,
+            &mut sources,
+        ),
+        Args::Repl {
+            fj_lib_files,
+            typecheck,
+            eval,
+        } => repl::Repl::new(
+            fj_lib_files,
+            typecheck.config(),
+            eval.limits(),
+            eval.evaluator,
+        )
+        .run(),
+        Args::Disassemble {
+            fj_lib_files,
+            fj_expression_file,
+            typecheck,
+        } => disassemble(
+            &fj_lib_files,
+            fj_expression_file.as_deref(),
+            &typecheck.config(),
+            &mut sources,
+        ),
+        Args::Fmt {
+            fj_files,
+            check,
+            width,
+        } => format(&fj_files, check, width, &mut sources),
+        Args::Lsp {
+            fj_lib_files,
+            typecheck,
+        } => lsp::Server::new(fj_lib_files, typecheck.config()).run(),
+    };
+    if let Err(err) = result {
+        diagnostics::emit_error(&sources, &err)?;
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    typecheck_ast(&ct, &ast)?;
-    println!("TYPECHECK for library OK");
+fn run(
+    lib_files: &[PathBuf],
+    expression_file: &Path,
+    config: &TypecheckConfig,
+    eval: &EvalArgs,
+    eliminate_casts: bool,
+    output: &OutputArgs,
+    sources: &mut SourceMap,
+) -> anyhow::Result<()> {
+    eval.check_cast_elimination(eliminate_casts)?;
+    let ast = loader::load_libraries(sources, lib_files)?;
+    output.status("LIBRARY AST PARSED OK");
+    let (ct, warnings) = build_class_table(&ast, config)?;
+    diagnostics::emit_warnings(sources, &warnings)?;
+    output.status("CLASS TABLE OK");
+    output.status("TYPECHECK for library OK");
 
     let subtypes_of_object = ct
-        .subtypes(&ast::ClassName("Object".into()))
+        .subtypes(&ClassName::object())
         .unwrap()
         .cloned()
         .collect::<Vec<_>>();
-    println!("Subtypes of object: {:?}", &subtypes_of_object);
+    output.status(format!("Subtypes of object: {:?}", &subtypes_of_object));
 
     let tree = class_tree::ClassTree::new(&ct);
-    println!("CLASS TREE:\n {}", &tree);
+    output.status(format!("CLASS TREE:\n {}", &tree));
 
-    let input = std::fs::read_to_string(args.fj_expression_file).context("could not read file")?;
-    let term = parser::parse_eval_input(&input).context("parsing failed")?;
-    println!("TERM PARSED OK");
-    println!("INPUT TERM {}", &term);
+    let file = load_file(sources, expression_file)?;
+    let term = parser::parse_eval_input(file, sources.source(file)).context("parsing failed")?;
+    output.status("TERM PARSED OK");
+    output.status(format!("INPUT TERM {}", &term));
 
-    let term_type = typecheck_term(&ct, &Gamma::empty(), &term)
+    let term_type = typecheck_term(&ct, config, &Gamma::empty(), &term)
         .context("Typechecking for input term failed")?;
-    println!("TYPECHECK types term as {}", &term_type);
+    diagnostics::emit_warnings(sources, &term_type.warnings)?;
+    output.status(format!("TYPECHECK types term as {}", &term_type.ty));
+
+    let (ct, term) = if eliminate_casts {
+        let mut elimination =
+            CastElimination::new(&ct, [&term]).context("cast elimination failed")?;
+        let (ct, report) = elimination.class_table()?;
+        report_casts(sources, output, "library", &report)?;
+        let (term, report) = elimination.term(&term)?;
+        report_casts(sources, output, "term", &report)?;
+        (ct, term)
+    } else {
+        (ct, term)
+    };
+
+    let result = output.eval(&ct, term, eval).context("eval failed")?;
+    output.result("EVALUATION RESULT", &result);
+
+    Ok(())
+}
 
-    let result = eval_full(&ct, term).context("eval failed")?;
-    println!("EVALUATION RESULT {}", &result);
+fn run_script(
+    script_file: &Path,
+    config: &TypecheckConfig,
+    eval: &EvalArgs,
+    eliminate_casts: bool,
+    output: &OutputArgs,
+    sources: &mut SourceMap,
+) -> anyhow::Result<()> {
+    eval.check_cast_elimination(eliminate_casts)?;
+    let script = loader::load_script(sources, script_file)?;
+    output.status("SCRIPT PARSED OK");
+    let (ct, warnings) = build_class_table(&script.ast, config)?;
+    diagnostics::emit_warnings(sources, &warnings)?;
+    output.status("CLASS TABLE OK");
+    output.status("TYPECHECK for library OK");
+
+    let mut elimination = if eliminate_casts {
+        let elimination =
+            CastElimination::new(&ct, &script.eval_terms).context("cast elimination failed")?;
+        Some(elimination)
+    } else {
+        None
+    };
+    let optimized_ct = match &mut elimination {
+        Some(elimination) => {
+            let (ct, report) = elimination.class_table()?;
+            report_casts(sources, output, "library", &report)?;
+            Some(ct)
+        }
+        None => None,
+    };
+
+    for (i, term) in script.eval_terms.iter().enumerate() {
+        output.status(format!("EVAL #{} INPUT TERM {}", i, &term));
+        let term_type = typecheck_term(&ct, config, &Gamma::empty(), term)
+            .with_context(|| format!("Typechecking for eval block #{} failed", i))?;
+        diagnostics::emit_warnings(sources, &term_type.warnings)?;
+        output.status(format!(
+            "EVAL #{} TYPECHECK types term as {}",
+            i, &term_type.ty
+        ));
+        let term = match &mut elimination {
+            Some(elimination) => {
+                let (term, report) = elimination.term(term)?;
+                report_casts(sources, output, format!("eval block #{}", i), &report)?;
+                term
+            }
+            None => term.clone(),
+        };
+        let result = output
+            .eval(optimized_ct.as_ref().unwrap_or(&ct), term, eval)
+            .with_context(|| format!("eval block #{} failed", i))?;
+        output.result(format!("EVAL #{} RESULT", i), &result);
+    }
+
+    Ok(())
+}
+
+/// Report the casts that were kept by cast elimination in `what`.
+fn report_casts(
+    sources: &SourceMap,
+    output: &OutputArgs,
+    what: impl Display,
+    report: &CastReport,
+) -> anyhow::Result<()> {
+    diagnostics::emit_warnings(sources, &report.remaining)?;
+    output.status(format!(
+        "CAST ELIMINATION removed {} cast(s) from {}, {} may fail",
+        report.eliminated,
+        what,
+        report.remaining.len()
+    ));
+    Ok(())
+}
+
+fn disassemble(
+    lib_files: &[PathBuf],
+    expression_file: Option<&Path>,
+    config: &TypecheckConfig,
+    sources: &mut SourceMap,
+) -> anyhow::Result<()> {
+    let ast = loader::load_libraries(sources, lib_files)?;
+    let (ct, warnings) = build_class_table(&ast, config)?;
+    diagnostics::emit_warnings(sources, &warnings)?;
+    let mut program = Program::compile(&ct).context("compilation failed")?;
+
+    // the classes of the lambdas in the term are added to the program
+    let entry = match expression_file {
+        Some(expression_file) => {
+            let file = load_file(sources, expression_file)?;
+            let term =
+                parser::parse_eval_input(file, sources.source(file)).context("parsing failed")?;
+            let term_type = typecheck_term(&ct, config, &Gamma::empty(), &term)
+                .context("Typechecking for input term failed")?;
+            diagnostics::emit_warnings(sources, &term_type.warnings)?;
+            let entry = program
+                .compile_term(&ct, &term)
+                .context("compilation failed")?;
+            Some(entry)
+        }
+        None => None,
+    };
+    print!("{}", &program);
+    if let Some(entry) = entry {
+        println!();
+        print!("{}", program.display_function(&entry));
+    }
+    Ok(())
+}
 
+/// Format `files`, or with `check` only list those whose formatting differs.
+fn format(
+    files: &[PathBuf],
+    check: bool,
+    width: usize,
+    sources: &mut SourceMap,
+) -> anyhow::Result<()> {
+    let mut unformatted = 0;
+    for path in files {
+        let file = load_file(sources, path)?;
+        let source = sources.source(file);
+        let formatted = if path.extension().is_some_and(|extension| extension == "fje") {
+            pretty::format_eval_input(file, source, width)
+        } else {
+            pretty::format_script(file, source, width)
+        }
+        .with_context(|| format!("could not format `{}`", path.display()))?;
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", path.display());
+            unformatted += 1;
+        } else {
+            std::fs::write(path, formatted)
+                .with_context(|| format!("could not write `{}`", path.display()))?;
+        }
+    }
+    if unformatted > 0 {
+        anyhow::bail!("{} file(s) would be reformatted", unformatted);
+    }
     Ok(())
 }
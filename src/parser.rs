@@ -1,4 +1,5 @@
-use crate::ast;
+use crate::ast::{self, FileId, Span};
+use crate::error::ParseError;
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::Parser;
@@ -7,23 +8,127 @@ use pest_derive::Parser;
 #[grammar = "fj.pest"]
 pub struct FJParser;
 
-type ParserError = pest::error::Error<Rule>;
-type ParserResult<T> = Result<T, ParserError>;
+type ParserResult<T> = Result<T, ParseError>;
 
-pub fn parse(input: &str) -> ParserResult<ast::Ast> {
-    let pairs = FJParser::parse(Rule::program, input)?;
-    Ok(parse_program(pairs))
+fn parse_rule(file: FileId, rule: Rule, input: &str) -> ParserResult<Pairs<'_, Rule>> {
+    let pairs = FJParser::parse(rule, input).map_err(|inner| ParseError {
+        file,
+        inner: Box::new(inner),
+    })?;
+    check_assignment_targets(file, &pairs)?;
+    check_int_literals(file, &pairs)?;
+    Ok(pairs)
 }
 
-pub fn parse_eval_input(input: &str) -> ParserResult<ast::Term> {
-    let pairs = FJParser::parse(Rule::eval_input_term, input)?;
-    Ok(parse_eval_input_term(pairs))
+/// The grammar accepts any operand as the target of an assignment, so that
+/// terms are not parsed twice; only field accesses `t.f` can be assigned.
+fn check_assignment_targets(file: FileId, pairs: &Pairs<Rule>) -> ParserResult<()> {
+    for pair in pairs.clone().flatten() {
+        if pair.as_rule() != Rule::term {
+            continue;
+        }
+        let mut inner = pair.into_inner();
+        let (Some(target), Some(Rule::field_assignment)) =
+            (inner.next(), inner.next().map(|pair| pair.as_rule()))
+        else {
+            continue;
+        };
+        let parts: Vec<_> = target.clone().into_inner().collect();
+        let is_field_access = parts[0].as_rule() != Rule::unary_operator
+            && parts.last().is_some_and(|last| {
+                last.as_rule() == Rule::dot_chain
+                    // NOTE(unwrap): a dot chain is a method call or field access
+                    && last.clone().into_inner().next().unwrap().as_rule() == Rule::field_access
+            });
+        if !is_field_access {
+            let inner = pest::error::Error::new_from_span(
+                pest::error::ErrorVariant::CustomError {
+                    message: "only a field access `t.f` can be assigned to".into(),
+                },
+                // the span of the operand includes the whitespace before `=`
+                parts[0]
+                    .as_span()
+                    .start_pos()
+                    // NOTE(unwrap): an operand is not empty
+                    .span(&parts.last().unwrap().as_span().end_pos()),
+            );
+            return Err(ParseError {
+                file,
+                inner: Box::new(inner),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Integer literals must fit into an `int`. The magnitude of the smallest
+/// `int` does not, so `9223372036854775808` is only a literal right after a
+/// `-`, which [`parse_operand`] folds into it.
+fn check_int_literals(file: FileId, pairs: &Pairs<Rule>) -> ParserResult<()> {
+    for pair in pairs.clone().flatten() {
+        if pair.as_rule() != Rule::operand {
+            continue;
+        }
+        let parts: Vec<_> = pair.into_inner().collect();
+        let Some(index) = parts
+            .iter()
+            .position(|part| part.as_rule() == Rule::term_left)
+        else {
+            continue;
+        };
+        let Some(literal) = parts[index]
+            .clone()
+            .into_inner()
+            .next()
+            .filter(|inner| inner.as_rule() == Rule::literal)
+            // NOTE(unwrap): a literal has exactly one kind
+            .map(|literal| literal.into_inner().next().unwrap())
+            .filter(|literal| literal.as_rule() == Rule::int_literal)
+        else {
+            continue;
+        };
+        let negated = index > 0 && parts[index - 1].as_str() == "-" && index == parts.len() - 1;
+        let in_range = match literal.as_str().parse::<u64>() {
+            Ok(magnitude) if negated => magnitude <= i64::MIN.unsigned_abs(),
+            Ok(magnitude) => magnitude <= i64::MAX as u64,
+            Err(_) => false,
+        };
+        if !in_range {
+            let inner = pest::error::Error::new_from_span(
+                pest::error::ErrorVariant::CustomError {
+                    message: "integer literal is out of range for `int`".into(),
+                },
+                literal.as_span(),
+            );
+            return Err(ParseError {
+                file,
+                inner: Box::new(inner),
+            });
+        }
+    }
+    Ok(())
 }
 
-fn parse_eval_input_term(mut pairs: Pairs<Rule>) -> ast::Term {
+pub fn parse(file: FileId, input: &str) -> ParserResult<ast::Ast> {
+    let pairs = parse_rule(file, Rule::program, input)?;
+    Ok(parse_program(file, pairs))
+}
+
+pub fn parse_eval_input(file: FileId, input: &str) -> ParserResult<ast::Term> {
+    let pairs = parse_rule(file, Rule::eval_input_term, input)?;
+    Ok(parse_eval_input_term(file, pairs))
+}
+
+pub fn parse_script(file: FileId, input: &str) -> ParserResult<ast::Script> {
+    let pairs = parse_rule(file, Rule::script_input, input)?;
+    Ok(parse_script_input(file, pairs))
+}
+
+fn parse_eval_input_term(file: FileId, mut pairs: Pairs<Rule>) -> ast::Term {
     // println!("parse_eval_input_term {:#?}", &pairs);
     let pair = pairs.next().unwrap();
-    let term = parse_term(pair);
+    let mut term = parse_term(file, pair);
+    resolve_term(&[], &mut term);
     term
 }
 
@@ -31,22 +136,84 @@ fn parse_eval_input_term(mut pairs: Pairs<Rule>) -> ast::Term {
 // as is will always work, if the parser was ok
 // .unwrap() is acutally idiomatic here
 
-fn parse_program(pairs: Pairs<Rule>) -> ast::Ast {
+fn parse_program(file: FileId, pairs: Pairs<Rule>) -> ast::Ast {
     // println!("parse_program {:#?}", &pairs);
-    let class_definitions = pairs
-        .take_while(|pair| pair.as_rule() != Rule::EOI)
-        .map(parse_class_definition)
-        .collect();
-    ast::Ast { class_definitions }
+    let mut ast = ast::Ast::default();
+    for pair in pairs.take_while(|pair| pair.as_rule() != Rule::EOI) {
+        match pair.as_rule() {
+            Rule::import_declaration => ast.imports.push(parse_import(file, pair)),
+            Rule::class_definition => ast
+                .class_definitions
+                .push(parse_class_definition(file, pair)),
+            Rule::interface_definition => ast
+                .interface_definitions
+                .push(parse_interface_definition(file, pair)),
+            _ => unreachable!(),
+        }
+    }
+    ast
 }
 
-fn parse_class_definition(pair: Pair<Rule>) -> ast::ClassDefinition {
+fn parse_script_input(file: FileId, pairs: Pairs<Rule>) -> ast::Script {
+    // println!("parse_script_input {:#?}", &pairs);
+    let mut imports = Vec::new();
+    let mut class_definitions = Vec::new();
+    let mut interface_definitions = Vec::new();
+    let mut eval_terms = Vec::new();
+    for pair in pairs.take_while(|pair| pair.as_rule() != Rule::EOI) {
+        match pair.as_rule() {
+            Rule::import_declaration => imports.push(parse_import(file, pair)),
+            Rule::class_definition => class_definitions.push(parse_class_definition(file, pair)),
+            Rule::interface_definition => {
+                interface_definitions.push(parse_interface_definition(file, pair))
+            }
+            // eval blocks are silent, so only their term is visible
+            Rule::term => {
+                let mut term = parse_term(file, pair);
+                resolve_term(&[], &mut term);
+                eval_terms.push(term)
+            }
+            _ => unreachable!(),
+        }
+    }
+    ast::Script {
+        ast: ast::Ast {
+            imports,
+            class_definitions,
+            interface_definitions,
+        },
+        eval_terms,
+    }
+}
+
+fn parse_import(file: FileId, pair: Pair<Rule>) -> ast::Import {
+    // println!("parse_import {:#?}", &pair);
+    let span = Span::from_pest(file, pair.as_span());
+    let path = parse_string_literal(pair.into_inner().next().unwrap());
+    ast::Import { path, span }
+}
+
+fn parse_class_definition(file: FileId, pair: Pair<Rule>) -> ast::ClassDefinition {
     // println!("parse_class_definition {:#?}", &pair);
     match pair.as_rule() {
         Rule::class_definition => {
-            let mut pairs = pair.into_inner();
-            let name = pairs.next().unwrap().as_str();
-            let super_type = pairs.next().unwrap().as_str();
+            let span = Span::from_pest(file, pair.as_span());
+            let mut pairs = pair.into_inner().peekable();
+            let is_abstract = pairs
+                .next_if(|pair| pair.as_rule() == Rule::abstract_modifier)
+                .is_some();
+            let name_pair = pairs.next().unwrap();
+            let name = name_pair.as_str();
+            let name_span = Span::from_pest(file, name_pair.as_span());
+            let type_params = pairs
+                .next_if(|pair| pair.as_rule() == Rule::type_params)
+                .map(parse_type_params)
+                .unwrap_or_default();
+            let super_type = parse_class_type(pairs.next().unwrap());
+            let interfaces = pairs
+                .next_if(|pair| pair.as_rule() == Rule::implements)
+                .map(|pair| pair.into_inner().map(parse_class_type).collect())
+                .unwrap_or_default();
 
             let fields = pairs
                 .clone()
@@ -55,14 +222,94 @@ fn parse_class_definition(pair: Pair<Rule>) -> ast::ClassDefinition {
                 .collect();
             let mut pairs = pairs.skip_while(|pair| pair.as_rule() == Rule::field_definition);
             let ctor_pair = pairs.next().unwrap();
-            let constructor = parse_constructor(ctor_pair);
-            let methods = pairs.map(parse_method_definition).collect();
-            ast::ClassDefinition {
+            let constructor = parse_constructor(file, ctor_pair);
+            let (abstract_methods, methods): (Vec<_>, Vec<_>) =
+                pairs.partition(|pair| pair.as_rule() == Rule::abstract_method);
+            let abstract_methods = abstract_methods
+                .into_iter()
+                .map(|pair| parse_method_signature(file, pair.into_inner().next().unwrap()))
+                .collect();
+            let methods = methods
+                .into_iter()
+                .map(|pair| parse_method_definition(file, pair))
+                .collect();
+            let mut class = ast::ClassDefinition {
+                is_abstract,
                 name: ast::ClassName(name.into()),
-                super_type: ast::ClassName(super_type.into()),
+                type_params,
+                super_type,
+                interfaces,
                 fields,
                 constructor,
                 methods,
+                abstract_methods,
+                span,
+                name_span,
+            };
+            resolve_class_type_variables(&mut class);
+            class
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_interface_definition(file: FileId, pair: Pair<Rule>) -> ast::InterfaceDefinition {
+    // println!("parse_interface_definition {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::interface_definition => {
+            let span = Span::from_pest(file, pair.as_span());
+            let mut pairs = pair.into_inner().peekable();
+            let name_pair = pairs.next().unwrap();
+            let name = name_pair.as_str();
+            let name_span = Span::from_pest(file, name_pair.as_span());
+            let type_params = pairs
+                .next_if(|pair| pair.as_rule() == Rule::type_params)
+                .map(parse_type_params)
+                .unwrap_or_default();
+            let super_types = pairs
+                .next_if(|pair| pair.as_rule() == Rule::super_interfaces)
+                .map(|pair| pair.into_inner().map(parse_class_type).collect())
+                .unwrap_or_default();
+            let methods = pairs
+                .map(|pair| parse_method_signature(file, pair))
+                .collect();
+            let mut interface = ast::InterfaceDefinition {
+                name: ast::ClassName(name.into()),
+                type_params,
+                super_types,
+                methods,
+                span,
+                name_span,
+            };
+            resolve_interface_type_variables(&mut interface);
+            interface
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_method_signature(file: FileId, pair: Pair<Rule>) -> ast::MethodSignature {
+    // println!("parse_method_signature {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::method_signature => {
+            let span = Span::from_pest(file, pair.as_span());
+            let mut pairs = pair.into_inner().peekable();
+            let type_params = pairs
+                .next_if(|pair| pair.as_rule() == Rule::type_params)
+                .map(parse_type_params)
+                .unwrap_or_default();
+            let return_type = parse_class_type(pairs.next().unwrap());
+            let name_pair = pairs.next().unwrap();
+            let method_name = name_pair.as_str();
+            let name_span = Span::from_pest(file, name_pair.as_span());
+            let args = pairs.next().map(parse_arg_list).unwrap_or_default();
+            ast::MethodSignature {
+                type_params,
+                return_type: return_type.into_type(),
+                method_name: ast::MethodName(method_name.into()),
+                args,
+                span,
+                name_span,
             }
         }
         _ => unreachable!(),
@@ -74,21 +321,19 @@ fn parse_field_definition(pair: Pair<Rule>) -> ast::ArgPair {
     match pair.as_rule() {
         Rule::field_definition => {
             let mut pairs = pair.into_inner();
-            let class_name = pairs.next().unwrap().as_str();
+            let field_type = parse_class_type(pairs.next().unwrap());
             let field_name = pairs.next().unwrap().as_str();
-            (
-                ast::ClassName(class_name.into()),
-                ast::FieldName(field_name.into()),
-            )
+            (field_type.into_type(), ast::FieldName(field_name.into()))
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_constructor(pair: Pair<Rule>) -> ast::Constructor {
+fn parse_constructor(file: FileId, pair: Pair<Rule>) -> ast::Constructor {
     // println!("parse_constructor {:#?}", &pair);
     match pair.as_rule() {
         Rule::constructor => {
+            let span = Span::from_pest(file, pair.as_span());
             let mut pairs = pair.into_inner();
             let name = pairs.next().unwrap().as_str();
             let args = pairs
@@ -111,6 +356,7 @@ fn parse_constructor(pair: Pair<Rule>) -> ast::Constructor {
                 args,
                 super_call,
                 assignments,
+                span,
             }
         }
         _ => unreachable!(),
@@ -125,7 +371,7 @@ fn parse_arg_list(pair: Pair<Rule>) -> Vec<ast::ArgPair> {
             let mut args = Vec::new();
             while pairs.peek().is_some() {
                 args.push((
-                    ast::ClassName(pairs.next().unwrap().as_str().into()),
+                    parse_class_type(pairs.next().unwrap()).into_type(),
                     ast::FieldName(pairs.next().unwrap().as_str().into()),
                 ));
             }
@@ -162,73 +408,359 @@ fn parse_assignment(pair: Pair<Rule>) -> (ast::FieldName, ast::FieldName) {
     }
 }
 
-fn parse_method_definition(pair: Pair<Rule>) -> ast::MethodDefinition {
+fn parse_method_definition(file: FileId, pair: Pair<Rule>) -> ast::MethodDefinition {
     // println!("parse_method_definition {:#?}", &pair);
     match pair.as_rule() {
         Rule::method_definition => {
-            let mut pairs = pair.into_inner();
-            let return_type = pairs.next().unwrap().as_str();
-            let method_name = pairs.next().unwrap().as_str();
+            let span = Span::from_pest(file, pair.as_span());
+            let mut pairs = pair.into_inner().peekable();
+            let type_params = pairs
+                .next_if(|pair| pair.as_rule() == Rule::type_params)
+                .map(parse_type_params)
+                .unwrap_or_default();
+            let return_type = parse_class_type(pairs.next().unwrap());
+            let name_pair = pairs.next().unwrap();
+            let method_name = name_pair.as_str();
+            let name_span = Span::from_pest(file, name_pair.as_span());
             let args = pairs
-                .clone()
-                .find(|pair| pair.as_rule() == Rule::arg_list)
+                .next_if(|pair| pair.as_rule() == Rule::arg_list)
                 .map(parse_arg_list)
                 .unwrap_or_default();
-            let return_term = pairs
-                .clone()
-                .find(|pair| pair.as_rule() == Rule::term)
-                .map(parse_term)
-                .unwrap()
-                .boxed();
+            // the statements of the body are followed by the returned term
+            let mut statements: Vec<_> = pairs.collect();
+            let return_term = parse_term(file, statements.pop().unwrap());
+            let return_term = parse_statements(file, statements, return_term).boxed();
             ast::MethodDefinition {
-                return_type: ast::ClassName(return_type.into()),
+                type_params,
+                return_type: return_type.into_type(),
                 method_name: ast::MethodName(method_name.into()),
                 args,
                 return_term,
+                span,
+                name_span,
             }
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_term(pair: Pair<Rule>) -> ast::Term {
-    // println!("parse_term_left {:#?}", &pair);
+/// The span of the term `pair`. A rule ending in a repetition, such as the
+/// method calls of an operand or the binary operators of a term, also covers
+/// the whitespace and comments skipped before the repetition stops, which is
+/// left out here.
+fn term_span(file: FileId, pair: &Pair<Rule>) -> Span {
+    Span::new(file, pair.as_span().start(), term_end(pair))
+}
+
+fn term_end(pair: &Pair<Rule>) -> usize {
+    let (start, end) = (pair.as_span().start(), pair.as_span().end());
+    match pair.clone().into_inner().last() {
+        // the end of the inner pair is only followed by skipped text
+        Some(last) if is_skipped(&pair.as_str()[last.as_span().end() - start..]) => term_end(&last),
+        _ => end,
+    }
+}
+
+/// Whether `text` only consists of whitespace and comments.
+fn is_skipped(mut text: &str) -> bool {
+    loop {
+        text = text.trim_start();
+        if let Some(comment) = text.strip_prefix("//") {
+            text = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(comment) = text.strip_prefix("/*") {
+            match comment.split_once("*/") {
+                Some((_, rest)) => text = rest,
+                None => return false,
+            }
+        } else {
+            return text.is_empty();
+        }
+    }
+}
+
+/// Desugar the statements preceding `last`, local variables and terms, into
+/// nested [`ast::Let`] and [`ast::Seq`] terms.
+fn parse_statements(file: FileId, statements: Vec<Pair<Rule>>, last: ast::Term) -> ast::Term {
+    statements.into_iter().rev().fold(last, |rest, pair| {
+        let span = term_span(file, &pair).to(rest.span());
+        match pair.as_rule() {
+            Rule::local_variable => {
+                let mut pairs = pair.into_inner();
+                let ty = parse_class_type(pairs.next().unwrap()).into_type();
+                let name = pairs.next().unwrap().as_str();
+                let value_term = parse_term(file, pairs.next().unwrap());
+                ast::Let {
+                    ty,
+                    name: ast::FieldName(name.into()),
+                    value_term: value_term.boxed(),
+                    body: rest.boxed(),
+                    span,
+                }
+                .into_term()
+            }
+            Rule::term => ast::Seq {
+                first: parse_term(file, pair).boxed(),
+                second: rest.boxed(),
+                span,
+            }
+            .into_term(),
+            _ => unreachable!(),
+        }
+    })
+}
+
+fn parse_block(file: FileId, pair: Pair<Rule>) -> ast::Term {
+    // println!("parse_block {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::block => {
+            let mut statements: Vec<_> = pair.into_inner().collect();
+            // NOTE(unwrap): a block ends with a term
+            let last = parse_term(file, statements.pop().unwrap());
+            parse_statements(file, statements, last)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_term(file: FileId, pair: Pair<Rule>) -> ast::Term {
+    // println!("parse_term {:#?}", &pair);
     match pair.as_rule() {
         Rule::term => {
+            let mut pairs = pair.into_inner().peekable();
+            let first = pairs.next().unwrap();
+            match first.as_rule() {
+                Rule::conditional => return parse_conditional(file, first),
+                Rule::throw_term => return parse_throw(file, first),
+                Rule::try_term => return parse_try(file, first),
+                _ => {}
+            }
+            let first = parse_operand(file, first);
+            if let Some(pair) = pairs
+                .peek()
+                .filter(|pair| pair.as_rule() == Rule::field_assignment)
+            {
+                let value_term = parse_term(file, pair.clone().into_inner().next().unwrap());
+                let span = first.span().to(value_term.span());
+                let ast::Term::FieldAccess(field_access) = first else {
+                    unreachable!("assignment targets are checked to be field accesses")
+                };
+                return ast::FieldAssign {
+                    object_term: field_access.object_term,
+                    field: field_access.field,
+                    value_term: value_term.boxed(),
+                    span,
+                }
+                .into_term();
+            }
+            let mut rest = Vec::new();
+            while let Some(op_pair) = pairs.next() {
+                rest.push((
+                    parse_binary_operator(op_pair),
+                    parse_operand(file, pairs.next().unwrap()),
+                ));
+            }
+            climb_precedence(first, &mut rest.into_iter().peekable(), 0)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Group the operands following `lhs` with their operators into a tree,
+/// consuming operators binding stronger than `min_precedence`.
+fn climb_precedence(
+    mut lhs: ast::Term,
+    rest: &mut std::iter::Peekable<impl Iterator<Item = (ast::BinaryOperator, ast::Term)>>,
+    min_precedence: u8,
+) -> ast::Term {
+    while let Some((op, _)) = rest.peek() {
+        let op = *op;
+        if op.precedence() <= min_precedence {
+            break;
+        }
+        let (_, mut rhs) = rest.next().unwrap();
+        // operators binding stronger than `op` take its right operand
+        while rest
+            .peek()
+            .is_some_and(|(next, _)| next.precedence() > op.precedence())
+        {
+            rhs = climb_precedence(rhs, rest, op.precedence());
+        }
+        let span = lhs.span().to(rhs.span());
+        lhs = ast::BinaryOp {
+            op,
+            lhs: lhs.boxed(),
+            rhs: rhs.boxed(),
+            span,
+        }
+        .into_term();
+    }
+    lhs
+}
+
+fn parse_binary_operator(pair: Pair<Rule>) -> ast::BinaryOperator {
+    // println!("parse_binary_operator {:#?}", &pair);
+    match pair.as_str() {
+        "||" => ast::BinaryOperator::Or,
+        "&&" => ast::BinaryOperator::And,
+        "==" => ast::BinaryOperator::Eq,
+        "!=" => ast::BinaryOperator::Ne,
+        "<" => ast::BinaryOperator::Lt,
+        "<=" => ast::BinaryOperator::Le,
+        ">" => ast::BinaryOperator::Gt,
+        ">=" => ast::BinaryOperator::Ge,
+        "+" => ast::BinaryOperator::Add,
+        "-" => ast::BinaryOperator::Sub,
+        "*" => ast::BinaryOperator::Mul,
+        "/" => ast::BinaryOperator::Div,
+        "%" => ast::BinaryOperator::Rem,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_conditional(file: FileId, pair: Pair<Rule>) -> ast::Term {
+    // println!("parse_conditional {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::conditional => {
+            let span = term_span(file, &pair);
             let mut pairs = pair.into_inner();
-            let term_left = parse_term_left(pairs.next().unwrap());
+            let condition = parse_term(file, pairs.next().unwrap());
+            let then_term = parse_term(file, pairs.next().unwrap());
+            let else_term = parse_term(file, pairs.next().unwrap());
+            ast::If {
+                condition: condition.boxed(),
+                then_term: then_term.boxed(),
+                else_term: else_term.boxed(),
+                span,
+            }
+            .into_term()
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_throw(file: FileId, pair: Pair<Rule>) -> ast::Term {
+    // println!("parse_throw {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::throw_term => {
+            let span = term_span(file, &pair);
+            let term = parse_term(file, pair.into_inner().next().unwrap());
+            ast::Throw {
+                term: term.boxed(),
+                span,
+            }
+            .into_term()
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_try(file: FileId, pair: Pair<Rule>) -> ast::Term {
+    // println!("parse_try {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::try_term => {
+            let span = term_span(file, &pair);
+            let mut pairs = pair.into_inner();
+            let body = parse_block(file, pairs.next().unwrap());
+            let catches = pairs
+                .map(|pair| {
+                    let span = term_span(file, &pair);
+                    let mut pairs = pair.into_inner();
+                    let class_name = ast::ClassName(pairs.next().unwrap().as_str().into());
+                    let name = ast::FieldName(pairs.next().unwrap().as_str().into());
+                    let body = parse_block(file, pairs.next().unwrap());
+                    ast::Catch {
+                        class_name,
+                        name,
+                        body: body.boxed(),
+                        span,
+                    }
+                })
+                .collect();
+            ast::Try {
+                body: body.boxed(),
+                catches,
+                span,
+            }
+            .into_term()
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_operand(file: FileId, pair: Pair<Rule>) -> ast::Term {
+    // println!("parse_operand {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::operand => {
+            let span = term_span(file, &pair);
+            let mut pairs = pair.into_inner().peekable();
+            let mut unary_operators = Vec::new();
+            while let Some(pair) = pairs.next_if(|pair| pair.as_rule() == Rule::unary_operator) {
+                let op = match pair.as_str() {
+                    "!" => ast::UnaryOperator::Not,
+                    "-" => ast::UnaryOperator::Neg,
+                    _ => unreachable!(),
+                };
+                unary_operators.push((op, pair.as_span().start()));
+            }
+            let mut term_left = parse_term_left(file, pairs.next().unwrap());
+            // `-9223372036854775808` is the literal `i64::MIN`, see `check_int_literals`
+            if let (Some((ast::UnaryOperator::Neg, start)), ast::Term::Literal(literal)) =
+                (unary_operators.last(), &mut term_left)
+            {
+                if literal.value == ast::Primitive::Int(i64::MIN) {
+                    literal.span.start = *start;
+                    unary_operators.pop();
+                }
+            }
             // build tree of following, left-associative terms, from left to right
-            pairs.fold(term_left, |left_term, pair| match pair.as_rule() {
+            let term = pairs.fold(term_left, |left_term, pair| match pair.as_rule() {
                 Rule::dot_chain => {
+                    let span = left_term.span().to(term_span(file, &pair));
                     let pair = pair.into_inner().next().unwrap();
                     match pair.as_rule() {
-                        Rule::method_call => {
-                            parse_method_call(pair).into_full(left_term).into_term()
-                        }
+                        Rule::method_call => parse_method_call(file, pair)
+                            .into_full(left_term, span)
+                            .into_term(),
 
-                        Rule::field_access => {
-                            parse_field_access(pair).into_full(left_term).into_term()
-                        }
+                        Rule::field_access => parse_field_access(pair)
+                            .into_full(left_term, span)
+                            .into_term(),
                         _ => unreachable!(),
                     }
                 }
                 _ => unreachable!(),
-            })
+            });
+            // unary operators bind weaker than method calls and field accesses
+            unary_operators
+                .into_iter()
+                .rev()
+                .fold(term, |term, (op, start)| {
+                    let span = Span { start, ..span };
+                    ast::UnaryOp {
+                        op,
+                        term: term.boxed(),
+                        span,
+                    }
+                    .into_term()
+                })
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_term_left(pair: Pair<Rule>) -> ast::Term {
+fn parse_term_left(file: FileId, pair: Pair<Rule>) -> ast::Term {
     // println!("parse_term_left {:#?}", &pair);
     match pair.as_rule() {
         Rule::term_left => {
             let pair = pair.into_inner().next().unwrap();
             match pair.as_rule() {
-                Rule::term => parse_term(pair),
-                Rule::cast => parse_cast(pair).into_term(),
-                Rule::new_call => parse_new_call(pair).into_term(),
-                Rule::ident => ast::Term::from_variable_str(pair.as_str()),
+                Rule::term => parse_term(file, pair),
+                Rule::cast => parse_cast(file, pair).into_term(),
+                Rule::lambda => parse_lambda(file, pair).into_term(),
+                Rule::block => parse_block(file, pair),
+                Rule::new_call => parse_new_call(file, pair).into_term(),
+                Rule::literal => parse_literal(file, pair).into_term(),
+                Rule::ident => ast::Term::from_variable_str(pair.as_str(), term_span(file, &pair)),
                 _ => unreachable!(),
             }
         }
@@ -236,32 +768,105 @@ fn parse_term_left(pair: Pair<Rule>) -> ast::Term {
     }
 }
 
-fn parse_cast(pair: Pair<Rule>) -> ast::Cast {
+fn parse_literal(file: FileId, pair: Pair<Rule>) -> ast::Literal {
+    // println!("parse_literal {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::literal => {
+            let span = term_span(file, &pair);
+            let pair = pair.into_inner().next().unwrap();
+            let value = match pair.as_rule() {
+                // NOTE(unwrap): `check_int_literals` only accepts literals up
+                // to 9223372036854775808, which wraps to `i64::MIN` to be
+                // negated by `parse_operand`
+                Rule::int_literal => {
+                    ast::Primitive::Int(pair.as_str().parse::<u64>().unwrap() as i64)
+                }
+                Rule::boolean_literal => ast::Primitive::Boolean(pair.as_str() == "true"),
+                Rule::string_literal => ast::Primitive::String(parse_string_literal(pair)),
+                _ => unreachable!(),
+            };
+            ast::Literal { value, span }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// The content of a string literal, with its escapes resolved.
+fn parse_string_literal(pair: Pair<Rule>) -> String {
+    let content = pair.into_inner().next().unwrap().as_str();
+    let mut value = String::new();
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        value.push(match c {
+            '\\' => match chars.next().unwrap() {
+                'n' => '\n',
+                't' => '\t',
+                c => c,
+            },
+            c => c,
+        });
+    }
+    value
+}
+
+fn parse_cast(file: FileId, pair: Pair<Rule>) -> ast::Cast {
     // println!("parse_cast {:#?}", &pair);
     match pair.as_rule() {
         Rule::cast => {
+            let span = term_span(file, &pair);
             let mut pairs = pair.into_inner();
-            let to_class_name = pairs.next().unwrap().as_str();
-            let term = parse_term(pairs.next().unwrap());
+            let to_type = parse_class_type(pairs.next().unwrap());
+            let term = parse_operand(file, pairs.next().unwrap());
             ast::Cast {
-                to_class_name: ast::ClassName(to_class_name.into()),
+                to_class_name: to_type.name,
+                type_args: to_type.args,
                 term: term.boxed(),
+                span,
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_lambda(file: FileId, pair: Pair<Rule>) -> ast::Lambda {
+    // println!("parse_lambda {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::lambda => {
+            let span = term_span(file, &pair);
+            let mut pairs = pair.into_inner().peekable();
+            let args = pairs
+                .next_if(|pair| pair.as_rule() == Rule::arg_list)
+                .map(parse_arg_list)
+                .unwrap_or_default();
+            let body = parse_term(file, pairs.next().unwrap());
+            ast::Lambda {
+                args,
+                body: body.boxed(),
+                target: None,
+                span,
             }
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_new_call(pair: Pair<Rule>) -> ast::NewCall {
+fn parse_new_call(file: FileId, pair: Pair<Rule>) -> ast::NewCall {
     // println!("parse_new_call {:#?}", &pair);
     match pair.as_rule() {
         Rule::new_call => {
-            let mut pairs = pair.into_inner();
+            let span = term_span(file, &pair);
+            let mut pairs = pair.into_inner().peekable();
             let class_name = pairs.next().unwrap().as_str();
-            let arg_terms = pairs.map(|pair| parse_term(pair).boxed()).collect();
+            let type_args = pairs
+                .next_if(|pair| pair.as_rule() == Rule::type_args)
+                .map(parse_type_args)
+                .unwrap_or_default();
+            let arg_terms = pairs.map(|pair| parse_term(file, pair).boxed()).collect();
             ast::NewCall {
                 class_name: ast::ClassName(class_name.into()),
+                type_args,
                 arg_terms,
+                span,
             }
         }
         _ => unreachable!(),
@@ -273,10 +878,11 @@ struct PartialFieldAccess {
 }
 
 impl PartialFieldAccess {
-    fn into_full(self, object_term: ast::Term) -> ast::FieldAccess {
+    fn into_full(self, object_term: ast::Term, span: Span) -> ast::FieldAccess {
         ast::FieldAccess {
             field: self.field,
             object_term: object_term.boxed(),
+            span,
         }
     }
 }
@@ -297,31 +903,257 @@ fn parse_field_access(pair: Pair<Rule>) -> PartialFieldAccess {
 
 struct PartialMethodCall {
     method_name: ast::MethodName,
+    type_args: Vec<ast::Type>,
     arg_terms: Vec<Box<ast::Term>>,
 }
 
 impl PartialMethodCall {
-    fn into_full(self, object_term: ast::Term) -> ast::MethodCall {
+    fn into_full(self, object_term: ast::Term, span: Span) -> ast::MethodCall {
         ast::MethodCall {
             object_term: object_term.boxed(),
             arg_terms: self.arg_terms,
             method_name: self.method_name,
+            type_args: self.type_args,
+            span,
         }
     }
 }
 
-fn parse_method_call(pair: Pair<Rule>) -> PartialMethodCall {
+fn parse_method_call(file: FileId, pair: Pair<Rule>) -> PartialMethodCall {
     // println!("parse_method_call {:#?}", &pair);
     match pair.as_rule() {
         Rule::method_call => {
-            let mut pairs = pair.into_inner();
+            let mut pairs = pair.into_inner().peekable();
             let method_name = pairs.next().unwrap().as_str();
-            let arg_terms = pairs.map(|pair| parse_term(pair).boxed()).collect();
+            let type_args = pairs
+                .next_if(|pair| pair.as_rule() == Rule::type_args)
+                .map(parse_type_args)
+                .unwrap_or_default();
+            let arg_terms = pairs.map(|pair| parse_term(file, pair).boxed()).collect();
             PartialMethodCall {
                 method_name: ast::MethodName(method_name.into()),
+                type_args,
                 arg_terms,
             }
         }
         _ => unreachable!(),
     }
 }
+
+fn parse_class_type(pair: Pair<Rule>) -> ast::ClassType {
+    // println!("parse_class_type {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::class_type => {
+            let mut pairs = pair.into_inner();
+            let name = pairs.next().unwrap().as_str();
+            let args = pairs.next().map(parse_type_args).unwrap_or_default();
+            ast::ClassType {
+                name: ast::ClassName(name.into()),
+                args,
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_type_args(pair: Pair<Rule>) -> Vec<ast::Type> {
+    // println!("parse_type_args {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::type_args => pair
+            .into_inner()
+            .map(|pair| parse_class_type(pair).into_type())
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_type_params(pair: Pair<Rule>) -> Vec<ast::TypeParam> {
+    // println!("parse_type_params {:#?}", &pair);
+    match pair.as_rule() {
+        Rule::type_params => pair
+            .into_inner()
+            .map(|pair| {
+                let mut pairs = pair.into_inner();
+                let name = pairs.next().unwrap().as_str();
+                let bound = pairs
+                    .next()
+                    .map(parse_class_type)
+                    .unwrap_or_else(ast::ClassType::object);
+                ast::TypeParam {
+                    name: ast::TypeVariable(name.into()),
+                    bound,
+                }
+            })
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+/// Turn the class types in `class` which name a type parameter in scope into
+/// type variables. Method type parameters shadow those of the class.
+fn resolve_class_type_variables(class: &mut ast::ClassDefinition) {
+    let scope: Vec<_> = class
+        .type_params
+        .iter()
+        .map(|param| param.name.clone())
+        .collect();
+    for param in &mut class.type_params {
+        resolve_class_type(&scope, &mut param.bound);
+    }
+    resolve_class_type(&scope, &mut class.super_type);
+    for interface in &mut class.interfaces {
+        resolve_class_type(&scope, interface);
+    }
+    for (field_type, _) in class
+        .fields
+        .iter_mut()
+        .chain(class.constructor.args.iter_mut())
+    {
+        resolve_type(&scope, field_type);
+    }
+    for method in &mut class.methods {
+        let scope: Vec<_> = method
+            .type_params
+            .iter()
+            .map(|param| param.name.clone())
+            .chain(scope.iter().cloned())
+            .collect();
+        for param in &mut method.type_params {
+            resolve_class_type(&scope, &mut param.bound);
+        }
+        resolve_type(&scope, &mut method.return_type);
+        for (arg_type, _) in &mut method.args {
+            resolve_type(&scope, arg_type);
+        }
+        resolve_term(&scope, &mut method.return_term);
+    }
+    for method in &mut class.abstract_methods {
+        resolve_method_signature(&scope, method);
+    }
+}
+
+/// Like [`resolve_class_type_variables`] for an interface.
+fn resolve_interface_type_variables(interface: &mut ast::InterfaceDefinition) {
+    let scope: Vec<_> = interface
+        .type_params
+        .iter()
+        .map(|param| param.name.clone())
+        .collect();
+    for param in &mut interface.type_params {
+        resolve_class_type(&scope, &mut param.bound);
+    }
+    for super_type in &mut interface.super_types {
+        resolve_class_type(&scope, super_type);
+    }
+    for method in &mut interface.methods {
+        resolve_method_signature(&scope, method);
+    }
+}
+
+/// Resolve the types of a method without a body, within `scope` of its class or interface.
+fn resolve_method_signature(scope: &[ast::TypeVariable], method: &mut ast::MethodSignature) {
+    let scope: Vec<_> = method
+        .type_params
+        .iter()
+        .map(|param| param.name.clone())
+        .chain(scope.iter().cloned())
+        .collect();
+    for param in &mut method.type_params {
+        resolve_class_type(&scope, &mut param.bound);
+    }
+    resolve_type(&scope, &mut method.return_type);
+    for (arg_type, _) in &mut method.args {
+        resolve_type(&scope, arg_type);
+    }
+}
+
+/// Turn a class type naming a type variable in `scope` or a primitive type
+/// into that type. Type variables shadow primitive types.
+fn resolve_type(scope: &[ast::TypeVariable], ty: &mut ast::Type) {
+    match ty {
+        ast::Type::Var(_) | ast::Type::Primitive(_) => {}
+        ast::Type::Class(class_type) => {
+            if class_type.args.is_empty() {
+                if let Some(var) = scope.iter().find(|var| var.0 == class_type.name.0) {
+                    *ty = ast::Type::Var(var.clone());
+                    return;
+                }
+                if let Some(primitive_type) = ast::PrimitiveType::from_name(&class_type.name.0) {
+                    *ty = primitive_type.into_type();
+                    return;
+                }
+            }
+            resolve_class_type(scope, class_type);
+        }
+    }
+}
+
+fn resolve_class_type(scope: &[ast::TypeVariable], class_type: &mut ast::ClassType) {
+    for arg in &mut class_type.args {
+        resolve_type(scope, arg);
+    }
+}
+
+fn resolve_term(scope: &[ast::TypeVariable], term: &mut ast::Term) {
+    let (type_args, sub_terms): (&mut Vec<ast::Type>, Vec<&mut Box<ast::Term>>) = match term {
+        ast::Term::Variable(_) => return,
+        ast::Term::FieldAccess(field_access) => {
+            return resolve_term(scope, &mut field_access.object_term)
+        }
+        ast::Term::MethodCall(method_call) => (
+            &mut method_call.type_args,
+            std::iter::once(&mut method_call.object_term)
+                .chain(method_call.arg_terms.iter_mut())
+                .collect(),
+        ),
+        ast::Term::NewCall(new_call) => (
+            &mut new_call.type_args,
+            new_call.arg_terms.iter_mut().collect(),
+        ),
+        ast::Term::Cast(cast) => (&mut cast.type_args, vec![&mut cast.term]),
+        ast::Term::Lambda(lambda) => {
+            for (arg_type, _) in &mut lambda.args {
+                resolve_type(scope, arg_type);
+            }
+            return resolve_term(scope, &mut lambda.body);
+        }
+        ast::Term::Literal(_) => return,
+        ast::Term::BinaryOp(binary_op) => {
+            resolve_term(scope, &mut binary_op.lhs);
+            return resolve_term(scope, &mut binary_op.rhs);
+        }
+        ast::Term::UnaryOp(unary_op) => return resolve_term(scope, &mut unary_op.term),
+        ast::Term::If(if_term) => {
+            resolve_term(scope, &mut if_term.condition);
+            resolve_term(scope, &mut if_term.then_term);
+            return resolve_term(scope, &mut if_term.else_term);
+        }
+        ast::Term::FieldAssign(field_assign) => {
+            resolve_term(scope, &mut field_assign.object_term);
+            return resolve_term(scope, &mut field_assign.value_term);
+        }
+        ast::Term::Let(let_term) => {
+            resolve_type(scope, &mut let_term.ty);
+            resolve_term(scope, &mut let_term.value_term);
+            return resolve_term(scope, &mut let_term.body);
+        }
+        ast::Term::Seq(seq) => {
+            resolve_term(scope, &mut seq.first);
+            return resolve_term(scope, &mut seq.second);
+        }
+        ast::Term::Location(_) => return,
+        ast::Term::Throw(throw) => return resolve_term(scope, &mut throw.term),
+        ast::Term::Try(try_term) => {
+            for catch in &mut try_term.catches {
+                resolve_term(scope, &mut catch.body);
+            }
+            return resolve_term(scope, &mut try_term.body);
+        }
+    };
+    for ty in type_args {
+        resolve_type(scope, ty);
+    }
+    for sub_term in sub_terms {
+        resolve_term(scope, sub_term);
+    }
+}
@@ -0,0 +1,1033 @@
+//! Width-aware printing of syntax trees as FJ source, used by `fj fmt` and
+//! the [`Display`] implementations of definitions and terms.
+//!
+//! A tree is first turned into a [`Doc`] describing where lines may be
+//! broken, which is then rendered as in Wadler's "A prettier printer": a
+//! group is printed on one line if it fits into the remaining width, and
+//! otherwise all of its line breaks are taken. Comments are not part of the
+//! tree, so the formatter takes them from the tokens of the source and prints
+//! each one before the first item, member, statement or term after it.
+
+use crate::ast::{
+    ArgPair, Ast, BinaryOp, BinaryOperator, ClassDefinition, Constructor, DisplayTypeArgs,
+    DisplayTypeParams, If, InterfaceDefinition, Literal, MethodCall, MethodDefinition,
+    MethodSignature, Primitive, Script, Term, UnaryOp, UnaryOperator,
+};
+use crate::lexer::{self, Token, TokenKind};
+use crate::parser;
+use crate::source::{FileId, Span};
+use anyhow::Context;
+use std::fmt::Display;
+
+/// Line width `fj fmt` formats to, unless given `--width`.
+pub const DEFAULT_WIDTH: usize = 100;
+
+/// Spaces per level of indentation.
+const INDENT: usize = 4;
+
+/// Binding strength of terms: a term is printed in parentheses where a term
+/// of a higher level is expected. Binary operations are in between, at the
+/// precedence of their operator.
+///
+/// `if`, `throw`, `try`, field assignments and lambdas extend as far to the
+/// right as possible.
+const OPEN: u8 = 0;
+/// Unary operations and casts, the operands of binary operators.
+const OPERAND: u8 = 7;
+/// Terms that can be the receiver of a method call or field access.
+const ATOM: u8 = 8;
+
+/// Layouts of a piece of source.
+#[derive(Debug, Clone)]
+enum Doc {
+    Nil,
+    Text(String),
+    /// A space, or a line break if the enclosing group is broken.
+    Line,
+    /// Nothing, or a line break if the enclosing group is broken.
+    SoftLine,
+    /// A line break, breaking all enclosing groups.
+    HardLine,
+    /// Breaks all enclosing groups, without printing anything.
+    BreakParent,
+    /// Indents the lines started within by one more level.
+    Nest(Box<Doc>),
+    /// Breaks either all or none of the lines within.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(text: impl Into<String>) -> Doc {
+    Doc::Text(text.into())
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+fn concat(docs: impl IntoIterator<Item = Doc>) -> Doc {
+    Doc::Concat(docs.into_iter().collect())
+}
+
+/// `(items)` on one line, or with one item per line. Separators are part of
+/// the items, so comments can follow them.
+fn list(items: Vec<Doc>) -> Doc {
+    if items.is_empty() {
+        return text("()");
+    }
+    let mut inner = vec![Doc::SoftLine];
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            inner.push(Doc::Line);
+        }
+        inner.push(item);
+    }
+    group(concat([
+        text("("),
+        nest(concat(inner)),
+        Doc::SoftLine,
+        text(")"),
+    ]))
+}
+
+fn parens(doc: Doc) -> Doc {
+    concat([text("("), doc, text(")")])
+}
+
+/// Whether the lines of a group are broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    // the indentation of a line is written with its first text, so that
+    // blank lines have no trailing whitespace
+    let mut indentation = None;
+    let mut stack = vec![(0, Mode::Break, doc)];
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Nil | Doc::BreakParent => {}
+            Doc::Text(s) => {
+                if let Some(indent) = indentation.take() {
+                    out.extend(std::iter::repeat_n(' ', indent));
+                }
+                out.push_str(s);
+                column = match s.rfind('\n') {
+                    Some(i) => s[i + 1..].chars().count(),
+                    None => column + s.chars().count(),
+                };
+            }
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                out.push('\n');
+                indentation = Some(indent);
+                column = indent;
+            }
+            Doc::Nest(doc) => stack.push((indent + INDENT, mode, doc)),
+            Doc::Group(doc) => {
+                let mode = if mode == Mode::Flat || fits(width.saturating_sub(column), doc, &stack)
+                {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.push((indent, mode, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+    out
+}
+
+/// Whether `doc` fits into `width` on one line, together with what follows
+/// it up to the next line break in `rest`.
+fn fits(mut width: usize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Nil => {}
+            Doc::Text(s) => {
+                if s.contains('\n') {
+                    return mode == Mode::Break;
+                }
+                match width.checked_sub(s.chars().count()) {
+                    Some(remaining) => width = remaining,
+                    None => return false,
+                }
+            }
+            Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::BreakParent if mode == Mode::Break => {
+                if !matches!(doc, Doc::BreakParent) {
+                    return true;
+                }
+            }
+            Doc::Line => match width.checked_sub(1) {
+                Some(remaining) => width = remaining,
+                None => return false,
+            },
+            Doc::SoftLine => {}
+            Doc::HardLine | Doc::BreakParent => return false,
+            Doc::Nest(doc) | Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+        }
+    }
+}
+
+/// Format a script or library, keeping its comments.
+///
+/// The formatted source is checked to parse to the same syntax tree.
+pub fn format_script(file: FileId, source: &str, width: usize) -> anyhow::Result<String> {
+    let script = parser::parse_script(file, source).context("parsing failed")?;
+    let doc = Printer::new(source).script(&script.ast, &script.eval_terms);
+    let formatted = finish(render(&doc, width));
+    if !parser::parse_script(file, &formatted).is_ok_and(|formatted| formatted == script) {
+        anyhow::bail!("formatting would change the program, this is a bug of the formatter");
+    }
+    Ok(formatted)
+}
+
+/// Format a term, as in `.fje` files, keeping its comments.
+///
+/// The formatted source is checked to parse to the same term.
+pub fn format_eval_input(file: FileId, source: &str, width: usize) -> anyhow::Result<String> {
+    let term = parser::parse_eval_input(file, source).context("parsing failed")?;
+    let doc = Printer::new(source).eval_input(&term);
+    let formatted = finish(render(&doc, width));
+    if !parser::parse_eval_input(file, &formatted).is_ok_and(|formatted| formatted == term) {
+        anyhow::bail!("formatting would change the term, this is a bug of the formatter");
+    }
+    Ok(formatted)
+}
+
+/// Print a script with lines of at most `width` characters where possible.
+pub fn print_script(script: &Script, width: usize) -> String {
+    finish(render(
+        &Printer::new("").script(&script.ast, &script.eval_terms),
+        width,
+    ))
+}
+
+/// Print a term with lines of at most `width` characters where possible.
+pub fn print_term(term: &Term, width: usize) -> String {
+    render(&Printer::new("").term(term), width)
+}
+
+/// End a file with a single line break.
+fn finish(mut out: String) -> String {
+    out.truncate(out.trim_end().len());
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Converts syntax trees to [`Doc`]s, with the comments of the source they
+/// were parsed from.
+struct Printer<'s> {
+    source: &'s str,
+    /// Tokens of `source` other than comments.
+    tokens: Vec<Token>,
+    /// Comments of `source`, of which the first `printed` are printed.
+    comments: Vec<Token>,
+    printed: usize,
+}
+
+/// A definition or `eval` block at the top level of a script.
+enum Item<'a> {
+    Class(&'a ClassDefinition),
+    Interface(&'a InterfaceDefinition),
+    Eval(&'a Term, Span),
+}
+
+/// A method of a class, which is abstract or has a body.
+enum Method<'a> {
+    Abstract(&'a MethodSignature),
+    Concrete(&'a MethodDefinition),
+}
+
+impl<'s> Printer<'s> {
+    fn new(source: &'s str) -> Self {
+        let (comments, tokens) = lexer::tokenize(source)
+            .into_iter()
+            .partition(|token| token.kind == TokenKind::Comment);
+        Printer {
+            source,
+            tokens,
+            comments,
+            printed: 0,
+        }
+    }
+
+    fn next_comment(&self, before: usize) -> Option<Token> {
+        self.comments
+            .get(self.printed)
+            .filter(|comment| comment.start < before)
+            .copied()
+    }
+
+    fn comment_text(&self, comment: &Token) -> String {
+        lexer::text(self.source, comment).trim_end().to_string()
+    }
+
+    /// The comments before `start`, each on a line of its own. A blank line
+    /// after a comment is kept.
+    fn leading(&mut self, start: usize) -> Doc {
+        let mut docs = vec![];
+        while let Some(comment) = self.next_comment(start) {
+            self.printed += 1;
+            docs.push(text(self.comment_text(&comment)));
+            docs.push(Doc::HardLine);
+            let next = self
+                .comments
+                .get(self.printed)
+                .map_or(start, |next| next.start.min(start))
+                .clamp(comment.end, self.source.len());
+            let between = &self.source[comment.end..next];
+            if between.trim().is_empty() && between.matches('\n').count() > 1 {
+                docs.push(Doc::HardLine);
+            }
+        }
+        concat(docs)
+    }
+
+    /// The comments before `end`, at the end of the body of a definition or
+    /// block, each on a line of its own.
+    fn closing(&mut self, end: usize) -> Doc {
+        let mut docs = vec![];
+        while let Some(comment) = self.next_comment(end) {
+            self.printed += 1;
+            docs.push(Doc::HardLine);
+            docs.push(text(self.comment_text(&comment)));
+        }
+        if docs.is_empty() {
+            return Doc::Nil;
+        }
+        docs.push(Doc::BreakParent);
+        concat(docs)
+    }
+
+    /// A comment after `end` on the same line, separated from it by at most
+    /// `;` or `,`. A line break has to follow.
+    fn trailing(&mut self, end: usize) -> Doc {
+        match self.comments.get(self.printed).copied() {
+            Some(comment)
+                if comment.start >= end
+                    && self.source[end..comment.start]
+                        .chars()
+                        .all(|c| matches!(c, ' ' | '\t' | ';' | ',')) =>
+            {
+                self.printed += 1;
+                concat([
+                    text(format!(" {}", self.comment_text(&comment))),
+                    Doc::BreakParent,
+                ])
+            }
+            _ => Doc::Nil,
+        }
+    }
+
+    /// Start of the keyword before a term starting at `start`, or `start` if
+    /// it is not there. The span of a term in parentheses or braces does not
+    /// include them, so they are skipped.
+    fn keyword_start(&self, keyword: &str, start: usize) -> usize {
+        let i = self.tokens.partition_point(|token| token.start < start);
+        self.tokens[..i]
+            .iter()
+            .rev()
+            .find(|token| !matches!(lexer::text(self.source, token), "(" | "{"))
+            .filter(|token| lexer::text(self.source, token) == keyword)
+            .map_or(start, |token| token.start)
+    }
+
+    /// End of the `)` and `}` closing a term ending at `end`, like
+    /// [`Self::keyword_start`].
+    fn closing_end(&self, end: usize) -> usize {
+        let i = self.tokens.partition_point(|token| token.start < end);
+        self.tokens[i..]
+            .iter()
+            .take_while(|token| matches!(lexer::text(self.source, token), ")" | "}"))
+            .last()
+            .map_or(end, |token| token.end)
+    }
+
+    /// Start and end of the field declarations of `class`, which have no
+    /// spans, or nothing if they are not found in the source.
+    fn field_positions(&self, class: &ClassDefinition) -> Vec<(usize, usize)> {
+        let first = self
+            .tokens
+            .partition_point(|token| token.start < class.span.start);
+        let last = self
+            .tokens
+            .partition_point(|token| token.start < class.constructor.span.start);
+        let tokens = &self.tokens[first..last];
+        let Some(open) = tokens
+            .iter()
+            .position(|token| lexer::text(self.source, token) == "{")
+        else {
+            return vec![];
+        };
+        let mut positions = vec![];
+        let mut start = None;
+        for token in &tokens[open + 1..] {
+            let field_start = *start.get_or_insert(token.start);
+            if lexer::text(self.source, token) == ";" {
+                positions.push((field_start, token.end));
+                start = None;
+            }
+        }
+        positions
+    }
+
+    fn eval_input(&mut self, term: &Term) -> Doc {
+        let term = self.term(term);
+        let rest = self.leading(self.source.len() + 1);
+        concat([term, Doc::HardLine, rest])
+    }
+
+    /// Imports first, then definitions and `eval` blocks in the order of the
+    /// source, separated by blank lines.
+    fn script(&mut self, ast: &Ast, eval_terms: &[Term]) -> Doc {
+        let mut items = vec![];
+        for import in &ast.imports {
+            items.push(concat([
+                self.leading(import.span.start),
+                text(import.to_string()),
+                self.trailing(import.span.end),
+                Doc::HardLine,
+            ]));
+        }
+        if !ast.imports.is_empty() {
+            items.push(Doc::HardLine);
+        }
+
+        let mut definitions: Vec<_> = ast
+            .interface_definitions
+            .iter()
+            .map(|interface| (interface.span, Item::Interface(interface)))
+            .chain(
+                ast.class_definitions
+                    .iter()
+                    .map(|class| (class.span, Item::Class(class))),
+            )
+            .chain(eval_terms.iter().map(|term| {
+                let span = Span {
+                    start: self.keyword_start("eval", term.span().start),
+                    end: self.closing_end(term.span().end),
+                    ..term.span()
+                };
+                (span, Item::Eval(term, span))
+            }))
+            .collect();
+        definitions.sort_by_key(|(span, _)| *span);
+        for (span, item) in definitions {
+            let leading = self.leading(span.start);
+            let item = match item {
+                Item::Class(class) => self.class(class),
+                Item::Interface(interface) => self.interface(interface),
+                Item::Eval(term, span) => {
+                    let term = self.term(term);
+                    let closing = self.closing(span.end);
+                    group(concat([
+                        text("eval {"),
+                        nest(concat([Doc::Line, term, closing])),
+                        Doc::Line,
+                        text("}"),
+                    ]))
+                }
+            };
+            items.push(concat([
+                leading,
+                item,
+                self.trailing(span.end),
+                Doc::HardLine,
+                Doc::HardLine,
+            ]));
+        }
+        items.push(self.leading(self.source.len() + 1));
+        concat(items)
+    }
+
+    /// `header {`, followed by `members` on lines of their own and `}`.
+    fn body(&mut self, header: String, members: Vec<Doc>, end: usize) -> Doc {
+        let closing = self.closing(end);
+        if members.is_empty() && matches!(closing, Doc::Nil) {
+            return text(format!("{} {{}}", header));
+        }
+        let mut inner = vec![];
+        for member in members {
+            inner.push(Doc::HardLine);
+            inner.push(member);
+        }
+        inner.push(closing);
+        concat([
+            text(format!("{} {{", header)),
+            nest(concat(inner)),
+            Doc::HardLine,
+            text("}"),
+        ])
+    }
+
+    fn interface(&mut self, interface: &InterfaceDefinition) -> Doc {
+        let mut header = format!(
+            "interface {}{}",
+            interface.name,
+            DisplayTypeParams(&interface.type_params)
+        );
+        if !interface.super_types.is_empty() {
+            header += &format!(" extends {}", join(&interface.super_types));
+        }
+        let members = interface
+            .methods
+            .iter()
+            .map(|method| self.signature(method, false))
+            .collect();
+        self.body(header, members, interface.span.end)
+    }
+
+    fn class(&mut self, class: &ClassDefinition) -> Doc {
+        let mut header = format!(
+            "{}class {}{} extends {}",
+            if class.is_abstract { "abstract " } else { "" },
+            class.name,
+            DisplayTypeParams(&class.type_params),
+            class.super_type
+        );
+        if !class.interfaces.is_empty() {
+            header += &format!(" implements {}", join(&class.interfaces));
+        }
+
+        let mut members = vec![];
+        let positions = self.field_positions(class);
+        for (i, (ty, name)) in class.fields.iter().enumerate() {
+            let field = text(format!("{} {};", ty, name));
+            members.push(match positions.get(i) {
+                Some(&(start, end)) if positions.len() == class.fields.len() => {
+                    concat([self.leading(start), field, self.trailing(end)])
+                }
+                _ => field,
+            });
+        }
+        members.push(self.constructor(&class.constructor));
+
+        let mut methods: Vec<_> = class
+            .abstract_methods
+            .iter()
+            .map(|method| (method.span, Method::Abstract(method)))
+            .chain(
+                class
+                    .methods
+                    .iter()
+                    .map(|method| (method.span, Method::Concrete(method))),
+            )
+            .collect();
+        methods.sort_by_key(|(span, _)| *span);
+        for (_, method) in methods {
+            members.push(match method {
+                Method::Abstract(method) => self.signature(method, true),
+                Method::Concrete(method) => self.method(method),
+            });
+        }
+        self.body(header, members, class.span.end)
+    }
+
+    /// `C(T̄ f̄) { super(f̄); this.f̄ = f̄; }`, on one line if it only calls
+    /// `super`.
+    fn constructor(&mut self, constructor: &Constructor) -> Doc {
+        let leading = self.leading(constructor.span.start);
+        let mut body = vec![
+            Doc::Line,
+            text(format!("super({});", join(&constructor.super_call))),
+        ];
+        for (field, arg) in &constructor.assignments {
+            body.push(Doc::Line);
+            body.push(text(format!("this.{} = {};", field, arg)));
+        }
+        if constructor.assignments.len() > 1 {
+            body.push(Doc::BreakParent);
+        }
+        body.push(self.closing(constructor.span.end));
+        concat([
+            leading,
+            group(concat([
+                text(constructor.name.to_string()),
+                params(&constructor.args),
+                text(" {"),
+                nest(concat(body)),
+                Doc::Line,
+                text("}"),
+            ])),
+            self.trailing(constructor.span.end),
+        ])
+    }
+
+    /// A method without a body, declared in an interface or `abstract` in a
+    /// class.
+    fn signature(&mut self, method: &MethodSignature, is_abstract: bool) -> Doc {
+        concat([
+            self.leading(method.span.start),
+            text(format!(
+                "{}{}{} {}",
+                if is_abstract { "abstract " } else { "" },
+                DisplayTypeParamsBefore(&method.type_params),
+                method.return_type,
+                method.method_name
+            )),
+            params(&method.args),
+            text(";"),
+            self.trailing(method.span.end),
+        ])
+    }
+
+    fn method(&mut self, method: &MethodDefinition) -> Doc {
+        let leading = self.leading(method.span.start);
+        let header = concat([
+            text(format!(
+                "{}{} {}",
+                DisplayTypeParamsBefore(&method.type_params),
+                method.return_type,
+                method.method_name
+            )),
+            params(&method.args),
+            text(" {"),
+        ]);
+        let mut body = vec![];
+        for statement in self.statements(&method.return_term, true) {
+            body.push(Doc::HardLine);
+            body.push(statement);
+        }
+        body.push(self.closing(method.span.end));
+        concat([
+            leading,
+            header,
+            nest(concat(body)),
+            Doc::HardLine,
+            text("}"),
+            self.trailing(method.span.end),
+        ])
+    }
+
+    /// The local variables `T x = t;` and terms `t;` of a method body or
+    /// block, followed by its result, which is returned from methods.
+    fn statements(&mut self, mut term: &Term, is_method: bool) -> Vec<Doc> {
+        let mut statements = vec![];
+        loop {
+            match term {
+                Term::Let(let_term) => {
+                    let leading = self.leading(let_term.span.start);
+                    let value = self.term(&let_term.value_term);
+                    statements.push(concat([
+                        leading,
+                        text(format!("{} {} = ", let_term.ty, let_term.name)),
+                        value,
+                        text(";"),
+                        self.trailing(let_term.value_term.span().end),
+                    ]));
+                    term = &let_term.body;
+                }
+                Term::Seq(seq) => {
+                    let first = self.term(&seq.first);
+                    statements.push(concat([
+                        first,
+                        text(";"),
+                        self.trailing(seq.first.span().end),
+                    ]));
+                    term = &seq.second;
+                }
+                _ if is_method => {
+                    let leading = self.leading(self.keyword_start("return", term.span().start));
+                    let result = self.term(term);
+                    statements.push(concat([
+                        leading,
+                        text("return "),
+                        result,
+                        text(";"),
+                        self.trailing(term.span().end),
+                    ]));
+                    return statements;
+                }
+                _ => {
+                    let result = self.term(term);
+                    statements.push(concat([result, self.trailing(term.span().end)]));
+                    return statements;
+                }
+            }
+        }
+    }
+
+    /// `{ t̄; t }`, on one line if it fits.
+    fn block(&mut self, term: &Term) -> Doc {
+        group(self.block_lines(term))
+    }
+
+    /// A block whose lines are broken with the enclosing group.
+    fn block_lines(&mut self, term: &Term) -> Doc {
+        let mut inner = vec![];
+        for statement in self.statements(term, false) {
+            inner.push(Doc::Line);
+            inner.push(statement);
+        }
+        concat([text("{"), nest(concat(inner)), Doc::Line, text("}")])
+    }
+
+    fn term(&mut self, term: &Term) -> Doc {
+        self.term_at(term, OPEN)
+    }
+
+    /// `term` where a term of at least `level` is expected.
+    fn term_at(&mut self, term: &Term, level: u8) -> Doc {
+        if self::level(term) < level {
+            return parens(self.term(term));
+        }
+        let leading = self.leading(term.span().start);
+        let doc = match term {
+            Term::Variable(variable) => text(variable.name.to_string()),
+            Term::Literal(literal) => text(literal.value.to_string()),
+            Term::Location(location) => text(format!("@{}", location.id)),
+            Term::FieldAccess(_) | Term::MethodCall(_) => self.chain(term),
+            Term::NewCall(new_call) => concat([
+                text(format!(
+                    "new {}{}",
+                    new_call.class_name,
+                    DisplayTypeArgs(&new_call.type_args)
+                )),
+                self.args(&new_call.arg_terms),
+            ]),
+            Term::Cast(cast) => {
+                // NOTE: `(C) -t` is a subtraction
+                let operand = if is_negative(&cast.term) {
+                    parens(self.term(&cast.term))
+                } else {
+                    self.term_at(&cast.term, OPERAND)
+                };
+                concat([
+                    text(format!(
+                        "({}{}) ",
+                        cast.to_class_name,
+                        DisplayTypeArgs(&cast.type_args)
+                    )),
+                    operand,
+                ])
+            }
+            Term::Lambda(lambda) => {
+                let body = self.term(&lambda.body);
+                let lambda_doc = concat([params(&lambda.args), text(" -> "), body]);
+                match &lambda.target {
+                    Some(target) => concat([text(format!("({}) ", target)), parens(lambda_doc)]),
+                    None => lambda_doc,
+                }
+            }
+            Term::BinaryOp(binary_op) => self.binary(binary_op),
+            Term::UnaryOp(unary_op) => {
+                let operand = if unary_op.op == UnaryOperator::Neg && is_negative(&unary_op.term) {
+                    parens(self.term(&unary_op.term))
+                } else {
+                    self.term_at(&unary_op.term, OPERAND)
+                };
+                concat([text(unary_op.op.to_string()), operand])
+            }
+            Term::If(if_term) => self.conditional(if_term),
+            Term::FieldAssign(field_assign) => concat([
+                self.term_at(&field_assign.object_term, ATOM),
+                text(format!(".{} = ", field_assign.field)),
+                self.term(&field_assign.value_term),
+            ]),
+            Term::Let(_) | Term::Seq(_) => self.block(term),
+            Term::Throw(throw) => concat([text("throw "), self.term(&throw.term)]),
+            Term::Try(try_term) => {
+                let mut docs = vec![text("try "), self.block_lines(&try_term.body)];
+                for catch in &try_term.catches {
+                    docs.push(text(" "));
+                    docs.push(self.leading(catch.span.start));
+                    docs.push(text(format!(
+                        "catch ({} {}) ",
+                        catch.class_name, catch.name
+                    )));
+                    docs.push(self.block_lines(&catch.body));
+                }
+                group(concat(docs))
+            }
+        };
+        concat([leading, doc])
+    }
+
+    /// Arguments `(t̄)` of a method call or `new`.
+    fn args(&mut self, arg_terms: &[Box<Term>]) -> Doc {
+        let mut items = vec![];
+        for (i, arg) in arg_terms.iter().enumerate() {
+            let is_last = i + 1 == arg_terms.len();
+            // NOTE: `m(a.f < b, c > (d))` would call `f<b, c>`
+            let arg_doc = if !is_last && ends_with_less_than_variable(arg) {
+                parens(self.term(arg))
+            } else {
+                self.term(arg)
+            };
+            items.push(concat([
+                arg_doc,
+                text(if is_last { "" } else { "," }),
+                self.trailing(arg.span().end),
+            ]));
+        }
+        list(items)
+    }
+
+    /// A chain of method calls and field accesses, with a line per call if it
+    /// has several and does not fit.
+    fn chain(&mut self, term: &Term) -> Doc {
+        let mut members = vec![];
+        let mut receiver = term;
+        loop {
+            match receiver {
+                Term::FieldAccess(field_access) => {
+                    members.push(Err(&field_access.field));
+                    receiver = &field_access.object_term;
+                }
+                Term::MethodCall(method_call) => {
+                    members.push(Ok(method_call));
+                    receiver = &method_call.object_term;
+                }
+                _ => break,
+            }
+        }
+        let calls = members.iter().filter(|member| member.is_ok()).count();
+        let receiver = self.term_at(receiver, ATOM);
+        let mut docs = vec![];
+        for member in members.into_iter().rev() {
+            match member {
+                Ok(MethodCall {
+                    method_name,
+                    type_args,
+                    arg_terms,
+                    ..
+                }) => {
+                    if calls > 1 {
+                        docs.push(Doc::SoftLine);
+                    }
+                    docs.push(text(format!(
+                        ".{}{}",
+                        method_name,
+                        DisplayTypeArgs(type_args)
+                    )));
+                    docs.push(self.args(arg_terms));
+                }
+                Err(field) => docs.push(text(format!(".{}", field))),
+            }
+        }
+        group(concat([receiver, nest(concat(docs))]))
+    }
+
+    /// A chain of binary operations of the same precedence, with a line per
+    /// operator if it does not fit.
+    fn binary(&mut self, binary_op: &BinaryOp) -> Doc {
+        let precedence = binary_op.op.precedence();
+        let mut operations = vec![];
+        let mut lhs = binary_op;
+        loop {
+            operations.push((lhs.op, lhs.rhs.as_ref()));
+            match lhs.lhs.as_ref() {
+                Term::BinaryOp(inner) if inner.op.precedence() == precedence => lhs = inner,
+                _ => break,
+            }
+        }
+        operations.reverse();
+        let first = self.term_at(&lhs.lhs, precedence);
+        let mut docs = vec![];
+        for (i, &(op, rhs)) in operations.iter().enumerate() {
+            // NOTE: `a.f < b > (c)` would call `f<b>`
+            let is_type_arg = op == BinaryOperator::Lt
+                && matches!(rhs, Term::Variable(_))
+                && operations
+                    .get(i + 1)
+                    .is_some_and(|&(next, _)| next == BinaryOperator::Gt);
+            let rhs = if is_type_arg {
+                parens(self.term(rhs))
+            } else {
+                self.term_at(rhs, precedence + 1)
+            };
+            docs.push(concat([Doc::Line, text(format!("{} ", op)), rhs]));
+        }
+        group(concat([first, nest(concat(docs))]))
+    }
+
+    /// `if (c) t else u`, with `else if` chained.
+    fn conditional(&mut self, mut if_term: &If) -> Doc {
+        let mut docs = vec![];
+        loop {
+            let condition = self.term(&if_term.condition);
+            let then_term = self.term(&if_term.then_term);
+            docs.extend([
+                text("if ("),
+                condition,
+                text(")"),
+                nest(concat([Doc::Line, then_term])),
+                Doc::Line,
+                text("else"),
+            ]);
+            match if_term.else_term.as_ref() {
+                Term::If(else_if) => {
+                    docs.push(text(" "));
+                    if_term = else_if;
+                }
+                else_term => {
+                    let else_term = self.term(else_term);
+                    docs.push(nest(concat([Doc::Line, else_term])));
+                    return group(concat(docs));
+                }
+            }
+        }
+    }
+}
+
+fn level(term: &Term) -> u8 {
+    match term {
+        Term::If(_) | Term::Throw(_) | Term::Try(_) | Term::FieldAssign(_) => OPEN,
+        Term::Lambda(lambda) if lambda.target.is_none() => OPEN,
+        Term::BinaryOp(binary_op) => binary_op.op.precedence(),
+        Term::UnaryOp(_) | Term::Cast(_) | Term::Lambda(_) => OPERAND,
+        Term::Literal(Literal {
+            value: Primitive::Int(i64::MIN..=-1),
+            ..
+        }) => OPERAND,
+        _ => ATOM,
+    }
+}
+
+/// Whether `term` is printed starting with `-`.
+fn is_negative(term: &Term) -> bool {
+    matches!(
+        term,
+        Term::UnaryOp(UnaryOp {
+            op: UnaryOperator::Neg,
+            ..
+        }) | Term::Literal(Literal {
+            value: Primitive::Int(i64::MIN..=-1),
+            ..
+        })
+    )
+}
+
+/// Whether `term` is printed ending in `< x`, which a following `, y > (...)`
+/// would turn into type arguments.
+fn ends_with_less_than_variable(term: &Term) -> bool {
+    match term {
+        Term::BinaryOp(BinaryOp {
+            op: BinaryOperator::Lt,
+            rhs,
+            ..
+        }) if matches!(rhs.as_ref(), Term::Variable(_)) => true,
+        Term::BinaryOp(BinaryOp { rhs: term, .. })
+        | Term::If(If {
+            else_term: term, ..
+        })
+        | Term::Throw(crate::ast::Throw { term, .. })
+        | Term::FieldAssign(crate::ast::FieldAssign {
+            value_term: term, ..
+        }) => ends_with_less_than_variable(term),
+        Term::Lambda(lambda) if lambda.target.is_none() => {
+            ends_with_less_than_variable(&lambda.body)
+        }
+        _ => false,
+    }
+}
+
+/// Parameters `(T̄ x̄)` of a constructor, method or lambda.
+fn params(args: &[ArgPair]) -> Doc {
+    let items = args
+        .iter()
+        .enumerate()
+        .map(|(i, (ty, name))| {
+            let separator = if i + 1 == args.len() { "" } else { "," };
+            text(format!("{} {}{}", ty, name, separator))
+        })
+        .collect();
+    list(items)
+}
+
+fn join(items: &[impl Display]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Type parameters `<X̄ ◁ N̄> ` of a method, followed by a space.
+struct DisplayTypeParamsBefore<'a>(&'a [crate::ast::TypeParam]);
+
+impl Display for DisplayTypeParamsBefore<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            write!(f, "{} ", DisplayTypeParams(self.0))
+        }
+    }
+}
+
+/// Printed as a library, see [`print_script`].
+impl Display for Ast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&finish(render(
+            &Printer::new("").script(self, &[]),
+            DEFAULT_WIDTH,
+        )))
+    }
+}
+
+impl Display for InterfaceDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&render(&Printer::new("").interface(self), DEFAULT_WIDTH))
+    }
+}
+
+impl Display for ClassDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&render(&Printer::new("").class(self), DEFAULT_WIDTH))
+    }
+}
+
+impl Display for Constructor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&render(&Printer::new("").constructor(self), DEFAULT_WIDTH))
+    }
+}
+
+impl Display for MethodDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&render(&Printer::new("").method(self), DEFAULT_WIDTH))
+    }
+}
+
+impl Display for MethodSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&render(
+            &Printer::new("").signature(self, false),
+            DEFAULT_WIDTH,
+        ))
+    }
+}
+
+/// Terms are printed on one line, as in evaluation traces.
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&print_term(self, usize::MAX))
+    }
+}
@@ -0,0 +1,295 @@
+use crate::Evaluator;
+use anyhow::Context;
+use featherweight_java::class_tree::ClassTree;
+use featherweight_java::evaluation::{
+    eval_full_with, eval_heap_step_derivation, eval_heap_with, eval_step_derivation, EvalLimits,
+    Heap, Step,
+};
+use featherweight_java::{
+    build_class_table, diagnostics, loader, parser, typecheck_term, Ast, ClassTable, Gamma,
+    SourceMap, Term, Type, TypecheckConfig,
+};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::PathBuf;
+
+const HELP: &str = "\
+Enter a term to typecheck and evaluate it, or one of the following commands:
+  :type <term>    show the type of a term
+  :step [<term>]  perform a single evaluation step, continues with the last term if none is given
+  :trace <term>   evaluate a term and show every intermediate step with the reduction rules used
+  :load <file>    load an additional library
+  :reload         reload all libraries from disk
+  :classes        list all loaded classes
+  :tree           show the class hierarchy
+  :help           show this help
+  :quit           exit the repl";
+
+pub struct Repl {
+    lib_files: Vec<PathBuf>,
+    config: TypecheckConfig,
+    limits: EvalLimits,
+    evaluator: Evaluator,
+    sources: SourceMap,
+    ct: ClassTable,
+    /// Term to continue with on a bare `:step`, with its heap for `--evaluator heap`
+    stepping: Option<(Term, Option<Heap>)>,
+}
+
+impl Repl {
+    pub fn new(
+        lib_files: Vec<PathBuf>,
+        config: TypecheckConfig,
+        limits: EvalLimits,
+        evaluator: Evaluator,
+    ) -> Self {
+        Repl {
+            lib_files,
+            config,
+            limits,
+            evaluator,
+            sources: SourceMap::new(),
+            ct: ClassTable::try_from_ast(Ast::default()).unwrap(),
+            stepping: None,
+        }
+    }
+
+    pub fn run(mut self) -> anyhow::Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".fj_history"));
+        if let Some(history) = &history {
+            let _ = editor.load_history(history);
+        }
+
+        self.reload();
+        println!("Featherweight Java repl, type `:help` for help.");
+
+        loop {
+            match editor.readline("fj> ") {
+                Ok(line) => {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    editor.add_history_entry(line)?;
+                    if line == ":quit" || line == ":q" {
+                        break;
+                    }
+                    self.handle_line(line);
+                }
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if let Some(history) = &history {
+            let _ = editor.save_history(history);
+        }
+        Ok(())
+    }
+
+    fn handle_line(&mut self, line: &str) {
+        let (command, arg) = match line.strip_prefix(':') {
+            Some(command) => match command.split_once(char::is_whitespace) {
+                Some((command, arg)) => (Some(command), arg.trim()),
+                None => (Some(command), ""),
+            },
+            None => (None, line),
+        };
+        let result = match command {
+            None => self.eval(arg),
+            Some("type") | Some("t") => self.show_type(arg),
+            Some("step") | Some("s") => self.step(arg),
+            Some("trace") => self.trace(arg),
+            Some("load") | Some("l") => self.load(arg),
+            Some("reload") | Some("r") => {
+                self.reload();
+                Ok(())
+            }
+            Some("classes") => {
+                for class_name in self.ct.inner().keys() {
+                    println!("{}", class_name);
+                }
+                Ok(())
+            }
+            Some("tree") => {
+                print!("{}", ClassTree::new(&self.ct));
+                Ok(())
+            }
+            Some("help") | Some("h") | Some("?") => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            Some(command) => Err(anyhow::anyhow!(
+                "unknown command `:{}`, type `:help` for help",
+                command
+            )),
+        };
+        if let Err(err) = result {
+            self.report(&err);
+        }
+    }
+
+    fn report(&self, err: &anyhow::Error) {
+        if let Err(err) = diagnostics::emit_error(&self.sources, err) {
+            eprintln!("error: {:#}", err);
+        }
+    }
+
+    /// (Re-)load all library files into a fresh class table.
+    /// On failure the previous class table is kept.
+    fn reload(&mut self) {
+        let mut sources = SourceMap::new();
+        match Self::load_libraries(&self.lib_files, &self.config, &mut sources) {
+            Ok(ct) => {
+                println!(
+                    "loaded {} classes from {} file(s)",
+                    ct.inner().len(),
+                    self.lib_files.len()
+                );
+                self.ct = ct;
+                self.sources = sources;
+            }
+            Err(err) => {
+                if let Err(err) = diagnostics::emit_error(&sources, &err) {
+                    eprintln!("error: {:#}", err);
+                }
+            }
+        }
+    }
+
+    fn load_libraries(
+        lib_files: &[PathBuf],
+        config: &TypecheckConfig,
+        sources: &mut SourceMap,
+    ) -> anyhow::Result<ClassTable> {
+        let libraries = loader::load_libraries(sources, lib_files)?;
+        let (ct, warnings) = build_class_table(&libraries, config)?;
+        diagnostics::emit_warnings(sources, &warnings)?;
+        Ok(ct)
+    }
+
+    fn load(&mut self, path: &str) -> anyhow::Result<()> {
+        if path.is_empty() {
+            anyhow::bail!("usage: `:load <file>`");
+        }
+        let mut lib_files = self.lib_files.clone();
+        lib_files.push(path.into());
+        let mut sources = SourceMap::new();
+        match Self::load_libraries(&lib_files, &self.config, &mut sources) {
+            Ok(ct) => {
+                println!("loaded {} classes", ct.inner().len());
+                self.lib_files = lib_files;
+                self.ct = ct;
+                self.sources = sources;
+                Ok(())
+            }
+            Err(err) => {
+                // the failing file is only known to the new source map
+                if let Err(err) = diagnostics::emit_error(&sources, &err) {
+                    eprintln!("error: {:#}", err);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn parse_term(&mut self, input: &str) -> anyhow::Result<Term> {
+        if input.is_empty() {
+            anyhow::bail!("expected a term");
+        }
+        let file = self.sources.add("<repl>", input);
+        parser::parse_eval_input(file, self.sources.source(file)).context("parsing failed")
+    }
+
+    fn typecheck(&self, term: &Term) -> anyhow::Result<Type> {
+        let term_type = typecheck_term(&self.ct, &self.config, &Gamma::empty(), term)
+            .context("typechecking failed")?;
+        diagnostics::emit_warnings(&self.sources, &term_type.warnings)?;
+        Ok(term_type.ty)
+    }
+
+    fn show_type(&mut self, input: &str) -> anyhow::Result<()> {
+        let term = self.parse_term(input)?;
+        println!("{} : {}", &term, self.typecheck(&term)?);
+        Ok(())
+    }
+
+    fn eval(&mut self, input: &str) -> anyhow::Result<()> {
+        let term = self.parse_term(input)?;
+        let term_type = self.typecheck(&term)?;
+        let (value, heap) = self
+            .evaluator
+            .eval(&self.ct, term, &self.limits)
+            .context("evaluation failed")?;
+        println!("{} : {}", &value, &term_type);
+        if let Some(heap) = heap {
+            print!("{}", heap);
+        }
+        Ok(())
+    }
+
+    fn trace(&mut self, input: &str) -> anyhow::Result<()> {
+        let term = self.parse_term(input)?;
+        let term_type = self.typecheck(&term)?;
+        let mut steps = 0;
+        println!("   {}", &term);
+        let observer = |step: &Step| {
+            steps += 1;
+            println!("-> {}    [{}]", &step.term, &step.derivation);
+        };
+        let (value, heap) = if self.evaluator == Evaluator::Heap {
+            let (value, heap) = eval_heap_with(&self.ct, term, &self.limits, observer)
+                .context("evaluation failed")?;
+            (value, Some(heap))
+        } else {
+            let value = eval_full_with(&self.ct, term, &self.limits, observer)
+                .context("evaluation failed")?;
+            (value, None)
+        };
+        println!("{} : {} in {} step(s)", &value, &term_type, steps);
+        if let Some(heap) = heap {
+            print!("{}", heap);
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, input: &str) -> anyhow::Result<()> {
+        let (term, mut heap) = if input.is_empty() {
+            self.stepping
+                .take()
+                .context("nothing to step, use `:step <term>`")?
+        } else {
+            let term = self.parse_term(input)?;
+            self.typecheck(&term)?;
+            (term, (self.evaluator == Evaluator::Heap).then(Heap::new))
+        };
+        let is_value = |term: &Term, heap: &Option<Heap>| match heap {
+            Some(_) => term.is_heap_value(),
+            None => term.is_value(),
+        };
+        if is_value(&term, &heap) {
+            println!("{} is a value", &term);
+            return Ok(());
+        }
+        let Step {
+            term: next,
+            derivation,
+        } = match &mut heap {
+            Some(heap) => eval_heap_step_derivation(&self.ct, term, heap),
+            None => eval_step_derivation(&self.ct, term),
+        }
+        .context("evaluation failed")?;
+        println!("-> {}    [{}]", &next, &derivation);
+        if is_value(&next, &heap) {
+            println!("(value reached)");
+            if let Some(heap) = heap {
+                print!("{}", heap);
+            }
+        } else {
+            self.stepping = Some((next, heap));
+        }
+        Ok(())
+    }
+}
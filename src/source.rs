@@ -0,0 +1,103 @@
+use codespan_reporting::files::{Files, SimpleFile};
+use std::ops::Range;
+
+/// Identifies a source file loaded into a [`SourceMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct FileId(pub usize);
+
+/// A byte range `start..end` in the source file `file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        Span { file, start, end }
+    }
+
+    pub fn from_pest(file: FileId, span: pest::Span<'_>) -> Self {
+        Span::new(file, span.start(), span.end())
+    }
+
+    /// Span covering both `self` and `other`, which must be in the same file.
+    pub fn to(self, other: Span) -> Self {
+        debug_assert_eq!(self.file, other.file);
+        Span::new(
+            self.file,
+            self.start.min(other.start),
+            self.end.max(other.end),
+        )
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// All source files known to the program, used to render diagnostics.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SimpleFile<String, String>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        self.files.push(SimpleFile::new(name.into(), source.into()));
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn source(&self, file: FileId) -> &str {
+        self.files[file.0].source()
+    }
+
+    /// The name of `file`, the path it was read from.
+    pub fn name(&self, file: FileId) -> &str {
+        self.files[file.0].name()
+    }
+
+    fn get(
+        &self,
+        file: FileId,
+    ) -> Result<&SimpleFile<String, String>, codespan_reporting::files::Error> {
+        self.files
+            .get(file.0)
+            .ok_or(codespan_reporting::files::Error::FileMissing)
+    }
+}
+
+impl<'a> Files<'a> for SourceMap {
+    type FileId = FileId;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<&'a str, codespan_reporting::files::Error> {
+        Ok(self.get(id)?.name())
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, codespan_reporting::files::Error> {
+        Ok(self.get(id)?.source())
+    }
+
+    fn line_index(
+        &'a self,
+        id: FileId,
+        byte_index: usize,
+    ) -> Result<usize, codespan_reporting::files::Error> {
+        self.get(id)?.line_index((), byte_index)
+    }
+
+    fn line_range(
+        &'a self,
+        id: FileId,
+        line_index: usize,
+    ) -> Result<Range<usize>, codespan_reporting::files::Error> {
+        self.get(id)?.line_range((), line_index)
+    }
+}
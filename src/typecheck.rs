@@ -1,243 +1,1961 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 use crate::class_table::MethodType;
-use crate::error::TypingError;
+use crate::error::{TypingError, TypingWarning};
 use crate::{ast::*, class_table::ClassTable};
 use anyhow::{Context, Result};
+use std::fmt::Display;
 use std::iter;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Options selecting variations of the FJ typing rules.
+#[derive(Debug, Clone, Default)]
+pub struct TypecheckConfig {
+    /// Allow overriding methods to specialize the return type (`C0 <: D0`),
+    /// instead of requiring the exact return type of the overridden method.
+    pub covariant_returns: bool,
+    /// How to treat casts between unrelated classes (rule T-SCast).
+    pub stupid_casts: StupidCasts,
+    /// Reject primitive types, literals, operators, `if`, blocks, field
+    /// assignments, `throw` and `try`. Generics, interfaces, abstract classes
+    /// and lambdas are still accepted.
+    pub pure: bool,
+}
+
+/// Treatment of stupid casts `(C) e` where the type of `e` and `C` are
+/// unrelated, so the cast can never succeed.
+///
+/// FJ accepts them (T-SCast) since they can arise during reduction of
+/// well-typed terms, which is needed for subject reduction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StupidCasts {
+    /// Accept stupid casts silently.
+    Allow,
+    /// Accept stupid casts, but report a [`TypingWarning::StupidCast`].
+    #[default]
+    Warn,
+    /// Reject stupid casts with [`TypingError::StupidCast`].
+    Deny,
+}
+
+impl FromStr for StupidCasts {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "allow" => Ok(StupidCasts::Allow),
+            "warn" => Ok(StupidCasts::Warn),
+            "deny" => Ok(StupidCasts::Deny),
+            _ => anyhow::bail!("expected one of `allow`, `warn` or `deny`, found `{}`", s),
+        }
+    }
+}
+
+/// Substitution `[T̄/X̄]` of types for type variables.
+pub type Subst = BTreeMap<TypeVariable, Type>;
+
+/// The substitution instantiating the type parameters `params` with `args`.
+pub fn subst_of(params: &[TypeParam], args: &[Type]) -> Subst {
+    params
+        .iter()
+        .map(|param| param.name.clone())
+        .zip(args.iter().cloned())
+        .collect()
+}
+
+impl Type {
+    pub fn subst(&self, subst: &Subst) -> Type {
+        match self {
+            Type::Var(var) => subst.get(var).cloned().unwrap_or_else(|| self.clone()),
+            Type::Class(class_type) => class_type.subst(subst).into_type(),
+            Type::Primitive(_) => self.clone(),
+        }
+    }
+
+    /// Whether the type variable `var` occurs in the type.
+    pub fn mentions(&self, var: &TypeVariable) -> bool {
+        match self {
+            Type::Var(v) => v == var,
+            Type::Class(class_type) => class_type.mentions(var),
+            Type::Primitive(_) => false,
+        }
+    }
+}
+
+impl ClassType {
+    pub fn subst(&self, subst: &Subst) -> ClassType {
+        ClassType {
+            name: self.name.clone(),
+            args: self.args.iter().map(|arg| arg.subst(subst)).collect(),
+        }
+    }
+
+    /// Whether the type variable `var` occurs in the type arguments.
+    pub fn mentions(&self, var: &TypeVariable) -> bool {
+        self.args.iter().any(|arg| arg.mentions(var))
+    }
+}
+
+/// The bounds `Δ` of the type variables in scope.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Delta {
+    inner: BTreeMap<TypeVariable, ClassType>,
+}
+
+impl Delta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// `Δ` extended by `params`, which shadow type variables of the same name.
+    pub fn with_type_params(&self, params: &[TypeParam]) -> Self {
+        let mut delta = self.clone();
+        delta.inner.extend(
+            params
+                .iter()
+                .map(|param| (param.name.clone(), param.bound.clone())),
+        );
+        delta
+    }
+    pub fn contains(&self, var: &TypeVariable) -> bool {
+        self.inner.contains_key(var)
+    }
+    /// `bound_Δ(T)`: the bound of a type variable, or the class type itself.
+    /// Primitive types have no bound.
+    pub fn bound(&self, ty: &Type) -> Option<ClassType> {
+        match ty {
+            Type::Var(var) => self.inner.get(var).cloned(),
+            Type::Class(class_type) => Some(class_type.clone()),
+            Type::Primitive(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Gamma {
-    inner: BTreeMap<FieldName, ClassName>,
+    inner: BTreeMap<FieldName, Type>,
+    delta: Delta,
 }
 
 impl Gamma {
     pub fn new() -> Self {
-        Gamma {
-            inner: BTreeMap::new(),
-        }
+        Self::default()
     }
     pub fn empty() -> Self {
         Self::new()
     }
-    pub fn from_class_method(class_name: &ClassName, method: &MethodDefinition) -> Self {
+    pub fn from_class_method(class: &ClassDefinition, method: &MethodDefinition) -> Self {
         Gamma {
-            inner: iter::once((FieldName("this".into()), class_name.clone()))
+            inner: iter::once((FieldName("this".into()), class.this_type().into_type()))
                 .chain(
-                    method.args.iter().map(|(arg_class_name, arg_name)| {
-                        (arg_name.clone(), arg_class_name.clone())
-                    }),
+                    method
+                        .args
+                        .iter()
+                        .map(|(arg_type, arg_name)| (arg_name.clone(), arg_type.clone())),
                 )
                 .collect(),
+            delta: Delta::new()
+                .with_type_params(&class.type_params)
+                .with_type_params(&method.type_params),
+        }
+    }
+    /// `Γ` binding `vars`, later ones shadowing earlier ones of the same name,
+    /// with the bounds `delta` of the type variables in scope.
+    pub fn from_vars(vars: impl IntoIterator<Item = (FieldName, Type)>, delta: Delta) -> Self {
+        Gamma {
+            inner: vars.into_iter().collect(),
+            delta,
+        }
+    }
+    pub fn get(&self, name: &FieldName) -> Option<&Type> {
+        self.inner.get(name)
+    }
+    /// `Γ` extended by the parameters `args` of a lambda, which shadow
+    /// variables of the same name.
+    pub fn with_args(&self, args: &[ArgPair]) -> Self {
+        let mut gamma = self.clone();
+        gamma.inner.extend(
+            args.iter()
+                .map(|(arg_type, arg_name)| (arg_name.clone(), arg_type.clone())),
+        );
+        gamma
+    }
+    pub fn delta(&self) -> &Delta {
+        &self.delta
+    }
+}
+
+fn bound_of(delta: &Delta, ty: &Type, span: Span) -> Result<ClassType> {
+    if let Type::Primitive(_) = ty {
+        Err(TypingError::PrimitiveHasNoMembers(ty.clone(), span))?;
+    }
+    delta
+        .bound(ty)
+        .ok_or_else(|| TypingError::UndefinedClass(ClassName(ty.to_string()), span).into())
+}
+
+/// The supertype `[T̄/X̄]N` of `C<T̄>`, where `class C<X̄ ◁ N̄> ◁ N`.
+/// `None` for `Object` and undefined classes.
+pub fn super_type_of(ct: &ClassTable, class_type: &ClassType) -> Option<ClassType> {
+    let class = ct.inner().get(&class_type.name)?;
+    Some(
+        class
+            .super_type
+            .subst(&subst_of(&class.type_params, &class_type.args)),
+    )
+}
+
+/// The direct supertypes of `C<T̄>`: the superclass followed by the
+/// implemented interfaces, or the extended interfaces of an interface, which
+/// is a direct subtype of `Object` if it does not extend any.
+pub fn direct_super_types_of(ct: &ClassTable, class_type: &ClassType) -> Vec<ClassType> {
+    if let Some(interface) = ct.interfaces().get(&class_type.name) {
+        if interface.super_types.is_empty() {
+            return vec![ClassType::object()];
+        }
+        let subst = subst_of(&interface.type_params, &class_type.args);
+        return interface
+            .super_types
+            .iter()
+            .map(|super_type| super_type.subst(&subst))
+            .collect();
+    }
+    match ct.inner().get(&class_type.name) {
+        Some(class) => {
+            let subst = subst_of(&class.type_params, &class_type.args);
+            iter::once(&class.super_type)
+                .chain(class.interfaces.iter())
+                .map(|super_type| super_type.subst(&subst))
+                .collect()
         }
+        None => vec![],
     }
 }
 
-pub fn typecheck_term(ct: &ClassTable, gamma: &Gamma, term: &Term) -> Result<ClassName> {
+/// `bound_Δ(T)` followed by all its supertypes, nearest first and `Object` last.
+pub fn super_types_of(ct: &ClassTable, delta: &Delta, ty: &Type) -> Vec<ClassType> {
+    let mut super_types = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut pending: VecDeque<_> = delta.bound(ty).into_iter().collect();
+    while let Some(class_type) = pending.pop_front() {
+        if class_type.name.is_object() || !seen.insert(class_type.clone()) {
+            continue;
+        }
+        pending.extend(direct_super_types_of(ct, &class_type));
+        super_types.push(class_type);
+    }
+    if !super_types.is_empty() || delta.bound(ty).is_some() {
+        super_types.push(ClassType::object());
+    }
+    super_types
+}
+
+/// `Δ ⊢ S <: T`. Type arguments are invariant, so `C<S̄> <: C<T̄>` only if `S̄ = T̄`.
+///
+/// The class table tells whether the classes are related at all, so the
+/// supertypes of `S` are only instantiated to compare the type arguments of
+/// a generic `T`.
+pub fn is_subtype(ct: &ClassTable, delta: &Delta, lhs: &Type, rhs: &Type) -> bool {
+    lhs == rhs
+        || match (lhs, rhs) {
+            (Type::Var(_), _) => delta
+                .bound(lhs)
+                .is_some_and(|bound| is_subtype(ct, delta, &bound.into_type(), rhs)),
+            (Type::Class(class_type), Type::Class(rhs_type)) => {
+                ct.is_subtype(&class_type.name, &rhs_type.name) == Some(true)
+                    && (rhs_type.args.is_empty()
+                        || direct_super_types_of(ct, class_type)
+                            .into_iter()
+                            .any(|super_type| is_subtype(ct, delta, &super_type.into_type(), rhs)))
+            }
+            _ => false,
+        }
+}
+
+/// `fields(N)`: the fields of `C<T̄>` with instantiated types, inherited fields first.
+/// Interfaces have no fields. Only the fields of generic hierarchies are
+/// instantiated class by class, the others are those of the class table.
+pub fn fields_of(ct: &ClassTable, class_type: &ClassType) -> Option<Vec<ArgPair>> {
+    if class_type.name.is_object() || ct.is_interface(&class_type.name) {
+        return Some(vec![]);
+    }
+    if !ct.is_generic(&class_type.name) {
+        return Some(ct.fields(&class_type.name)?.cloned().collect());
+    }
+    let class = ct.inner().get(&class_type.name)?;
+    let subst = subst_of(&class.type_params, &class_type.args);
+    let mut fields = fields_of(ct, &class.super_type.subst(&subst))?;
+    fields.extend(
+        class
+            .fields
+            .iter()
+            .map(|(field_type, field_name)| (field_type.subst(&subst), field_name.clone())),
+    );
+    Some(fields)
+}
+
+/// `mtype(m, N)`: the type of method `m` in `C<T̄>` or the closest supertype
+/// declaring it, with the type arguments substituted. Abstract methods have a
+/// type as well. Interfaces search the interfaces they extend in order, and
+/// an abstract class may leave the methods of its interfaces to subclasses.
+///
+/// The class declaring the method is looked up in the class table, unless a
+/// superclass is generic and must be instantiated.
+pub fn method_type_of(
+    ct: &ClassTable,
+    method_name: &MethodName,
+    class_type: &ClassType,
+) -> Option<MethodType> {
+    if let Some(interface) = ct.interfaces().get(&class_type.name) {
+        let subst = subst_of(&interface.type_params, &class_type.args);
+        return match interface
+            .methods
+            .iter()
+            .find(|method| &method.method_name == method_name)
+        {
+            Some(method) => Some(instantiate_method(
+                MethodType::from_signature(method),
+                subst,
+            )),
+            None => interface
+                .super_types
+                .iter()
+                .find_map(|super_type| method_type_of(ct, method_name, &super_type.subst(&subst))),
+        };
+    }
+    if !ct.is_generic(&class_type.name) {
+        if let Some(owner) = ct.method_owner(method_name, &class_type.name) {
+            let method_type = declared_method_type(ct.inner().get(owner)?, method_name)?;
+            return Some(instantiate_method(method_type, Subst::new()));
+        }
+    }
+    let class = ct.inner().get(&class_type.name)?;
+    let subst = subst_of(&class.type_params, &class_type.args);
+    if let Some(method_type) = declared_method_type(class, method_name) {
+        return Some(instantiate_method(method_type, subst));
+    }
+    method_type_of(ct, method_name, &class.super_type.subst(&subst)).or_else(|| {
+        class
+            .interfaces
+            .iter()
+            .find_map(|interface| method_type_of(ct, method_name, &interface.subst(&subst)))
+    })
+}
+
+/// The type of method `method_name` as declared by `class`, abstract or not.
+fn declared_method_type(class: &ClassDefinition, method_name: &MethodName) -> Option<MethodType> {
+    if let Some(method) = class
+        .methods
+        .iter()
+        .find(|method| &method.method_name == method_name)
+    {
+        return Some(MethodType::from_method(method));
+    }
+    class
+        .abstract_methods
+        .iter()
+        .find(|method| &method.method_name == method_name)
+        .map(MethodType::from_signature)
+}
+
+/// The types of all methods declared by the interface `I<T̄>` and the
+/// interfaces it extends, which every class implementing it must define.
+fn interface_method_types(
+    ct: &ClassTable,
+    interface_type: &ClassType,
+) -> Vec<(MethodName, MethodType)> {
+    super_types_of(ct, &Delta::new(), &interface_type.clone().into_type())
+        .into_iter()
+        .filter_map(|super_type| {
+            let interface = ct.interfaces().get(&super_type.name)?;
+            let subst = subst_of(&interface.type_params, &super_type.args);
+            Some(interface.methods.iter().map(move |method| {
+                (
+                    method.method_name.clone(),
+                    instantiate_method(MethodType::from_signature(method), subst.clone()),
+                )
+            }))
+        })
+        .flatten()
+        .collect()
+}
+
+/// The type of `method` under the class substitution `subst`. Method type
+/// parameters are renamed if they would capture a type variable of `subst`.
+fn instantiate_method(method: MethodType, mut subst: Subst) -> MethodType {
+    let names: Vec<TypeVariable> = method
+        .type_params
+        .iter()
+        .map(|param| {
+            let mut name = param.name.clone();
+            // `'` can not occur in source, so the new name is fresh
+            while subst.values().any(|ty| ty.mentions(&name)) {
+                name.0.push('\'');
+            }
+            name
+        })
+        .collect();
+    for (param, name) in method.type_params.iter().zip(&names) {
+        subst.insert(param.name.clone(), Type::Var(name.clone()));
+    }
+    MethodType {
+        type_params: method
+            .type_params
+            .iter()
+            .zip(names)
+            .map(|(param, name)| TypeParam {
+                name,
+                bound: param.bound.subst(&subst),
+            })
+            .collect(),
+        arg_types: method
+            .arg_types
+            .iter()
+            .map(|arg_type| arg_type.subst(&subst))
+            .collect(),
+        ret_type: method.ret_type.subst(&subst),
+    }
+}
+
+/// `Δ ⊢ T ok`: every class in `ty` is defined and instantiated with type
+/// arguments within the bounds of its type parameters.
+pub fn check_type(ct: &ClassTable, delta: &Delta, ty: &Type, span: Span) -> Result<()> {
+    match ty {
+        Type::Var(var) if delta.contains(var) => Ok(()),
+        Type::Var(var) => Err(TypingError::UndefinedClass(ClassName(var.0.clone()), span).into()),
+        Type::Class(class_type) => check_class_type(ct, delta, class_type, span),
+        Type::Primitive(_) => Ok(()),
+    }
+}
+
+/// Like [`check_type`] for the declared type of a field, parameter or
+/// method, which in pure FJ must not be a primitive type.
+fn check_declared_type(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    delta: &Delta,
+    ty: &Type,
+    span: Span,
+) -> Result<()> {
+    if config.pure && matches!(ty, Type::Primitive(_)) {
+        Err(TypingError::NotInPureFj(ty.to_string(), span))?;
+    }
+    check_type(ct, delta, ty, span)
+}
+
+/// Reject the term `what`, which extends FJ, in pure FJ.
+fn check_extension_enabled(config: &TypecheckConfig, what: &dyn Display, span: Span) -> Result<()> {
+    if config.pure {
+        Err(TypingError::NotInPureFj(what.to_string(), span))?;
+    }
+    Ok(())
+}
+
+/// Like [`check_type`] for a class type, which must not name a type variable.
+pub fn check_class_type(
+    ct: &ClassTable,
+    delta: &Delta,
+    class_type: &ClassType,
+    span: Span,
+) -> Result<()> {
+    let var = TypeVariable(class_type.name.0.clone());
+    if delta.contains(&var) {
+        Err(TypingError::TypeVariableAsClass(var, span))?;
+    }
+    let type_params: &[TypeParam] = if class_type.name.is_object() {
+        &[]
+    } else if let Some(interface) = ct.interfaces().get(&class_type.name) {
+        &interface.type_params
+    } else {
+        &ct.inner()
+            .get(&class_type.name)
+            .ok_or_else(|| TypingError::UndefinedClass(class_type.name.clone(), span))?
+            .type_params
+    };
+    if type_params.len() != class_type.args.len() {
+        Err(TypingError::TypeArgumentCount(
+            class_type.name.clone(),
+            type_params.len(),
+            class_type.args.len(),
+            span,
+        ))?;
+    }
+    for arg in &class_type.args {
+        check_type(ct, delta, arg, span)?;
+    }
+    check_bounds(ct, delta, type_params, &class_type.args, span)
+}
+
+/// Check that each of `args` is a subtype of the bound of its parameter in
+/// `params`, with the arguments substituted into the bounds.
+fn check_bounds(
+    ct: &ClassTable,
+    delta: &Delta,
+    params: &[TypeParam],
+    args: &[Type],
+    span: Span,
+) -> Result<()> {
+    let subst = subst_of(params, args);
+    for (param, arg) in params.iter().zip(args) {
+        let bound = param.bound.subst(&subst);
+        if !is_subtype(ct, delta, arg, &bound.clone().into_type()) {
+            Err(TypingError::TypeArgumentNotWithinBound(
+                arg.clone(),
+                param.name.clone(),
+                bound,
+                span,
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that the names of `params` are unique and their bounds are ok in
+/// `delta`, which already contains the parameters.
+fn check_type_params(
+    ct: &ClassTable,
+    delta: &Delta,
+    params: &[TypeParam],
+    span: Span,
+) -> Result<()> {
+    for (i, param) in params.iter().enumerate() {
+        if params[..i].iter().any(|other| other.name == param.name) {
+            Err(TypingError::NonUniqueTypeParameters(
+                param.name.clone(),
+                span,
+            ))?;
+        }
+        check_class_type(ct, delta, &param.bound, span)?;
+    }
+    Ok(())
+}
+
+/// The type of a term, together with the warnings found while typing it.
+#[derive(Debug, Clone)]
+pub struct TermType {
+    pub ty: Type,
+    pub warnings: Vec<TypingWarning>,
+}
+
+pub fn typecheck_term(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    term: &Term,
+) -> Result<TermType> {
+    let mut warnings = Vec::new();
+    let ty = type_of(ct, config, gamma, term, &mut warnings)?;
+    Ok(TermType { ty, warnings })
+}
+
+/// Like [`typecheck_term`] where a term of type `expected` is required, so
+/// `term` may be a lambda, which is typed against `expected`. The caller has
+/// to check that the resulting type is a subtype of `expected`.
+pub fn typecheck_term_against(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    term: &Term,
+    expected: &Type,
+) -> Result<TermType> {
+    let mut warnings = Vec::new();
+    let ty = type_against(ct, config, gamma, term, expected, &mut warnings)?;
+    Ok(TermType { ty, warnings })
+}
+
+fn type_of(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    term: &Term,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<Type> {
+    let delta = &gamma.delta;
     match term {
         //T-Var
-        Term::Variable(x) => gamma
+        Term::Variable(Variable { name, span }) => gamma
             .inner
-            .get(x)
+            .get(name)
             .cloned()
-            .ok_or(TypingError::VariableNotInGamma(x.clone()).into()),
+            .ok_or_else(|| TypingError::VariableNotInGamma(name.clone(), *span).into()),
         // T-Field
-        Term::FieldAccess(FieldAccess { field, object_term }) => {
-            typecheck_term(ct, gamma, object_term).and_then(|object_term_type| {
-                ct.fields(&object_term_type)
-                    .ok_or(TypingError::UndefinedClass(object_term_type.clone()))?
-                    .find(|(_, field_name)| field_name == field)
-                    .map(|(class_name, _)| class_name)
-                    .cloned()
-                    .ok_or(
-                        TypingError::UndefinedField(field.clone(), object_term_type.clone()).into(),
-                    )
-            })
+        Term::FieldAccess(FieldAccess {
+            field,
+            object_term,
+            span,
+        }) => {
+            let object_term_type = type_of(ct, config, gamma, object_term, warnings)?;
+            field_type(ct, delta, &object_term_type, field, *span)
         }
         // T-Invk
-        Term::MethodCall(MethodCall {
-            arg_terms,
-            method_name,
-            object_term,
+        Term::MethodCall(method_call) => {
+            let object_term_type = type_of(ct, config, gamma, &method_call.object_term, warnings)?;
+            let arg_term_types = method_call
+                .arg_terms
+                .iter()
+                .map(|arg_term| arg_type_of(ct, config, gamma, arg_term, warnings))
+                .collect::<Result<Vec<_>>>()?;
+            let method_type =
+                method_call_type(ct, delta, &object_term_type, method_call, &arg_term_types)?;
+            // lambdas are typed against the instantiated parameter types
+            for (arg_term, param_type) in method_call.arg_terms.iter().zip(&method_type.arg_types) {
+                if let Term::Lambda(lambda) = &**arg_term {
+                    check_lambda(ct, config, gamma, lambda, param_type, warnings)?;
+                }
+            }
+            Ok(method_type.ret_type)
+        }
+        Term::Lambda(Lambda { span, .. }) => Err(TypingError::LambdaWithoutTarget(*span))?,
+        // T-Lit
+        Term::Literal(Literal { value, span }) => {
+            check_extension_enabled(config, value, *span)?;
+            Ok(value.primitive_type().into_type())
+        }
+        // T-BinOp
+        Term::BinaryOp(BinaryOp { op, lhs, rhs, span }) => {
+            check_extension_enabled(config, op, *span)?;
+            let lhs_type = type_of(ct, config, gamma, lhs, warnings)?;
+            let rhs_type = type_of(ct, config, gamma, rhs, warnings)?;
+            binary_op_type(*op, &lhs_type, &rhs_type)
+                .map(PrimitiveType::into_type)
+                .ok_or_else(|| {
+                    TypingError::InvalidBinaryOperands(*op, lhs_type, rhs_type, *span).into()
+                })
+        }
+        // T-UnOp
+        Term::UnaryOp(UnaryOp { op, term, span }) => {
+            check_extension_enabled(config, op, *span)?;
+            let term_type = type_of(ct, config, gamma, term, warnings)?;
+            unary_op_type(*op, &term_type)
+                .map(PrimitiveType::into_type)
+                .ok_or_else(|| TypingError::InvalidUnaryOperand(*op, term_type, *span).into())
+        }
+        // T-If
+        Term::If(if_term) => {
+            // a branch throwing an exception takes the type of the other one
+            let (then_type, else_type) = match (&*if_term.then_term, &*if_term.else_term) {
+                (then_term @ Term::Throw(_), else_term) => {
+                    let else_type = type_of(ct, config, gamma, else_term, warnings)?;
+                    let then_type =
+                        type_against(ct, config, gamma, then_term, &else_type, warnings)?;
+                    (then_type, else_type)
+                }
+                (then_term, else_term @ Term::Throw(_)) => {
+                    let then_type = type_of(ct, config, gamma, then_term, warnings)?;
+                    let else_type =
+                        type_against(ct, config, gamma, else_term, &then_type, warnings)?;
+                    (then_type, else_type)
+                }
+                (then_term, else_term) => (
+                    type_of(ct, config, gamma, then_term, warnings)?,
+                    type_of(ct, config, gamma, else_term, warnings)?,
+                ),
+            };
+            if_type(ct, config, gamma, if_term, then_type, else_type, warnings)
+        }
+        // T-Throw, where the exception's own type is used without an expected type
+        Term::Throw(throw) => exception_type(ct, config, gamma, throw, warnings),
+        // T-Try
+        Term::Try(try_term) => try_type(ct, config, gamma, try_term, None, warnings),
+        // T-Assign
+        Term::FieldAssign(field_assign) => {
+            field_assign_type(ct, config, gamma, field_assign, warnings)
+        }
+        // T-Let
+        Term::Let(let_term) => {
+            let body_gamma = let_gamma(ct, config, gamma, let_term, warnings)?;
+            type_of(ct, config, &body_gamma, &let_term.body, warnings)
+        }
+        // T-Seq
+        Term::Seq(Seq {
+            first,
+            second,
+            span,
         }) => {
-            typecheck_term(ct, gamma, object_term).and_then(|object_term_type| {
-                let method_type = ct.method_type(&method_name, &object_term_type).ok_or(
-                    TypingError::UndefinedMethod(method_name.clone(), object_term_type.clone()),
-                )?;
-                let arg_term_types = arg_terms
-                    .iter()
-                    .map(|arg_term| typecheck_term(ct, gamma, arg_term))
-                    .map(|r| r.map_err(From::from))
-                    .collect::<Result<Vec<_>>>()?;
-                // TODO: check matching length of both lists
-                arg_term_types
-                    .iter()
-                    .zip(method_type.arg_types.iter())
-                    .map(|(c, d)| match ct.is_subtype(c, d) {
-                        Some(true) => Ok(()),
-                        Some(false) => Err(TypingError::MethodArgumentNotSubtype(
-                            c.clone(),
-                            d.clone(),
-                            method_name.clone(),
-                            object_term_type.clone(),
-                        )),
-                        None => Err(TypingError::UndefinedClasses(vec![c.clone(), d.clone()])),
-                    })
-                    .map(|r| r.map_err(From::from))
-                    .collect::<Result<()>>()?;
-                Ok(method_type.ret_type)
-            })
+            check_extension_enabled(config, &";", *span)?;
+            type_of(ct, config, gamma, first, warnings)?;
+            type_of(ct, config, gamma, second, warnings)
         }
+        Term::Location(Location { id, span }) => Err(TypingError::HeapLocation(*id, *span))?,
         // T-New
-        Term::NewCall(NewCall {
-            class_name,
-            arg_terms,
-        }) => {
-            let fields = ct
-                .fields(class_name)
-                .ok_or(TypingError::UndefinedClass(class_name.clone()))?;
+        Term::NewCall(new_call) => {
+            let NewCall {
+                class_name,
+                arg_terms,
+                span,
+                ..
+            } = new_call;
+            let class_type = new_call.class_type();
+            check_class_type(ct, delta, &class_type, *span)?;
+            if ct.is_interface(class_name) {
+                Err(TypingError::InterfaceInstantiated(
+                    class_name.clone(),
+                    *span,
+                ))?;
+            }
+            if ct.is_abstract(class_name) {
+                Err(TypingError::AbstractClassInstantiated(
+                    class_name.clone(),
+                    *span,
+                ))?;
+            }
+            let fields = fields_of(ct, &class_type)
+                .ok_or_else(|| TypingError::UndefinedClass(class_name.clone(), *span))?;
+            if arg_terms.len() != fields.len() {
+                Err(TypingError::ConstructorArgumentCount(
+                    class_name.clone(),
+                    fields.len(),
+                    arg_terms.len(),
+                    *span,
+                ))?;
+            }
             let arg_term_types = arg_terms
                 .iter()
-                .map(|arg_term| typecheck_term(ct, gamma, arg_term))
+                .zip(&fields)
+                .map(|(arg_term, (field_type, _))| {
+                    type_against(ct, config, gamma, arg_term, field_type, warnings)
+                })
                 .collect::<Result<Vec<_>>>()?;
-            // TODO: check matching length of both lists
             arg_term_types
                 .iter()
-                .zip(fields.map(|(field_type, _)| field_type))
-                .map(|(c, d)| match ct.is_subtype(c, d) {
-                    Some(true) => Ok(()),
-                    Some(false) => Err(TypingError::ConstructorArgumentNotSubtype(
-                        c.clone(),
-                        d.clone(),
-                        class_name.clone(),
-                    )),
-                    None => Err(TypingError::UndefinedClasses(vec![c.clone(), d.clone()])),
-                })
-                .map(|r| r.map_err(From::from))
-                .collect::<Result<()>>()?;
-            Ok(class_name.clone())
+                .zip(arg_terms.iter())
+                .zip(fields.iter().map(|(field_type, _)| field_type))
+                .try_for_each(|((c, arg_term), d)| -> Result<()> {
+                    if is_subtype(ct, delta, c, d) {
+                        Ok(())
+                    } else {
+                        Err(TypingError::ConstructorArgumentNotSubtype(
+                            c.clone(),
+                            d.clone(),
+                            class_name.clone(),
+                            arg_term.span(),
+                        )
+                        .into())
+                    }
+                })?;
+            Ok(class_type.into_type())
         }
-        Term::Cast(Cast {
-            to_class_name,
-            term,
-        }) => {
-            let term_type = typecheck_term(ct, gamma, term)?;
-            if !ct.contains_class(to_class_name) {
-                Err(TypingError::UndefinedClass(to_class_name.clone()))?;
-            }
-            if !ct.contains_class(&term_type) {
-                Err(TypingError::UndefinedClass(term_type.clone()))?;
+        Term::Cast(cast) => {
+            let Cast { term, span, .. } = cast;
+            let to_class_type = cast.to_type();
+            // a lambda takes the target type of the cast
+            if let Term::Lambda(lambda) = &**term {
+                check_class_type(ct, delta, &to_class_type, *span)?;
+                let to = to_class_type.into_type();
+                check_lambda(ct, config, gamma, lambda, &to, warnings)?;
+                return Ok(to);
             }
+            check_class_type(ct, delta, &to_class_type, *span)?;
+            // the branches of an `if` may be lambdas as well
+            let term_type = type_against(
+                ct,
+                config,
+                gamma,
+                term,
+                &to_class_type.clone().into_type(),
+                warnings,
+            )?;
+            let from = bound_of(delta, &term_type, *span)?;
+            let to = to_class_type.clone().into_type();
+            let is_subclass = |lhs, rhs| ct.is_subtype(lhs, rhs).unwrap_or(false);
             // T-UpCast
-            // dbg!(&to_class_name);
-            // dbg!(&term_type);
-
-            if ct.is_subtype(&term_type, to_class_name).unwrap() {
-                return Ok(to_class_name.clone());
+            if is_subtype(ct, delta, &from.clone().into_type(), &to) {
+                Ok(to)
             }
             // T-DownCast
-            else if ct.is_subtype(to_class_name, &term_type).unwrap()
-                && to_class_name != &term_type
+            else if is_subclass(&to_class_type.name, &from.name)
+                && to_class_type.name != from.name
+                && is_subtype(ct, delta, &to, &from.clone().into_type())
             {
-                return Ok(to_class_name.clone());
+                if !to_class_type.args.is_empty()
+                    && !is_determined_downcast(ct, &to_class_type, &from.name)
+                {
+                    warnings.push(TypingWarning::UncheckedCast {
+                        from: term_type,
+                        to: to_class_type,
+                        span: *span,
+                    });
+                }
+                Ok(to)
             }
-            // T-SenselessCast
-            else if !ct.is_subtype(&term_type, to_class_name).unwrap()
-                && !ct.is_subtype(to_class_name, &term_type).unwrap()
+            // a class not implementing an interface may still have a subclass that does
+            else if !is_subclass(&from.name, &to_class_type.name)
+                && !is_subclass(&to_class_type.name, &from.name)
+                && (ct.is_interface(&from.name) || ct.is_interface(&to_class_type.name))
             {
-                // FIXME: should this be an actual error?
-                println!(
-                    "SENSELESS CAST WARNING: Term of type `{}` can not be cast to type `{}`",
-                    &term_type, &to_class_name
-                );
-                return Ok(to_class_name.clone());
+                if !to_class_type.args.is_empty() {
+                    warnings.push(TypingWarning::UncheckedCast {
+                        from: term_type,
+                        to: to_class_type,
+                        span: *span,
+                    });
+                }
+                Ok(to)
+            }
+            // T-SCast
+            else if !is_subclass(&from.name, &to_class_type.name)
+                && !is_subclass(&to_class_type.name, &from.name)
+            {
+                match config.stupid_casts {
+                    StupidCasts::Allow => {}
+                    StupidCasts::Warn => warnings.push(TypingWarning::StupidCast {
+                        from: term_type.clone(),
+                        to: to.clone(),
+                        span: *span,
+                    }),
+                    StupidCasts::Deny => Err(TypingError::StupidCast {
+                        from: term_type.clone(),
+                        to: to.clone(),
+                        span: *span,
+                    })?,
+                }
+                Ok(to)
             }
-            // cast fallthrough should not happen
+            // related classes, but the type arguments do not fit
             else {
-                Err(TypingError::InvalidCast {
-                    from: term_type.clone(),
-                    to: to_class_name.clone(),
+                Err(TypingError::IncompatibleCast {
+                    from: term_type,
+                    to,
+                    span: *span,
                 })?
             }
         }
     }
 }
 
+/// The type of `lhs op rhs` for operands of types `lhs` and `rhs`, if the
+/// operator applies to them: arithmetic and comparisons on `int`, `&&` and
+/// `||` on `boolean`, `==` and `!=` on two values of the same primitive type,
+/// and `+` concatenating a `String` with a value of any primitive type.
+pub fn binary_op_type(op: BinaryOperator, lhs: &Type, rhs: &Type) -> Option<PrimitiveType> {
+    use BinaryOperator::*;
+    use PrimitiveType::{Boolean, Int};
+    let (Type::Primitive(lhs), Type::Primitive(rhs)) = (lhs, rhs) else {
+        return None;
+    };
+    match (op, lhs, rhs) {
+        (Or | And, Boolean, Boolean) => Some(Boolean),
+        (Eq | Ne, lhs, rhs) if lhs == rhs => Some(Boolean),
+        (Lt | Le | Gt | Ge, Int, Int) => Some(Boolean),
+        (Add, PrimitiveType::String, _) | (Add, _, PrimitiveType::String) => {
+            Some(PrimitiveType::String)
+        }
+        (Add | Sub | Mul | Div | Rem, Int, Int) => Some(Int),
+        _ => None,
+    }
+}
+
+/// The type of `op term` for an operand of type `ty`: `!` negates a
+/// `boolean`, `-` an `int`.
+pub fn unary_op_type(op: UnaryOperator, ty: &Type) -> Option<PrimitiveType> {
+    match (op, ty) {
+        (UnaryOperator::Not, Type::Primitive(PrimitiveType::Boolean)) => {
+            Some(PrimitiveType::Boolean)
+        }
+        (UnaryOperator::Neg, Type::Primitive(PrimitiveType::Int)) => Some(PrimitiveType::Int),
+        _ => None,
+    }
+}
+
+/// T-If: the condition of `if_term` must be a `boolean`. Its type is the type
+/// of both branches, if one of them has a primitive type, and otherwise the
+/// least common supertype of the branch types.
+fn if_type(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    if_term: &If,
+    then_type: Type,
+    else_type: Type,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<Type> {
+    let If {
+        condition, span, ..
+    } = if_term;
+    check_extension_enabled(config, &"if", *span)?;
+    let condition_type = type_of(ct, config, gamma, condition, warnings)?;
+    if condition_type != PrimitiveType::Boolean.into_type() {
+        Err(TypingError::ConditionNotBoolean(
+            condition_type,
+            condition.span(),
+        ))?;
+    }
+    if then_type == else_type {
+        Ok(then_type)
+    } else if matches!(then_type, Type::Primitive(_)) || matches!(else_type, Type::Primitive(_)) {
+        Err(TypingError::IfBranchMismatch(then_type, else_type, *span))?
+    } else {
+        Ok(join(ct, &gamma.delta, &then_type, &else_type))
+    }
+}
+
+/// T-Assign: `t0.f = t` has the type of the field `f` of `t0`, which the type
+/// of `t` must be a subtype of.
+fn field_assign_type(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    field_assign: &FieldAssign,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<Type> {
+    let FieldAssign {
+        object_term,
+        field,
+        value_term,
+        span,
+    } = field_assign;
+    check_extension_enabled(config, &"=", *span)?;
+    let object_term_type = type_of(ct, config, gamma, object_term, warnings)?;
+    let field_type = field_type(ct, &gamma.delta, &object_term_type, field, *span)?;
+    let value_type = type_against(ct, config, gamma, value_term, &field_type, warnings)?;
+    if !is_subtype(ct, &gamma.delta, &value_type, &field_type) {
+        Err(TypingError::AssignmentNotSubtype(
+            value_type,
+            field_type.clone(),
+            field.clone(),
+            value_term.span(),
+        ))?;
+    }
+    Ok(field_type)
+}
+
+/// T-Let: in `{ T x = t; body }` the type of `t` must be a subtype of `T`.
+/// Returns `Γ` extended by `x : T`, the context of `body`.
+fn let_gamma(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    let_term: &Let,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<Gamma> {
+    let Let {
+        ty,
+        name,
+        value_term,
+        span,
+        ..
+    } = let_term;
+    check_extension_enabled(config, &format_args!("{} {}", ty, name), *span)?;
+    if name.is_this() {
+        Err(TypingError::InvalidLocalVariable(*span))?;
+    }
+    check_declared_type(ct, config, &gamma.delta, ty, *span)?;
+    let value_type = type_against(ct, config, gamma, value_term, ty, warnings)?;
+    if !is_subtype(ct, &gamma.delta, &value_type, ty) {
+        Err(TypingError::LocalVariableNotSubtype(
+            value_type,
+            ty.clone(),
+            name.clone(),
+            value_term.span(),
+        ))?;
+    }
+    Ok(gamma.with_args(&[(ty.clone(), name.clone())]))
+}
+
+/// The type of the exception `t` of `throw t`, which must be a subclass of
+/// `Throwable`. As `throw t` does not evaluate to a value, it can have any
+/// type.
+fn exception_type(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    throw: &Throw,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<Type> {
+    let Throw { term, span } = throw;
+    check_extension_enabled(config, &"throw", *span)?;
+    let ty = type_of(ct, config, gamma, term, warnings)?;
+    let throwable = ClassType::new(ClassName::throwable()).into_type();
+    if !is_subtype(ct, &gamma.delta, &ty, &throwable) {
+        Err(TypingError::NotThrowable(ty.clone(), term.span()))?
+    }
+    Ok(ty)
+}
+
+/// T-Try: the class of every `catch` clause must be a subclass of
+/// `Throwable`, its variable is bound in its block. Like the branches of an
+/// `if`, the blocks must have the same type if one of them is primitive, and
+/// otherwise the type of the `try` is their least common supertype, where
+/// blocks throwing an exception are left out. With `expected`, all blocks are
+/// typed against it.
+fn try_type(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    try_term: &Try,
+    expected: Option<&Type>,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<Type> {
+    let Try {
+        body,
+        catches,
+        span,
+    } = try_term;
+    check_extension_enabled(config, &"try", *span)?;
+    let mut block_type = |gamma: &Gamma, term: &Term| match expected {
+        Some(expected) => type_against(ct, config, gamma, term, expected, warnings),
+        None => type_of(ct, config, gamma, term, warnings),
+    };
+    let body_type = block_type(gamma, body)?;
+    let mut ty = (!matches!(**body, Term::Throw(_))).then(|| body_type.clone());
+    for Catch {
+        class_name,
+        name,
+        body,
+        span,
+    } in catches
+    {
+        let class_type = ClassType::new(class_name.clone());
+        check_class_type(ct, &gamma.delta, &class_type, *span)?;
+        let class_type = class_type.into_type();
+        if !is_subtype(
+            ct,
+            &gamma.delta,
+            &class_type,
+            &ClassType::new(ClassName::throwable()).into_type(),
+        ) {
+            Err(TypingError::NotThrowable(class_type.clone(), *span))?;
+        }
+        if name.is_this() {
+            Err(TypingError::InvalidLocalVariable(*span))?;
+        }
+        let catch_type = block_type(&gamma.with_args(&[(class_type, name.clone())]), body)?;
+        ty = match ty {
+            _ if matches!(**body, Term::Throw(_)) => ty,
+            None => Some(catch_type),
+            Some(ty) if ty == catch_type => Some(ty),
+            Some(ty)
+                if matches!(ty, Type::Primitive(_)) || matches!(catch_type, Type::Primitive(_)) =>
+            {
+                Err(TypingError::CatchBranchMismatch(ty, catch_type, *span))?
+            }
+            Some(ty) => Some(join(ct, &gamma.delta, &ty, &catch_type)),
+        };
+    }
+    Ok(ty.unwrap_or(body_type))
+}
+
+/// FGJ's `dcast(C, D)`: a downcast from `D<Ū>` to `C<T̄>` is fully checked by
+/// the class `C` at runtime, as every type on some path from `C` up to `D`
+/// passes all its type parameters on to its supertype.
+fn is_determined_downcast(ct: &ClassTable, class_type: &ClassType, to_super: &ClassName) -> bool {
+    if &class_type.name == to_super {
+        return true;
+    }
+    let type_params = match ct.interfaces().get(&class_type.name) {
+        Some(interface) => &interface.type_params,
+        None => match ct.inner().get(&class_type.name) {
+            Some(class) => &class.type_params,
+            None => return false,
+        },
+    };
+    let this_type = ClassType {
+        name: class_type.name.clone(),
+        args: type_params
+            .iter()
+            .map(|param| Type::Var(param.name.clone()))
+            .collect(),
+    };
+    direct_super_types_of(ct, &this_type)
+        .into_iter()
+        .any(|super_type| {
+            type_params
+                .iter()
+                .all(|param| super_type.mentions(&param.name))
+                && is_determined_downcast(ct, &super_type, to_super)
+        })
+}
+
+/// The type of field `field` of a term of type `object_type` (T-Field).
+pub fn field_type(
+    ct: &ClassTable,
+    delta: &Delta,
+    object_type: &Type,
+    field: &FieldName,
+    span: Span,
+) -> Result<Type> {
+    let class_type = bound_of(delta, object_type, span)?;
+    fields_of(ct, &class_type)
+        .ok_or_else(|| TypingError::UndefinedClass(class_type.name.clone(), span))?
+        .into_iter()
+        .find(|(_, field_name)| field_name == field)
+        .map(|(field_type, _)| field_type)
+        .ok_or_else(|| {
+            TypingError::UndefinedField(field.clone(), class_type.name.clone(), span).into()
+        })
+}
+
+/// The type of a method argument, for inferring type arguments. A lambda has
+/// no type of its own, so it is represented by the types of its parameters
+/// and of its body, if the body is not a lambda itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgType {
+    Term(Type),
+    Lambda {
+        arg_types: Vec<Type>,
+        body_type: Option<Type>,
+    },
+}
+
+/// The [`ArgType`]s of the arguments `arg_terms` of a method call, as
+/// expected by [`method_call_type`]. Warnings are not reported.
+pub fn arg_types(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    arg_terms: &[Box<Term>],
+) -> Result<Vec<ArgType>> {
+    arg_terms
+        .iter()
+        .map(|arg_term| arg_type_of(ct, config, gamma, arg_term, &mut Vec::new()))
+        .collect()
+}
+
+/// The [`ArgType`] of the argument `term`.
+fn arg_type_of(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    term: &Term,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<ArgType> {
+    match term {
+        Term::Lambda(Lambda { args, body, .. }) => Ok(ArgType::Lambda {
+            arg_types: args.iter().map(|(arg_type, _)| arg_type.clone()).collect(),
+            body_type: match &**body {
+                Term::Lambda(_) => None,
+                body => Some(type_of(ct, config, &gamma.with_args(args), body, warnings)?),
+            },
+        }),
+        term => Ok(ArgType::Term(type_of(ct, config, gamma, term, warnings)?)),
+    }
+}
+
+/// The type of `term` where a term of type `expected` is required. A lambda
+/// is checked against `expected` and has that type, other terms have their
+/// own type, which the caller has to compare with `expected`.
+fn type_against(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    term: &Term,
+    expected: &Type,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<Type> {
+    match term {
+        Term::Lambda(lambda) => {
+            check_lambda(ct, config, gamma, lambda, expected, warnings)?;
+            Ok(expected.clone())
+        }
+        // both branches are typed against `expected`, so they may be lambdas
+        Term::If(if_term) => {
+            let then_type =
+                type_against(ct, config, gamma, &if_term.then_term, expected, warnings)?;
+            let else_type =
+                type_against(ct, config, gamma, &if_term.else_term, expected, warnings)?;
+            if_type(ct, config, gamma, if_term, then_type, else_type, warnings)
+        }
+        // the result of a block is typed against `expected` as well
+        Term::Let(let_term) => {
+            let body_gamma = let_gamma(ct, config, gamma, let_term, warnings)?;
+            type_against(ct, config, &body_gamma, &let_term.body, expected, warnings)
+        }
+        Term::Seq(Seq {
+            first,
+            second,
+            span,
+        }) => {
+            check_extension_enabled(config, &";", *span)?;
+            type_of(ct, config, gamma, first, warnings)?;
+            type_against(ct, config, gamma, second, expected, warnings)
+        }
+        // `throw t` has the type expected of it
+        Term::Throw(throw) => {
+            exception_type(ct, config, gamma, throw, warnings)?;
+            Ok(expected.clone())
+        }
+        Term::Try(try_term) => try_type(ct, config, gamma, try_term, Some(expected), warnings),
+        term => type_of(ct, config, gamma, term, warnings),
+    }
+}
+
+/// The single method of the functional interface `I<T̄>`, with the type
+/// arguments substituted. `None` if `I` is not an interface, or declares or
+/// inherits more than one method, or its method is generic.
+pub fn functional_method(
+    ct: &ClassTable,
+    class_type: &ClassType,
+) -> Option<(MethodName, MethodType)> {
+    if !ct.is_interface(&class_type.name) {
+        return None;
+    }
+    let mut methods = interface_method_types(ct, class_type);
+    let (method_name, method_type) = methods.first()?.clone();
+    methods.retain(|(name, _)| name != &method_name);
+    (methods.is_empty() && method_type.type_params.is_empty()).then_some((method_name, method_type))
+}
+
+/// T-Lambda: `(T̄ x̄) -> t` has type `I<Ū>` if the method of the functional
+/// interface `I<Ū>` takes arguments of types `T̄` and `t` has a subtype of its
+/// return type, with `x̄` added to `Γ`.
+fn check_lambda(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    gamma: &Gamma,
+    lambda: &Lambda,
+    target: &Type,
+    warnings: &mut Vec<TypingWarning>,
+) -> Result<()> {
+    let Lambda {
+        args, body, span, ..
+    } = lambda;
+    let delta = &gamma.delta;
+    let (_, method_type) = match target {
+        Type::Class(class_type) => functional_method(ct, class_type),
+        Type::Var(_) | Type::Primitive(_) => None,
+    }
+    .ok_or_else(|| TypingError::NotAFunctionalInterface(target.clone(), *span))?;
+    for (i, (arg_type, arg_name)) in args.iter().enumerate() {
+        if arg_name.is_this() || args[..i].iter().any(|(_, other)| other == arg_name) {
+            Err(TypingError::InvalidLambdaParameter(arg_name.clone(), *span))?;
+        }
+        check_declared_type(ct, config, delta, arg_type, *span)?;
+    }
+    if args.len() != method_type.arg_types.len() {
+        Err(TypingError::LambdaParameterCount(
+            target.clone(),
+            method_type.arg_types.len(),
+            args.len(),
+            *span,
+        ))?;
+    }
+    for (i, ((arg_type, _), expected)) in args.iter().zip(&method_type.arg_types).enumerate() {
+        if arg_type != expected {
+            Err(TypingError::LambdaParameterMismatch(
+                target.clone(),
+                i,
+                expected.clone(),
+                arg_type.clone(),
+                *span,
+            ))?;
+        }
+    }
+    let gamma = gamma.with_args(args);
+    let body_type = type_against(ct, config, &gamma, body, &method_type.ret_type, warnings)?;
+    if !is_subtype(ct, delta, &body_type, &method_type.ret_type) {
+        Err(TypingError::LambdaBodyNotSubtype(
+            body_type,
+            method_type.ret_type,
+            target.clone(),
+            body.span(),
+        ))?;
+    }
+    Ok(())
+}
+
+/// The type of the method called by `method_call` on a receiver of type
+/// `object_type`, with the type arguments given or inferred from `arg_types`
+/// substituted, so it has no type parameters.
+///
+/// Every argument that is not a lambda is checked against its parameter type.
+/// Lambdas must be checked by the caller, using the returned parameter types.
+pub fn method_call_type(
+    ct: &ClassTable,
+    delta: &Delta,
+    object_type: &Type,
+    method_call: &MethodCall,
+    arg_types: &[ArgType],
+) -> Result<MethodType> {
+    let MethodCall {
+        method_name,
+        type_args,
+        arg_terms,
+        span,
+        ..
+    } = method_call;
+    let class_type = bound_of(delta, object_type, *span)?;
+    let method_type = method_type_of(ct, method_name, &class_type).ok_or_else(|| {
+        TypingError::UndefinedMethod(method_name.clone(), class_type.name.clone(), *span)
+    })?;
+    if arg_types.len() != method_type.arg_types.len() {
+        Err(TypingError::MethodArgumentCount(
+            method_name.clone(),
+            class_type.name.clone(),
+            method_type.arg_types.len(),
+            arg_types.len(),
+            *span,
+        ))?;
+    }
+    let type_args = if type_args.is_empty() && !method_type.type_params.is_empty() {
+        infer_type_args(ct, delta, &method_type, arg_types).ok_or_else(|| {
+            TypingError::CannotInferTypeArguments(
+                method_name.clone(),
+                class_type.name.clone(),
+                *span,
+            )
+        })?
+    } else {
+        if type_args.len() != method_type.type_params.len() {
+            Err(TypingError::MethodTypeArgumentCount(
+                method_name.clone(),
+                class_type.name.clone(),
+                method_type.type_params.len(),
+                type_args.len(),
+                *span,
+            ))?;
+        }
+        for type_arg in type_args {
+            check_type(ct, delta, type_arg, *span)?;
+        }
+        type_args.clone()
+    };
+    check_bounds(ct, delta, &method_type.type_params, &type_args, *span)?;
+    let subst = subst_of(&method_type.type_params, &type_args);
+    for ((arg_type, arg_term), param_type) in
+        arg_types.iter().zip(arg_terms).zip(&method_type.arg_types)
+    {
+        let ArgType::Term(arg_type) = arg_type else {
+            continue;
+        };
+        let param_type = param_type.subst(&subst);
+        if !is_subtype(ct, delta, arg_type, &param_type) {
+            Err(TypingError::MethodArgumentNotSubtype(
+                arg_type.clone(),
+                param_type,
+                method_name.clone(),
+                class_type.name.clone(),
+                arg_term.span(),
+            ))?;
+        }
+    }
+    Ok(MethodType {
+        type_params: vec![],
+        arg_types: method_type
+            .arg_types
+            .iter()
+            .map(|arg_type| arg_type.subst(&subst))
+            .collect(),
+        ret_type: method_type.ret_type.subst(&subst),
+    })
+}
+
+/// Infer the type arguments of a generic method from the argument types.
+///
+/// Each type parameter becomes the least common supertype of the types it is
+/// matched with in the argument types, or its bound if it does not occur in
+/// the parameter types.
+fn infer_type_args(
+    ct: &ClassTable,
+    delta: &Delta,
+    method_type: &MethodType,
+    arg_types: &[ArgType],
+) -> Option<Vec<Type>> {
+    let vars: Vec<_> = method_type
+        .type_params
+        .iter()
+        .map(|param| param.name.clone())
+        .collect();
+    let mut candidates = BTreeMap::new();
+    for (param_type, arg_type) in method_type.arg_types.iter().zip(arg_types) {
+        match arg_type {
+            ArgType::Term(arg_type) => {
+                match_type_args(ct, delta, &vars, param_type, arg_type, &mut candidates)
+            }
+            // match the method of the functional interface with the lambda
+            ArgType::Lambda {
+                arg_types,
+                body_type,
+            } => {
+                let Type::Class(class_type) = param_type else {
+                    continue;
+                };
+                let Some((_, lambda_type)) = functional_method(ct, class_type) else {
+                    continue;
+                };
+                for (param_type, arg_type) in lambda_type.arg_types.iter().zip(arg_types) {
+                    match_type_args(ct, delta, &vars, param_type, arg_type, &mut candidates);
+                }
+                if let Some(body_type) = body_type {
+                    match_type_args(
+                        ct,
+                        delta,
+                        &vars,
+                        &lambda_type.ret_type,
+                        body_type,
+                        &mut candidates,
+                    );
+                }
+            }
+        }
+    }
+    method_type
+        .type_params
+        .iter()
+        .map(|param| match candidates.remove(&param.name) {
+            Some(types) => types
+                .into_iter()
+                .reduce(|lhs, rhs| join(ct, delta, &lhs, &rhs)),
+            None => {
+                let bound = param.bound.clone().into_type();
+                (!vars.iter().any(|var| bound.mentions(var))).then_some(bound)
+            }
+        })
+        .collect()
+}
+
+/// Match the parameter type `param_type` with the argument type `arg_type`,
+/// collecting the types matched by each type variable of `vars`.
+fn match_type_args(
+    ct: &ClassTable,
+    delta: &Delta,
+    vars: &[TypeVariable],
+    param_type: &Type,
+    arg_type: &Type,
+    candidates: &mut BTreeMap<TypeVariable, Vec<Type>>,
+) {
+    match param_type {
+        Type::Var(var) => {
+            if vars.contains(var) {
+                candidates
+                    .entry(var.clone())
+                    .or_default()
+                    .push(arg_type.clone());
+            }
+        }
+        Type::Class(class_type) => {
+            if let Some(super_type) = super_types_of(ct, delta, arg_type)
+                .into_iter()
+                .find(|super_type| super_type.name == class_type.name)
+            {
+                for (param_arg, arg) in class_type.args.iter().zip(&super_type.args) {
+                    match_type_args(ct, delta, vars, param_arg, arg, candidates);
+                }
+            }
+        }
+        Type::Primitive(_) => {}
+    }
+}
+
+/// The least supertype of `lhs` which `rhs` is a subtype of.
+pub fn join(ct: &ClassTable, delta: &Delta, lhs: &Type, rhs: &Type) -> Type {
+    if is_subtype(ct, delta, rhs, lhs) {
+        return lhs.clone();
+    }
+    super_types_of(ct, delta, lhs)
+        .into_iter()
+        .map(ClassType::into_type)
+        .find(|super_type| is_subtype(ct, delta, rhs, super_type))
+        .unwrap_or_else(|| ClassType::object().into_type())
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct MethodOk(/* in */ pub ClassName);
+pub struct MethodOk(/* in */ pub ClassName, pub Vec<TypingWarning>);
 
 pub fn typecheck_method(
     ct: &ClassTable,
+    config: &TypecheckConfig,
     method: &MethodDefinition,
     in_class_name: &ClassName,
 ) -> Result<MethodOk> {
-    let gamma = Gamma::from_class_method(in_class_name, method);
-    let ret_term_type = typecheck_term(ct, &gamma, &method.return_term)
+    let class = ct
+        .inner()
+        .get(in_class_name)
+        .ok_or_else(|| TypingError::UndefinedClass(in_class_name.clone(), method.span))?;
+    let gamma = Gamma::from_class_method(class, method);
+    let delta = gamma.delta();
+    check_type_params(ct, delta, &method.type_params, method.name_span)?;
+    check_declared_type(ct, config, delta, &method.return_type, method.name_span)?;
+    for (arg_type, _) in &method.args {
+        check_declared_type(ct, config, delta, arg_type, method.name_span)?;
+    }
+    let TermType {
+        ty: ret_term_type,
+        warnings,
+    } = typecheck_term_against(ct, config, &gamma, &method.return_term, &method.return_type)
         .context(TypingError::InvalidTerm(*method.return_term.clone()))?;
-    let super_type = ct
-        .super_type(in_class_name)
-        .ok_or(TypingError::UndefinedClass(in_class_name.clone()))?;
     let method_type = MethodType::from_method(method);
-    if !ct
-        .is_correct_method_override(&method.method_name, in_class_name, &method_type)
-        .ok_or(TypingError::UndefinedMethod(
-            method.method_name.clone(),
-            in_class_name.clone(),
-        ))?
-    {
-        Err(TypingError::IncorrectMethodOverride(
-            method.method_name.clone(),
-            in_class_name.clone(),
-        ))?;
+    if let Some(expected_method_type) = method_type_of(ct, &method.method_name, &class.super_type) {
+        if !is_correct_method_override(
+            ct,
+            delta,
+            &method_type,
+            &expected_method_type,
+            config.covariant_returns,
+        ) {
+            Err(TypingError::IncorrectMethodOverride(
+                method.method_name.clone(),
+                in_class_name.clone(),
+                expected_method_type,
+                method_type.clone(),
+                method.name_span,
+            ))?;
+        }
     }
-    if !ct.is_subtype(&ret_term_type, &method_type.ret_type).ok_or(
-        anyhow::Error::from(TypingError::UndefinedClass(ret_term_type.clone())).context(
-            TypingError::UndefinedReturnType(method.method_name.clone(), in_class_name.clone()),
-        ),
-    )? {
+    if !is_subtype(ct, delta, &ret_term_type, &method_type.ret_type) {
         Err(TypingError::ReturnTypeNotSubtype(
             ret_term_type.clone(),
             method_type.ret_type.clone(),
             method.method_name.clone(),
             in_class_name.clone(),
+            method.return_term.span(),
         ))?;
     }
-    Ok(MethodOk(in_class_name.clone()))
+    Ok(MethodOk(in_class_name.clone(), warnings))
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ClassOk;
+/// The FGJ rule `override(m, N, <Ȳ ◁ P̄> T̄ → T0)`: if `m` is defined in the
+/// supertype `N` with type `<Z̄ ◁ Q̄> Ū → U0`, then with `Z̄` renamed to `Ȳ`,
+/// `P̄ = Q̄`, `T̄ = Ū` and `T0 = U0`.
+///
+/// With `covariant_return` the return type may be specialized, i.e. `T0 <: U0`.
+fn is_correct_method_override(
+    ct: &ClassTable,
+    delta: &Delta,
+    method_type: &MethodType,
+    expected_method_type: &MethodType,
+    covariant_return: bool,
+) -> bool {
+    if method_type.type_params.len() != expected_method_type.type_params.len()
+        || method_type.arg_types.len() != expected_method_type.arg_types.len()
+    {
+        return false;
+    }
+    let renaming: Subst = expected_method_type
+        .type_params
+        .iter()
+        .zip(&method_type.type_params)
+        .map(|(expected, param)| (expected.name.clone(), Type::Var(param.name.clone())))
+        .collect();
+    let correct_bounds = expected_method_type
+        .type_params
+        .iter()
+        .zip(&method_type.type_params)
+        .all(|(expected, param)| expected.bound.subst(&renaming) == param.bound);
+    let correct_args = expected_method_type
+        .arg_types
+        .iter()
+        .zip(&method_type.arg_types)
+        .all(|(expected, arg_type)| &expected.subst(&renaming) == arg_type);
+    let expected_ret_type = expected_method_type.ret_type.subst(&renaming);
+    let correct_return = if covariant_return {
+        is_subtype(ct, delta, &method_type.ret_type, &expected_ret_type)
+    } else {
+        method_type.ret_type == expected_ret_type
+    };
+    correct_bounds && correct_args && correct_return
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassOk(pub Vec<TypingWarning>);
 
-pub fn typecheck_class(ct: &ClassTable, class: &ClassDefinition) -> Result<ClassOk> {
+/// All errors and warnings found while checking a class or program.
+#[derive(Debug, Default)]
+pub struct TypecheckReport {
+    pub errors: Vec<anyhow::Error>,
+    pub warnings: Vec<TypingWarning>,
+}
+
+pub fn typecheck_class(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    class: &ClassDefinition,
+) -> Result<ClassOk> {
+    let report = typecheck_class_all(ct, config, class);
+    match report.errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(ClassOk(report.warnings)),
+    }
+}
+
+/// Like [`typecheck_class`], but checks every method of the class and returns
+/// all errors and warnings found.
+pub fn typecheck_class_all(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    class: &ClassDefinition,
+) -> TypecheckReport {
     // TODO: many checks are already done when building the class table. they might be moved here
 
-    let super_type = ct
-        .super_type(&class.name)
-        .ok_or(TypingError::UndefinedClass(class.name.clone()))?;
-    let super_fields = ct.fields(&super_type).unwrap();
+    if !ct.inner().contains_key(&class.name) {
+        return TypecheckReport {
+            errors: vec![TypingError::UndefinedClass(class.name.clone(), class.name_span).into()],
+            warnings: vec![],
+        };
+    }
+    // class C<X̄ ◁ N̄> ◁ N { T̄ f̄; ... }: N̄, N and T̄ must be ok in Δ = X̄ <: N̄
+    let delta = Delta::new().with_type_params(&class.type_params);
+    let mut report = TypecheckReport::default();
+    report.errors.extend(
+        iter::once(check_type_params(
+            ct,
+            &delta,
+            &class.type_params,
+            class.name_span,
+        ))
+        .chain(iter::once(check_class_type(
+            ct,
+            &delta,
+            &class.super_type,
+            class.name_span,
+        )))
+        .chain(
+            class
+                .interfaces
+                .iter()
+                .map(|interface| check_class_type(ct, &delta, interface, class.name_span)),
+        )
+        .chain(class.fields.iter().map(|(field_type, _)| {
+            check_declared_type(ct, config, &delta, field_type, class.name_span)
+        }))
+        .filter_map(Result::err),
+    );
+    let super_fields = fields_of(ct, &class.super_type).unwrap_or_default();
+    report
+        .errors
+        .extend(typecheck_constructor(class, &super_fields));
+    for method in &class.methods {
+        match typecheck_method(ct, config, method, &class.name).context(TypingError::InvalidMethod(
+            method.method_name.clone(),
+            class.name.clone(),
+            method.name_span,
+        )) {
+            Ok(MethodOk(_, warnings)) => report.warnings.extend(warnings),
+            Err(err) => report.errors.push(err),
+        }
+    }
+    for method in &class.abstract_methods {
+        if let Err(err) = typecheck_abstract_method(ct, config, method, class) {
+            report.errors.push(err.context(TypingError::InvalidMethod(
+                method.method_name.clone(),
+                class.name.clone(),
+                method.name_span,
+            )));
+        }
+    }
+    // methods defined for the implemented interfaces must have the declared types
+    let this_type = class.this_type();
+    for interface in &class.interfaces {
+        for (method_name, expected_method_type) in interface_method_types(ct, interface) {
+            let Some(method_type) = method_type_of(ct, &method_name, &this_type) else {
+                continue;
+            };
+            if !is_correct_method_override(
+                ct,
+                &delta,
+                &method_type,
+                &expected_method_type,
+                config.covariant_returns,
+            ) {
+                let span = class
+                    .methods
+                    .iter()
+                    .find(|method| method.method_name == method_name)
+                    .map_or(class.name_span, |method| method.name_span);
+                report.errors.push(
+                    TypingError::IncorrectMethodOverride(
+                        method_name,
+                        class.name.clone(),
+                        expected_method_type,
+                        method_type,
+                        span,
+                    )
+                    .into(),
+                );
+            }
+        }
+    }
+    // a concrete class must have a body for every abstract method it inherits
+    // or declares, and every method of the interfaces it implements. only
+    // classes lacking one look for the supertypes declaring it
+    if !class.is_abstract && ct.has_methods_without_body(&class.name) {
+        let mut missing = BTreeSet::new();
+        for super_type in super_types_of(ct, &delta, &this_type.clone().into_type()) {
+            let method_names: Vec<&MethodName> =
+                if let Some(interface) = ct.interfaces().get(&super_type.name) {
+                    interface.methods.iter().map(|m| &m.method_name).collect()
+                } else if let Some(super_class) = ct.inner().get(&super_type.name) {
+                    super_class
+                        .abstract_methods
+                        .iter()
+                        .map(|m| &m.method_name)
+                        .collect()
+                } else {
+                    vec![]
+                };
+            for method_name in method_names {
+                if ct.method_definition(method_name, &class.name).is_none()
+                    && missing.insert(method_name.clone())
+                {
+                    report.errors.push(
+                        TypingError::MissingMethodBody(
+                            method_name.clone(),
+                            class.name.clone(),
+                            super_type.clone(),
+                            class.name_span,
+                        )
+                        .into(),
+                    );
+                }
+            }
+        }
+    }
+    report
+}
 
-    // TODO: check correct super() call
+/// Check the abstract method `abstract <Ȳ ◁ P̄> T m(T̄ x̄);` of `class`: its
+/// types must be ok, and it must correctly override the method of the
+/// superclass, if there is one.
+fn typecheck_abstract_method(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    method: &MethodSignature,
+    class: &ClassDefinition,
+) -> Result<()> {
+    let delta = Delta::new()
+        .with_type_params(&class.type_params)
+        .with_type_params(&method.type_params);
+    check_type_params(ct, &delta, &method.type_params, method.name_span)?;
+    check_declared_type(ct, config, &delta, &method.return_type, method.name_span)?;
+    for (arg_type, _) in &method.args {
+        check_declared_type(ct, config, &delta, arg_type, method.name_span)?;
+    }
+    let method_type = MethodType::from_signature(method);
+    if let Some(expected_method_type) = method_type_of(ct, &method.method_name, &class.super_type) {
+        if !is_correct_method_override(
+            ct,
+            &delta,
+            &method_type,
+            &expected_method_type,
+            config.covariant_returns,
+        ) {
+            Err(TypingError::IncorrectMethodOverride(
+                method.method_name.clone(),
+                class.name.clone(),
+                expected_method_type,
+                method_type,
+                method.name_span,
+            ))?;
+        }
+    }
+    Ok(())
+}
 
-    let _ = class
-        .methods
-        .iter()
-        .map(|method| {
-            typecheck_method(ct, method, &class.name).context(TypingError::InvalidMethod(
+/// Check the interface `interface I<X̄ ◁ N̄> ◁ Ī { ... }`: its type parameters,
+/// the interfaces it extends and the types of its methods must be ok, and a
+/// method declared again must match the declaration in the extended interfaces.
+pub fn typecheck_interface_all(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    interface: &InterfaceDefinition,
+) -> TypecheckReport {
+    let delta = Delta::new().with_type_params(&interface.type_params);
+    let mut report = TypecheckReport::default();
+    report.errors.extend(
+        iter::once(check_type_params(
+            ct,
+            &delta,
+            &interface.type_params,
+            interface.name_span,
+        ))
+        .chain(
+            interface
+                .super_types
+                .iter()
+                .map(|super_type| check_class_type(ct, &delta, super_type, interface.name_span)),
+        )
+        .filter_map(Result::err),
+    );
+    for method in &interface.methods {
+        let delta = delta.clone().with_type_params(&method.type_params);
+        let method_type = MethodType::from_signature(method);
+        let result = iter::once(check_type_params(
+            ct,
+            &delta,
+            &method.type_params,
+            method.name_span,
+        ))
+        .chain(iter::once(check_declared_type(
+            ct,
+            config,
+            &delta,
+            &method.return_type,
+            method.name_span,
+        )))
+        .chain(method.args.iter().map(|(arg_type, _)| {
+            check_declared_type(ct, config, &delta, arg_type, method.name_span)
+        }))
+        .collect::<Result<()>>()
+        .and_then(|()| {
+            for super_type in &interface.super_types {
+                let Some(expected_method_type) =
+                    method_type_of(ct, &method.method_name, super_type)
+                else {
+                    continue;
+                };
+                if !is_correct_method_override(
+                    ct,
+                    &delta,
+                    &method_type,
+                    &expected_method_type,
+                    config.covariant_returns,
+                ) {
+                    Err(TypingError::IncorrectMethodOverride(
+                        method.method_name.clone(),
+                        interface.name.clone(),
+                        expected_method_type,
+                        method_type.clone(),
+                        method.name_span,
+                    ))?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            report.errors.push(err.context(TypingError::InvalidMethod(
                 method.method_name.clone(),
+                interface.name.clone(),
+                method.name_span,
+            )));
+        }
+    }
+    report
+}
+
+/// Check the constructor `C(D̄ ḡ, C̄ f̄) { super(ḡ); this.f̄ = f̄; }` of `class`
+/// against the fields `D̄ ḡ` of its supertype and its own fields `C̄ f̄`.
+///
+/// The assignments `this.f̄ = f̄` are reported as class table errors, but are
+/// checked here so that they do not keep the other classes from being
+/// typechecked.
+fn typecheck_constructor(class: &ClassDefinition, super_fields: &[ArgPair]) -> Vec<anyhow::Error> {
+    let ctor = &class.constructor;
+    let mut errors: Vec<anyhow::Error> = class
+        .ctor_init_errors()
+        .into_iter()
+        .map(From::from)
+        .collect();
+
+    let expected_args = super_fields.iter().chain(class.fields.iter());
+    if ctor.args.len() != super_fields.len() + class.fields.len() {
+        errors.push(
+            TypingError::ConstructorParameterCount(
                 class.name.clone(),
-            ))
-        })
-        .collect::<Result<Vec<_>>>()?;
+                super_fields.len() + class.fields.len(),
+                ctor.args.len(),
+                ctor.span,
+            )
+            .into(),
+        );
+    } else {
+        errors.extend(
+            ctor.args
+                .iter()
+                .zip(expected_args)
+                .enumerate()
+                .filter(|(_, (arg, expected))| arg != expected)
+                .map(|(i, ((arg_type, arg_name), (field_type, field_name)))| {
+                    TypingError::ConstructorParameterMismatch(
+                        class.name.clone(),
+                        i,
+                        field_type.clone(),
+                        field_name.clone(),
+                        arg_type.clone(),
+                        arg_name.clone(),
+                        ctor.span,
+                    )
+                    .into()
+                }),
+        );
+    }
 
-    Ok(ClassOk)
+    if ctor.super_call.len() != super_fields.len() {
+        errors.push(
+            TypingError::SuperCallArgumentCount(
+                class.name.clone(),
+                super_fields.len(),
+                ctor.super_call.len(),
+                ctor.span,
+            )
+            .into(),
+        );
+    } else {
+        errors.extend(
+            ctor.super_call
+                .iter()
+                .zip(super_fields.iter())
+                .enumerate()
+                .filter(|(_, (arg, (_, field_name)))| arg != &field_name)
+                .map(|(i, (arg, (_, field_name)))| {
+                    TypingError::SuperCallArgumentMismatch(
+                        class.name.clone(),
+                        i,
+                        field_name.clone(),
+                        arg.clone(),
+                        ctor.span,
+                    )
+                    .into()
+                }),
+        );
+    }
+    errors
 }
 
-pub fn typecheck_ast(ct: &ClassTable, ast: &Ast) -> Result<()> {
+pub fn typecheck_ast(
+    ct: &ClassTable,
+    config: &TypecheckConfig,
+    ast: &Ast,
+) -> Result<Vec<TypingWarning>> {
+    for interface in &ast.interface_definitions {
+        if let Some(err) = typecheck_interface_all(ct, config, interface)
+            .errors
+            .into_iter()
+            .next()
+        {
+            Err(err.context(TypingError::InvalidInterface(
+                interface.name.clone(),
+                interface.name_span,
+            )))?;
+        }
+    }
     ast.class_definitions
         .iter()
         .map(|class| {
-            typecheck_class(ct, class).context(TypingError::InvalidClass(class.name.clone()))
+            typecheck_class(ct, config, class)
+                .map(|ClassOk(warnings)| warnings)
+                .context(TypingError::InvalidClass(
+                    class.name.clone(),
+                    class.name_span,
+                ))
         })
-        .map(|r| r.map(|_| ()))
-        .collect()
+        .collect::<Result<Vec<_>>>()
+        .map(|warnings| warnings.into_iter().flatten().collect())
+}
+
+/// Like [`typecheck_ast`], but checks every class and method of the program
+/// and returns all errors and warnings found.
+pub fn typecheck_ast_all(ct: &ClassTable, config: &TypecheckConfig, ast: &Ast) -> TypecheckReport {
+    let mut report = TypecheckReport::default();
+    for interface in &ast.interface_definitions {
+        let interface_report = typecheck_interface_all(ct, config, interface);
+        report
+            .errors
+            .extend(interface_report.errors.into_iter().map(|err| {
+                err.context(TypingError::InvalidInterface(
+                    interface.name.clone(),
+                    interface.name_span,
+                ))
+            }));
+    }
+    for class in &ast.class_definitions {
+        let class_report = typecheck_class_all(ct, config, class);
+        report
+            .errors
+            .extend(class_report.errors.into_iter().map(|err| {
+                err.context(TypingError::InvalidClass(
+                    class.name.clone(),
+                    class.name_span,
+                ))
+            }));
+        report.warnings.extend(class_report.warnings);
+    }
+    report
 }
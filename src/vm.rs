@@ -0,0 +1,400 @@
+//! Stack machine executing the bytecode of [`crate::bytecode`].
+//!
+//! Method calls push a frame instead of recursing, so the depth of calls is
+//! only limited by [`MAX_FRAMES`]. Lambdas are instances of the classes they
+//! are compiled to, primitive values are kept on the stack directly.
+
+use crate::ast::*;
+use crate::bytecode::{ClassId, Function, Instr, Program};
+use crate::class_table::ClassTable;
+use crate::error::EvalError;
+use crate::evaluation::{self, EvalLimits};
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Maximum number of nested method invocations.
+pub const MAX_FRAMES: usize = 1_000_000;
+
+/// An instance of a class, with one value per field in constructor order.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Object {
+    pub class: ClassId,
+    pub fields: Box<[Value]>,
+}
+
+impl Drop for Object {
+    /// Drops the objects only referenced by this one iteratively, as the
+    /// machine can build chains of objects too deep to drop recursively.
+    fn drop(&mut self) {
+        let mut pending = take_objects(&mut self.fields);
+        while let Some(object) = pending.pop() {
+            if let Ok(mut object) = Rc::try_unwrap(object) {
+                pending.extend(take_objects(&mut object.fields));
+            }
+        }
+    }
+}
+
+/// The objects among `fields`, leaving no fields behind.
+fn take_objects(fields: &mut Box<[Value]>) -> Vec<Rc<Object>> {
+    std::mem::take(fields)
+        .into_vec()
+        .into_iter()
+        .filter_map(|field| match field {
+            Value::Object(object) => Some(object),
+            Value::Primitive(_) => None,
+        })
+        .collect()
+}
+
+/// A value on the stack or in a field: a shared object or a primitive value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Object(Rc<Object>),
+    Primitive(Primitive),
+}
+
+impl Value {
+    /// The object, unless this is a primitive value.
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            Value::Object(object) => Some(object),
+            Value::Primitive(_) => None,
+        }
+    }
+
+    pub fn as_primitive(&self) -> Option<&Primitive> {
+        match self {
+            Value::Object(_) => None,
+            Value::Primitive(value) => Some(value),
+        }
+    }
+}
+
+struct Frame<'a> {
+    function: &'a Function,
+    pc: usize,
+    /// Stack index of the first local, `this` for methods.
+    base: usize,
+}
+
+/// A handler installed by `TRY`, with the heights of the frame and value
+/// stacks to restore when it is entered.
+struct Handler {
+    frames: usize,
+    stack: usize,
+    pc: usize,
+}
+
+/// Execute `entry` within `limits`.
+///
+/// Steps are counted as in [`crate::evaluation::eval_full`], one for each
+/// `GETFIELD`, `INVOKEVIRTUAL`, `INVOKEINTERFACE`, `CHECKCAST`, `BINOP`,
+/// `UNOP`, `JUMPIFFALSE`, `STORE`, `POP` and `ENDTRY`, and one for entering a
+/// handler. As the machine does not keep terms, exceeding a limit reports the
+/// original term `term`.
+pub fn run(
+    program: &Program,
+    entry: &Function,
+    term: &Term,
+    limits: &EvalLimits,
+) -> Result<Value, EvalError> {
+    let start = Instant::now();
+    let mut steps = 0;
+    let step = |steps_taken: &mut usize| -> Result<(), EvalError> {
+        if limits
+            .max_steps
+            .is_some_and(|max_steps| *steps_taken >= max_steps)
+        {
+            Err(EvalError::StepLimitExceeded(
+                *steps_taken,
+                term.clone().boxed(),
+            ))?;
+        }
+        if let Some(timeout) = limits.timeout {
+            if start.elapsed() >= timeout {
+                Err(EvalError::TimeLimitExceeded(
+                    timeout,
+                    *steps_taken,
+                    term.clone().boxed(),
+                ))?;
+            }
+        }
+        *steps_taken += 1;
+        Ok(())
+    };
+
+    let mut stack: Vec<Value> = Vec::new();
+    reserve_locals(&mut stack, 0, entry);
+    let mut frames = vec![Frame {
+        function: entry,
+        pc: 0,
+        base: 0,
+    }];
+    let mut handlers: Vec<Handler> = Vec::new();
+    // where the exception on top of the stack in a handler was thrown
+    let mut thrown_at = Span::default();
+    loop {
+        let mut thrown = None;
+        // NOTE(unwrap): the machine stops when the last frame returns
+        let frame = frames.last_mut().unwrap();
+        let instr = frame.function.code[frame.pc];
+        frame.pc += 1;
+        match instr {
+            Instr::Load(i) => stack.push(stack[frame.base + i as usize].clone()),
+            Instr::Store(i) => {
+                step(&mut steps)?;
+                // NOTE(unwrap): the compiler only emits `STORE` after pushing a value
+                stack[frame.base + i as usize] = stack.pop().unwrap();
+            }
+            Instr::Pop => {
+                step(&mut steps)?;
+                stack.pop();
+            }
+            Instr::New { class, argc } => {
+                let fields = stack.split_off(stack.len() - argc as usize);
+                stack.push(Value::Object(Rc::new(Object {
+                    class,
+                    fields: fields.into_boxed_slice(),
+                })));
+            }
+            Instr::GetField(i) => {
+                step(&mut steps)?;
+                // NOTE(unwrap): the compiler only emits `GETFIELD` after pushing an object
+                let object = stack.pop().unwrap();
+                let field = object.as_object().unwrap().fields[i as usize].clone();
+                stack.push(field);
+            }
+            Instr::LoadCaptured(i) => {
+                // NOTE(unwrap): local 0 of the method of a lambda is the lambda
+                let lambda = stack[frame.base].as_object().unwrap();
+                stack.push(lambda.fields[i as usize].clone());
+            }
+            Instr::InvokeVirtual { slot, argc } => {
+                step(&mut steps)?;
+                let base = stack.len() - argc as usize - 1;
+                // NOTE(unwrap): primitive values have no methods
+                let class = program.class(stack[base].as_object().unwrap().class);
+                // NOTE(unwrap): only concrete classes are instantiated, which have no abstract methods
+                let function = program.function(class.vtable[slot as usize].unwrap());
+                if frames.len() >= MAX_FRAMES {
+                    Err(EvalError::CallDepthExceeded(
+                        MAX_FRAMES,
+                        term.clone().boxed(),
+                    ))?;
+                }
+                reserve_locals(&mut stack, base, function);
+                frames.push(Frame {
+                    function,
+                    pc: 0,
+                    base,
+                });
+            }
+            Instr::InvokeInterface { selector, argc } => {
+                step(&mut steps)?;
+                let base = stack.len() - argc as usize - 1;
+                // NOTE(unwrap): primitive values have no methods
+                let class = program.class(stack[base].as_object().unwrap().class);
+                // NOTE(unwrap): a class implementing an interface understands all its methods
+                let slot = class.itable[&selector];
+                let function = program.function(class.vtable[slot as usize].unwrap());
+                if frames.len() >= MAX_FRAMES {
+                    Err(EvalError::CallDepthExceeded(
+                        MAX_FRAMES,
+                        term.clone().boxed(),
+                    ))?;
+                }
+                reserve_locals(&mut stack, base, function);
+                frames.push(Frame {
+                    function,
+                    pc: 0,
+                    base,
+                });
+            }
+            Instr::CheckCast(to) => {
+                step(&mut steps)?;
+                // NOTE(unwrap): the compiler only emits `CHECKCAST` after pushing an object
+                let from = stack.last().unwrap().as_object().unwrap().class;
+                if !program.is_subclass(from, to) {
+                    let class = program.class_ids[&ClassName::class_cast_exception()];
+                    let exception = Value::Object(Rc::new(Object {
+                        class,
+                        fields: Box::new([]),
+                    }));
+                    thrown = Some((exception, frame.function.spans[frame.pc - 1]));
+                }
+            }
+            Instr::Const(i) => stack.push(Value::Primitive(
+                frame.function.constants[i as usize].clone(),
+            )),
+            Instr::BinaryOp(op) => {
+                step(&mut steps)?;
+                // NOTE(unwrap): the compiler only emits `BINOP` after pushing two primitive values
+                let rhs = stack.pop().unwrap();
+                let lhs = stack.pop().unwrap();
+                let value = evaluation::apply_binary(
+                    op,
+                    lhs.as_primitive().unwrap(),
+                    rhs.as_primitive().unwrap(),
+                    frame.function.spans[frame.pc - 1],
+                )?;
+                stack.push(Value::Primitive(value));
+            }
+            Instr::UnaryOp(op) => {
+                step(&mut steps)?;
+                // NOTE(unwrap): the compiler only emits `UNOP` after pushing a primitive value
+                let value = stack.pop().unwrap();
+                let value = evaluation::apply_unary(
+                    op,
+                    value.as_primitive().unwrap(),
+                    frame.function.spans[frame.pc - 1],
+                )?;
+                stack.push(Value::Primitive(value));
+            }
+            Instr::JumpIfFalse(target) => {
+                step(&mut steps)?;
+                // NOTE(unwrap): the compiler only emits `JUMPIFFALSE` after pushing a `boolean`
+                if stack.pop().unwrap() == Value::Primitive(Primitive::Boolean(false)) {
+                    frame.pc = target as usize;
+                }
+            }
+            Instr::Jump(target) => frame.pc = target as usize,
+            Instr::Return => {
+                // NOTE(unwrap): every function leaves its result on the stack
+                let result = stack.pop().unwrap();
+                let frame = frames.pop().unwrap();
+                if frames.is_empty() {
+                    return Ok(result);
+                }
+                stack.truncate(frame.base);
+                stack.push(result);
+            }
+            Instr::Throw => {
+                // NOTE(unwrap): the compiler only emits `THROW` after pushing an exception
+                thrown = Some((stack.pop().unwrap(), frame.function.spans[frame.pc - 1]));
+            }
+            Instr::Rethrow => {
+                // NOTE(unwrap): a handler is entered with the exception on the stack
+                thrown = Some((stack.pop().unwrap(), thrown_at));
+            }
+            Instr::Try(target) => handlers.push(Handler {
+                frames: frames.len(),
+                stack: stack.len(),
+                pc: target as usize,
+            }),
+            Instr::EndTry => {
+                step(&mut steps)?;
+                handlers.pop();
+            }
+            Instr::Catch { class, local, next } => {
+                // NOTE(unwrap): exceptions are objects
+                let exception = stack.last().unwrap().as_object().unwrap().class;
+                if program.is_subclass(exception, class) {
+                    stack[frame.base + local as usize] = stack.pop().unwrap();
+                } else {
+                    frame.pc = next as usize;
+                }
+            }
+        }
+        if let Some((exception, span)) = thrown {
+            let Some(handler) = handlers.pop() else {
+                Err(EvalError::UncaughtException(
+                    program.to_term(&exception).boxed(),
+                    span,
+                ))?
+            };
+            // E-Catch or E-Rethrow
+            step(&mut steps)?;
+            frames.truncate(handler.frames);
+            stack.truncate(handler.stack);
+            stack.push(exception);
+            // NOTE(unwrap): the frame of the `TRY` is still running
+            frames.last_mut().unwrap().pc = handler.pc;
+            thrown_at = span;
+        }
+    }
+}
+
+/// Extend the stack by the slots of the local variables of `function`, whose
+/// frame starts at `base` with `this` and the arguments.
+fn reserve_locals(stack: &mut Vec<Value>, base: usize, function: &Function) {
+    // NOTE: the placeholder is never loaded, the compiler emits a `STORE` first
+    stack.resize(
+        base + function.locals as usize,
+        Value::Primitive(Primitive::Int(0)),
+    );
+}
+
+/// Compile the classes of `ct` and `term` and run it on the machine.
+pub fn eval(ct: &ClassTable, term: &Term, limits: &EvalLimits) -> anyhow::Result<Term> {
+    let mut program = Program::compile(ct)?;
+    let entry = program.compile_term(ct, term)?;
+    let value = run(&program, &entry, term, limits)?;
+    Ok(program.to_term(&value))
+}
+
+/// Part of the conversion of a value to a term in [`Program::to_term`].
+enum ToTerm<'v> {
+    /// Convert the value.
+    Value(&'v Value),
+    /// Build the term of the object from the terms of its fields.
+    Object(&'v Object),
+}
+
+impl Program {
+    /// The value as a term `new C(v̄)`, a lambda, with the variables it
+    /// captures substituted, or a literal.
+    ///
+    /// The fields are converted with an explicit stack, as the machine can
+    /// build chains of objects too deep to convert recursively.
+    pub fn to_term(&self, value: &Value) -> Term {
+        let mut pending = vec![ToTerm::Value(value)];
+        let mut terms = Vec::new();
+        while let Some(next) = pending.pop() {
+            match next {
+                ToTerm::Value(Value::Object(object)) => {
+                    pending.push(ToTerm::Object(object));
+                    pending.extend(object.fields.iter().rev().map(ToTerm::Value));
+                }
+                ToTerm::Value(Value::Primitive(value)) => terms.push(
+                    Literal {
+                        value: value.clone(),
+                        span: Span::default(),
+                    }
+                    .into_term(),
+                ),
+                ToTerm::Object(object) => {
+                    let fields = terms.split_off(terms.len() - object.fields.len());
+                    terms.push(self.object_term(object, fields));
+                }
+            }
+        }
+        // NOTE(unwrap): the value itself is converted last
+        terms.pop().unwrap()
+    }
+
+    /// The term of `object`, given the terms of its fields.
+    fn object_term(&self, object: &Object, fields: Vec<Term>) -> Term {
+        let class = self.class(object.class);
+        match &class.lambda {
+            Some(info) => {
+                let mut body = evaluation::erase((*info.lambda.body).clone());
+                for (name, field) in info.captured.iter().zip(fields) {
+                    body = evaluation::substitute(body, name, field);
+                }
+                Lambda {
+                    body: body.boxed(),
+                    ..info.lambda.clone()
+                }
+                .into_term()
+            }
+            None => NewCall {
+                class_name: class.name.clone(),
+                type_args: vec![],
+                arg_terms: fields.into_iter().map(Term::boxed).collect(),
+                span: Span::default(),
+            }
+            .into_term(),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Helpers shared by the integration tests.
+
+// each test crate uses a different part of the helpers
+#![allow(dead_code)]
+
+use featherweight_java::ast::{Location, NewCall};
+use featherweight_java::evaluation::{eval_full_with, eval_heap_with, EvalLimits, Heap};
+use featherweight_java::{
+    build_class_table, interpreter, parse, parse_eval_input, vm, ClassTable, EvalError, SourceMap,
+    Term, TypecheckConfig,
+};
+
+/// The class table of `classes` and the term `term` against it, without
+/// typechecking the term.
+pub fn load(classes: &str, term: &str) -> (ClassTable, Term) {
+    let mut sources = SourceMap::new();
+    let lib = sources.add("lib.fj", classes);
+    let file = sources.add("term.fje", term);
+    let ast = parse(lib, sources.source(lib)).unwrap();
+    let (ct, _) = build_class_table(&ast, &TypecheckConfig::default()).unwrap();
+    (ct, parse_eval_input(file, sources.source(file)).unwrap())
+}
+
+/// The value `term`, with every location replaced by the object on `heap`.
+pub fn read_back(heap: &Heap, term: Term) -> Term {
+    match term {
+        Term::Location(Location { id, span }) => {
+            let object = heap.get(id).unwrap();
+            NewCall {
+                class_name: object.class_name.clone(),
+                type_args: vec![],
+                arg_terms: object
+                    .fields
+                    .iter()
+                    .map(|field| read_back(heap, (**field).clone()).boxed())
+                    .collect(),
+                span,
+            }
+            .into_term()
+        }
+        term => term,
+    }
+}
+
+/// The result of evaluating `term` within `limits` with each evaluator,
+/// named as by `--evaluator`: small-step, big-step, vm and heap.
+/// The value of the heap evaluator is read back from its heap.
+pub fn eval_all(
+    ct: &ClassTable,
+    term: &Term,
+    limits: &EvalLimits,
+) -> [(&'static str, Result<Term, EvalError>); 4] {
+    [
+        (
+            "small-step",
+            eval_full_with(ct, term.clone(), limits, |_| {}),
+        ),
+        ("big-step", interpreter::eval(ct, term, limits)),
+        (
+            "vm",
+            vm::eval(ct, term, limits).map_err(|error| error.downcast().unwrap()),
+        ),
+        (
+            "heap",
+            eval_heap_with(ct, term.clone(), limits, |_| {})
+                .map(|(value, heap)| read_back(&heap, value)),
+        ),
+    ]
+}
@@ -0,0 +1,645 @@
+//! Errors are reported for the right reason at the right place.
+
+mod common;
+
+use common::{eval_all, load};
+use featherweight_java::ast::{ClassName, Literal, NewCall, Primitive};
+use featherweight_java::error::{ClassTableError, ErrorList, LoadError, TypingError};
+use featherweight_java::evaluation::{eval_full_with, eval_heap_with, EvalLimits};
+use featherweight_java::{
+    build_class_table, interpreter, loader, parse, parse_eval_input, vm, EvalError, FileId,
+    SourceMap, Span, StupidCasts, Term, TypecheckConfig,
+};
+use std::path::PathBuf;
+
+/// Classes for the terms evaluated by the tests of runtime errors.
+const CLASSES: &str = "
+class B extends Object {
+    Object x;
+    B(Object x) { super(); this.x = x; }
+    Object id() { return this; }
+}
+class C extends B {
+    C(Object x) { super(x); }
+}
+class E extends Throwable {
+    E() { super(); }
+}
+";
+
+/// A class all libraries of the tests of typing errors start with.
+const CLASS_A: &str = "
+class A extends Object {
+    A() { super(); }
+    Object m(Object x) { return x; }
+}
+";
+
+/// The text of `source` covered by the span of the error parsing it.
+fn parse_error(source: &str) -> (String, &str) {
+    let error = parse_eval_input(FileId(0), source).unwrap_err();
+    let span = error.span();
+    (
+        error.inner.variant.message().into_owned(),
+        &source[span.start..span.end],
+    )
+}
+
+/// The text of `source` covered by `span`.
+fn text(source: &str, span: Option<Span>) -> &str {
+    &source[span.expect("the error has a span").range()]
+}
+
+/// The error evaluating `term` against [`CLASSES`] with each evaluator.
+fn eval_errors(term: &str) -> [(&'static str, EvalError); 4] {
+    let (ct, term) = load(CLASSES, term);
+    eval_all(&ct, &term, &EvalLimits::default())
+        .map(|(evaluator, result)| (evaluator, result.unwrap_err()))
+}
+
+/// The errors building and typechecking the library [`CLASS_A`] followed
+/// by `classes`, as the typing or class table errors causing them with the
+/// text they cover.
+fn library_errors(classes: &str, config: &TypecheckConfig) -> Vec<(anyhow::Error, String)> {
+    let source = format!("{CLASS_A}{classes}");
+    let ast = parse(FileId(0), &source).unwrap();
+    let error = build_class_table(&ast, config).unwrap_err();
+    let ErrorList(errors) = error.downcast().unwrap();
+    errors
+        .into_iter()
+        .map(|error| {
+            let cause = error.root_cause();
+            let span = match cause.downcast_ref::<TypingError>() {
+                Some(error) => error.span(),
+                None => cause.downcast_ref::<ClassTableError>().unwrap().span(),
+            };
+            let text = text(&source, span).to_string();
+            (error, text)
+        })
+        .collect()
+}
+
+/// The only error of [`library_errors`] with the default configuration.
+fn library_error(classes: &str) -> (anyhow::Error, String) {
+    let mut errors = library_errors(classes, &TypecheckConfig::default());
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    errors.pop().unwrap()
+}
+
+/// The typing error causing `error`.
+fn typing_error(error: &anyhow::Error) -> &TypingError {
+    error.root_cause().downcast_ref().unwrap()
+}
+
+#[test]
+fn int_literal_out_of_range() {
+    for (source, literal) in [
+        ("99999999999999999999", "99999999999999999999"),
+        ("9223372036854775808", "9223372036854775808"),
+        ("-(9223372036854775808)", "9223372036854775808"),
+        ("x.m(-9223372036854775809)", "9223372036854775809"),
+    ] {
+        assert_eq!(
+            parse_error(source),
+            ("integer literal is out of range for `int`".into(), literal)
+        );
+    }
+}
+
+#[test]
+fn int_literal_bounds() {
+    for (source, value) in [
+        ("9223372036854775807", i64::MAX),
+        ("-9223372036854775808", i64::MIN),
+    ] {
+        let term = parse_eval_input(FileId(0), source).unwrap();
+        let Term::Literal(Literal {
+            value: literal,
+            span,
+        }) = term
+        else {
+            panic!("{source} is not a literal");
+        };
+        assert_eq!(literal, Primitive::Int(value));
+        assert_eq!((span.start, span.end), (0, source.len()));
+    }
+}
+
+#[test]
+fn unary_op_span_covers_operand() {
+    for (source, spans) in [
+        ("-(1 + 2)\n", vec!["-(1 + 2)"]),
+        ("- !(x)  ", vec!["- !(x)", "!(x)"]),
+        ("-x.m()", vec!["-x.m()"]),
+    ] {
+        let mut term = parse_eval_input(FileId(0), source).unwrap();
+        for expected in spans {
+            let span = term.span();
+            assert_eq!(&source[span.start..span.end], expected);
+            let Term::UnaryOp(unary_op) = term else {
+                panic!("{expected} is not a unary operation");
+            };
+            term = *unary_op.term;
+        }
+    }
+}
+
+#[test]
+fn span_ends_with_term() {
+    for (source, expected) in [
+        ("x  ", "x"),
+        ("42\n", "42"),
+        ("new A()  ", "new A()"),
+        ("new A().f  // field\n", "new A().f"),
+        ("x.m(y)\n\n", "x.m(y)"),
+        ("(B) x\n\n", "(B) x"),
+        ("(B) new A().f  ", "(B) new A().f"),
+        ("(B) (A) x\n", "(B) (A) x"),
+        ("(Object x) -> x\n\n", "(Object x) -> x"),
+        ("(F) (A x) -> x /* body */ ", "(F) (A x) -> x"),
+        ("1 + 2 * 3  ", "1 + 2 * 3"),
+        ("!x\n", "!x"),
+        ("if (true) 1 else 2  \n", "if (true) 1 else 2"),
+        ("throw new A()  \n", "throw new A()"),
+        ("try {x} catch (A e) {y}  \n", "try {x} catch (A e) {y}"),
+        ("x.f = y\n", "x.f = y"),
+        ("{ A y = x; y.f = x; y }\n", "A y = x; y.f = x; y"),
+    ] {
+        let term = parse_eval_input(FileId(0), source).unwrap();
+        let span = term.span();
+        assert_eq!(&source[span.start..span.end], expected);
+    }
+}
+
+#[test]
+fn nested_spans_end_with_term() {
+    let source = "x.m((A y) -> if (y) y.f  else throw y  , new B(1 + 2  ))";
+    let Term::MethodCall(method_call) = parse_eval_input(FileId(0), source).unwrap() else {
+        panic!("{source} is not a method call");
+    };
+    let spans: Vec<_> = method_call
+        .arg_terms
+        .iter()
+        .map(|term| &source[term.span().range()])
+        .collect();
+    assert_eq!(
+        spans,
+        ["(A y) -> if (y) y.f  else throw y", "new B(1 + 2  )"]
+    );
+    let Term::Lambda(lambda) = &*method_call.arg_terms[0] else {
+        panic!("not a lambda");
+    };
+    let Term::If(if_term) = &*lambda.body else {
+        panic!("not a conditional");
+    };
+    assert_eq!(&source[if_term.then_term.span().range()], "y.f");
+    assert_eq!(&source[if_term.else_term.span().range()], "throw y");
+}
+
+#[test]
+fn argument_count_at_runtime() {
+    // the terms are not typechecked, so the evaluators see the wrong counts,
+    // except for the vm, which typechecks the term while compiling it
+    let limits = EvalLimits::default();
+    let eval_errors = |term: &str| {
+        let (ct, term) = load(CLASSES, term);
+        let vm_error = vm::eval(&ct, &term, &limits).unwrap_err();
+        let errors = [
+            eval_full_with(&ct, term.clone(), &limits, |_| {}).unwrap_err(),
+            interpreter::eval(&ct, &term, &limits).unwrap_err(),
+            eval_heap_with(&ct, term, &limits, |_| {}).unwrap_err(),
+        ];
+        (errors, vm_error)
+    };
+
+    let term = "new B(new Object()).id(new Object())";
+    let (errors, vm_error) = eval_errors(term);
+    for error in errors {
+        assert!(
+            matches!(&error, EvalError::MethodArgumentCount(method, _, 0, 1, _) if method.0 == "id"),
+            "{error:?}"
+        );
+        assert_eq!(text(term, error.span()), term);
+    }
+    assert!(
+        matches!(typing_error(&vm_error), TypingError::MethodArgumentCount(method, _, 0, 1, _) if method.0 == "id"),
+        "{vm_error:?}"
+    );
+
+    let term = "new B(new Object(), new Object()).x";
+    let (errors, vm_error) = eval_errors(term);
+    for error in errors {
+        assert!(
+            matches!(&error, EvalError::ConstructorArgumentCount(class, 1, 2, _) if class.0 == "B"),
+            "{error:?}"
+        );
+        assert_eq!(
+            text(term, error.span()),
+            "new B(new Object(), new Object())"
+        );
+    }
+    assert!(
+        matches!(typing_error(&vm_error), TypingError::ConstructorArgumentCount(class, 1, 2, _) if class.0 == "B"),
+        "{vm_error:?}"
+    );
+}
+
+#[test]
+fn argument_count() {
+    let (error, text) = library_error(
+        "class T extends Object { T() { super(); } Object t() { return new A().m(); } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::MethodArgumentCount(method, _, 1, 0, _) if method.0 == "m"),
+        "{error:?}"
+    );
+    assert_eq!(text, "new A().m()");
+    let (error, text) = library_error(
+        "class T extends Object { T() { super(); } Object t() { return new A(new A()); } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::ConstructorArgumentCount(class, 0, 1, _) if class.0 == "A"),
+        "{error:?}"
+    );
+    assert_eq!(text, "new A(new A())");
+}
+
+#[test]
+fn super_call_argument_count() {
+    let (error, text) = library_error(
+        "class P extends Object { Object a; P(Object a) { super(); this.a = a; } }
+         class Q extends P { Object b; Q(Object a, Object b) { super(); this.b = b; } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::SuperCallArgumentCount(class, 1, 0, _) if class.0 == "Q"),
+        "{error:?}"
+    );
+    assert_eq!(text, "Q(Object a, Object b) { super(); this.b = b; }");
+}
+
+#[test]
+fn constructor_assignment_with_typing_errors() {
+    let errors = library_errors(
+        "class P extends Object { Object a; P(Object a) { super(); this.a = b; } }
+         class T extends Object { T() { super(); } Object t() { return new A().m(); } }
+         class U extends Object { U() { super(); } Object u() { return new A(new A()); } }",
+        &TypecheckConfig::default(),
+    );
+    let texts: Vec<_> = errors.iter().map(|(_, text)| text.as_str()).collect();
+    assert_eq!(
+        texts,
+        [
+            "P(Object a) { super(); this.a = b; }",
+            "new A().m()",
+            "new A(new A())"
+        ],
+        "{errors:?}"
+    );
+    assert!(
+        matches!(
+            errors[0].0.root_cause().downcast_ref(),
+            Some(ClassTableError::FieldAssignmentMismatch(class, ..)) if class.0 == "P"
+        ),
+        "{errors:?}"
+    );
+}
+
+#[test]
+fn incorrect_method_override() {
+    let (error, text) =
+        library_error("class Q extends A { Q() { super(); } A m(Object x) { return new A(); } }");
+    assert!(
+        matches!(typing_error(&error), TypingError::IncorrectMethodOverride(method, class, ..) if method.0 == "m" && class.0 == "Q"),
+        "{error:?}"
+    );
+    assert_eq!(text, "m");
+    // the return type may be specialized with covariant returns
+    let config = TypecheckConfig {
+        covariant_returns: true,
+        ..TypecheckConfig::default()
+    };
+    let source = format!(
+        "{CLASS_A}class Q extends A {{ Q() {{ super(); }} A m(Object x) {{ return new A(); }} }}"
+    );
+    let ast = parse(FileId(0), &source).unwrap();
+    assert!(build_class_table(&ast, &config).is_ok());
+}
+
+#[test]
+fn stupid_cast() {
+    let classes = "class T extends Object { T() { super(); } Object t() { return (T) new A(); } }";
+    let config = TypecheckConfig {
+        stupid_casts: StupidCasts::Deny,
+        ..TypecheckConfig::default()
+    };
+    let [(error, cast)] = &library_errors(classes, &config)[..] else {
+        panic!("not a single error");
+    };
+    assert!(
+        matches!(typing_error(error), TypingError::StupidCast { .. }),
+        "{error:?}"
+    );
+    assert_eq!(cast, "(T) new A()");
+    // only a warning by default
+    let source = format!("{CLASS_A}{classes}");
+    let ast = parse(FileId(0), &source).unwrap();
+    let (_, warnings) = build_class_table(&ast, &TypecheckConfig::default()).unwrap();
+    let [warning] = &warnings[..] else {
+        panic!("{warnings:?}");
+    };
+    assert_eq!(text(&source, Some(warning.span())), "(T) new A()");
+}
+
+#[test]
+fn type_argument_count() {
+    let (error, text) = library_error(
+        "class G<X extends Object> extends Object { G() { super(); } }
+         class T extends Object { T() { super(); } Object t() { return new G<A, A>(); } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::TypeArgumentCount(class, 1, 2, _) if class.0 == "G"),
+        "{error:?}"
+    );
+    assert_eq!(text, "new G<A, A>()");
+}
+
+#[test]
+fn interface_errors() {
+    let errors = library_errors(
+        "interface I extends J { }
+         interface J extends I { }
+         class T extends Object implements A { T() { super(); } }",
+        &TypecheckConfig::default(),
+    );
+    let causes: Vec<_> = errors
+        .iter()
+        .map(|(error, text)| (error.root_cause().downcast_ref().unwrap(), text.as_str()))
+        .collect();
+    assert!(
+        matches!(
+            causes[..],
+            [
+                (ClassTableError::ImplementsClass(..), "T"),
+                (ClassTableError::CyclicInterface(..), "I"),
+                (ClassTableError::CyclicInterface(..), "J"),
+            ]
+        ),
+        "{errors:?}"
+    );
+}
+
+#[test]
+fn abstract_class_errors() {
+    let (error, text) = library_error(
+        "abstract class B extends Object { B() { super(); } abstract Object b(); }
+         class C extends B { C() { super(); } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::MissingMethodBody(method, class, super_type, _) if method.0 == "b" && class.0 == "C" && super_type.name.0 == "B"),
+        "{error:?}"
+    );
+    assert_eq!(text, "C");
+    let (error, text) = library_error(
+        "abstract class B extends Object { B() { super(); } }
+         class T extends Object { T() { super(); } Object t() { return new B(); } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::AbstractClassInstantiated(class, _) if class.0 == "B"),
+        "{error:?}"
+    );
+    assert_eq!(text, "new B()");
+}
+
+#[test]
+fn lambda_without_functional_interface() {
+    let (error, text) = library_error(
+        "class T extends Object { T() { super(); } Object t() { return (Object x) -> x; } }",
+    );
+    assert!(
+        matches!(
+            typing_error(&error),
+            TypingError::NotAFunctionalInterface(..)
+        ),
+        "{error:?}"
+    );
+    assert_eq!(text, "(Object x) -> x");
+}
+
+#[test]
+fn division_by_zero() {
+    for term in ["1 / 0", "10 % 0"] {
+        for (evaluator, error) in eval_errors(term) {
+            assert!(
+                matches!(error, EvalError::DivisionByZero(_)),
+                "{evaluator}: {error:?}"
+            );
+            assert_eq!(text(term, error.span()), term, "{evaluator}");
+        }
+    }
+}
+
+#[test]
+fn integer_overflow() {
+    for term in [
+        "9223372036854775807 + 1",
+        "-9223372036854775808 - 1",
+        "4611686018427387904 * 2",
+        "-(-9223372036854775807 - 1)",
+    ] {
+        for (evaluator, error) in eval_errors(term) {
+            assert!(
+                matches!(error, EvalError::IntegerOverflow(_)),
+                "{evaluator}: {error:?}"
+            );
+            assert_eq!(text(term, error.span()), term, "{evaluator}");
+        }
+    }
+}
+
+#[test]
+fn invalid_binary_operands() {
+    let (error, text) =
+        library_error("class T extends Object { T() { super(); } int t() { return 1 + true; } }");
+    assert!(
+        matches!(typing_error(&error), TypingError::InvalidBinaryOperands(..)),
+        "{error:?}"
+    );
+    assert_eq!(text, "1 + true");
+}
+
+#[test]
+fn assignment_without_heap() {
+    let classes = "
+        class Cell extends Object {
+            Object x;
+            Cell(Object x) { super(); this.x = x; }
+            Object set(Object x) { return this.x = x; }
+        }";
+    let (ct, term) = load(
+        classes,
+        "new Cell(new Object()).set(new Cell(new Object()))",
+    );
+    for (evaluator, result) in eval_all(&ct, &term, &EvalLimits::default()) {
+        match evaluator {
+            "heap" => assert!(result.is_ok(), "{evaluator}: {result:?}"),
+            // the vm compiles every method up front, so it rejects the
+            // assignment even if it is never run
+            _ => {
+                let error = result.unwrap_err();
+                assert!(
+                    matches!(error, EvalError::AssignmentWithoutHeap(_)),
+                    "{evaluator}: {error:?}"
+                );
+                assert_eq!(text(classes, error.span()), "this.x = x", "{evaluator}");
+            }
+        }
+    }
+}
+
+#[test]
+fn assignment_not_subtype() {
+    let (error, text) = library_error(
+        "class T extends Object {
+             A a;
+             T(A a) { super(); this.a = a; }
+             Object t() { return this.a = new Object(); }
+         }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::AssignmentNotSubtype(_, _, field, _) if field.0 == "a"),
+        "{error:?}"
+    );
+    assert_eq!(text, "new Object()");
+}
+
+#[test]
+fn uncaught_exception() {
+    let term = "new B(throw new E())";
+    for (evaluator, error) in eval_errors(term) {
+        let EvalError::UncaughtException(exception, span) = &error else {
+            panic!("{evaluator}: {error:?}");
+        };
+        assert!(
+            matches!(&**exception, Term::NewCall(NewCall { class_name, .. }) if class_name.0 == "E"),
+            "{evaluator}: {exception}"
+        );
+        assert_eq!(text(term, Some(*span)), "throw new E()", "{evaluator}");
+    }
+}
+
+#[test]
+fn failed_cast_throws_class_cast_exception() {
+    let term = "new B((C) new B(new Object()))";
+    for (evaluator, error) in eval_errors(term) {
+        let EvalError::UncaughtException(exception, span) = &error else {
+            panic!("{evaluator}: {error:?}");
+        };
+        assert!(
+            matches!(
+                &**exception,
+                Term::NewCall(NewCall { class_name, .. })
+                    if *class_name == ClassName::class_cast_exception()
+            ),
+            "{evaluator}: {exception}"
+        );
+        assert_eq!(
+            text(term, Some(*span)),
+            "(C) new B(new Object())",
+            "{evaluator}"
+        );
+    }
+}
+
+#[test]
+fn not_throwable() {
+    let (error, text) = library_error(
+        "class T extends Object { T() { super(); } Object t() { return throw new A(); } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::NotThrowable(..)),
+        "{error:?}"
+    );
+    assert_eq!(text, "new A()");
+    let (error, text) = library_error(
+        "class T extends Object { T() { super(); } Object t() { return try { new A() } catch (A e) { e }; } }",
+    );
+    assert!(
+        matches!(typing_error(&error), TypingError::NotThrowable(..)),
+        "{error:?}"
+    );
+    assert_eq!(text, "catch (A e) { e }");
+}
+
+/// A fresh directory containing `files`, named by `test`.
+fn files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fj-errors-{}-{test}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, contents) in files {
+        std::fs::write(dir.join(name), contents).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn import_errors() {
+    let dir = files(
+        "imports",
+        &[
+            ("a.fj", "import \"b.fj\";"),
+            ("b.fj", "import \"a.fj\";"),
+            ("c.fj", "import \"missing.fj\";"),
+        ],
+    );
+    let mut sources = SourceMap::new();
+    let error = loader::load_libraries(&mut sources, &[dir.join("a.fj")]).unwrap_err();
+    let Some(LoadError::ImportCycle(path, cycle, span)) = error.downcast_ref() else {
+        panic!("{error:?}");
+    };
+    assert!(path.ends_with("a.fj") && cycle.len() == 3, "{error:?}");
+    assert_eq!(
+        text(sources.source(span.file), Some(*span)),
+        "import \"a.fj\";"
+    );
+
+    let mut sources = SourceMap::new();
+    let error = loader::load_libraries(&mut sources, &[dir.join("c.fj")]).unwrap_err();
+    let Some(LoadError::ImportNotFound(path, _, span)) = error.downcast_ref() else {
+        panic!("{error:?}");
+    };
+    assert!(path.ends_with("missing.fj"), "{error:?}");
+    assert_eq!(
+        text(sources.source(span.file), Some(*span)),
+        "import \"missing.fj\";"
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn class_defined_twice_across_files() {
+    let dir = files(
+        "twice",
+        &[
+            (
+                "a.fj",
+                "import \"b.fj\";\nclass A extends Object { A() { super(); } }",
+            ),
+            ("b.fj", "class A extends Object { A() { super(); } }"),
+        ],
+    );
+    let mut sources = SourceMap::new();
+    let ast = loader::load_libraries(&mut sources, &[dir.join("a.fj")]).unwrap();
+    let error = build_class_table(&ast, &TypecheckConfig::default()).unwrap_err();
+    let ErrorList(errors) = error.downcast().unwrap();
+    let [error] = &errors[..] else {
+        panic!("{errors:?}");
+    };
+    let Some(ClassTableError::ClassDefinedTwice(class, second, first)) = error.downcast_ref()
+    else {
+        panic!("{error:?}");
+    };
+    assert_eq!(class.0, "A");
+    // `b.fj` is loaded first, as `a.fj` imports it
+    assert!(sources.name(first.file).ends_with("b.fj"));
+    assert!(sources.name(second.file).ends_with("a.fj"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
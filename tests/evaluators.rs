@@ -0,0 +1,87 @@
+//! All evaluators agree on the values of the examples.
+
+mod common;
+
+use common::{eval_all, load};
+use featherweight_java::evaluation::EvalLimits;
+use featherweight_java::{
+    build_class_table, loader, parse_eval_input, ClassTable, EvalError, SourceMap, Term,
+    TypecheckConfig,
+};
+use std::mem::Discriminant;
+use std::path::{Path, PathBuf};
+
+/// The value of a term, or the kind of error evaluating it.
+type Outcome = Result<String, Discriminant<EvalError>>;
+
+fn example(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join(name)
+}
+
+/// The outcome of `term` with each evaluator.
+fn outcomes(ct: &ClassTable, term: &Term) -> [Outcome; 4] {
+    eval_all(ct, term, &EvalLimits::default()).map(|(_, result)| {
+        result
+            .map(|value| value.to_string())
+            .map_err(|error| std::mem::discriminant(&error))
+    })
+}
+
+/// Check that all evaluators agree on `term`, returning the outcome.
+fn assert_agree(ct: &ClassTable, term: &Term) -> Outcome {
+    let [small_step, big_step, vm, heap] = outcomes(ct, term);
+    assert_eq!(small_step, big_step, "big-step evaluation of {term}");
+    assert_eq!(small_step, vm, "vm evaluation of {term}");
+    assert_eq!(small_step, heap, "heap evaluation of {term}");
+    small_step
+}
+
+#[test]
+fn libraries() {
+    let examples = [
+        ("boolean.fj", "boolean.fje"),
+        ("function.fj", "function.fje"),
+        ("stdlib.fj", "lambda.fje"),
+        ("list.fj", "list.fje"),
+        ("stdlib.fj", "map.fje"),
+        ("option.fj", "option.fje"),
+        ("stdlib.fj", "peano.fje"),
+        ("test.fj", "test.fje"),
+    ];
+    for (library, term) in examples {
+        let mut sources = SourceMap::new();
+        let ast = loader::load_libraries(&mut sources, &[example(library)]).unwrap();
+        let (ct, _) = build_class_table(&ast, &TypecheckConfig::default()).unwrap();
+        let file = loader::load_file(&mut sources, &example(term)).unwrap();
+        let term = parse_eval_input(file, sources.source(file)).unwrap();
+        assert!(assert_agree(&ct, &term).is_ok(), "{term} fails");
+    }
+}
+
+#[test]
+fn scripts() {
+    // `heap.fj` assigns fields, which only the heap evaluator supports
+    for script in ["script.fj", "primitives.fj", "exceptions.fj"] {
+        let mut sources = SourceMap::new();
+        let script = loader::load_script(&mut sources, &example(script)).unwrap();
+        let (ct, _) = build_class_table(&script.ast, &TypecheckConfig::default()).unwrap();
+        for term in &script.eval_terms {
+            // some eval blocks throw uncaught exceptions on purpose
+            let _ = assert_agree(&ct, term);
+        }
+    }
+}
+
+#[test]
+fn deep_recursion() {
+    let (ct, term) = load(
+        "class A extends Object {
+            A() { super(); }
+            int count(int n, int acc) { return if (n == 0) acc else this.count(n - 1, acc + 1); }
+        }",
+        "new A().count(2000, 0)",
+    );
+    assert_eq!(assert_agree(&ct, &term), Ok("2000".to_string()));
+}
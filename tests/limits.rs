@@ -0,0 +1,194 @@
+//! Evaluation limits are reported as errors instead of crashing the process.
+
+mod common;
+
+use common::{eval_all, load};
+use featherweight_java::ast::{MethodCall, NewCall};
+use featherweight_java::evaluation::{eval_full_with, eval_heap_with, EvalLimits};
+use featherweight_java::{interpreter, parse_eval_input, vm, ClassTable, EvalError, FileId, Term};
+use std::time::Duration;
+
+const CLASSES: &str = "
+class A extends Object {
+    A() { super(); }
+    Object loop() { return this.loop(); }
+}
+class B extends Object {
+    Object x;
+    B(Object x) { super(); this.x = x; }
+}
+class Grow extends Object {
+    Grow() { super(); }
+    Object grow(Object x) {
+        return this.grow(new B(new B(new B(new B(new B(new B(new B(new B(new B(new B(x)))))))))));
+    }
+}
+";
+
+/// The error evaluating `term` within `limits` with each evaluator.
+fn errors(ct: &ClassTable, term: &Term, limits: &EvalLimits) -> [(&'static str, EvalError); 4] {
+    eval_all(ct, term, limits).map(|(evaluator, result)| (evaluator, result.unwrap_err()))
+}
+
+#[test]
+fn step_limit() {
+    let (ct, term) = load(CLASSES, "new A().loop()");
+    let limits = EvalLimits {
+        max_steps: Some(100),
+        detect_cycles: false,
+        ..EvalLimits::default()
+    };
+    for (evaluator, error) in errors(&ct, &term, &limits) {
+        assert!(
+            matches!(error, EvalError::StepLimitExceeded(100, _)),
+            "{evaluator}: {error:?}"
+        );
+    }
+}
+
+#[test]
+fn last_term() {
+    // the method call is the whole term, so the small-step and big-step
+    // evaluators stop at the same term
+    let (ct, term) = load(CLASSES, "new Grow().grow(new Object())");
+    let limits = EvalLimits {
+        max_steps: Some(5),
+        ..EvalLimits::default()
+    };
+    let last_terms = errors(&ct, &term, &limits).map(|(evaluator, error)| {
+        let EvalError::StepLimitExceeded(5, last_term) = error else {
+            panic!("{evaluator}: {error:?}");
+        };
+        last_term.to_string()
+    });
+    let [small_step, big_step, vm, heap] = &last_terms;
+    assert_eq!(
+        nesting(&parse_eval_input(FileId(0), small_step).unwrap()),
+        50
+    );
+    assert_eq!(big_step, small_step);
+    assert_eq!(vm, "new Grow().grow(new Object())");
+    // every object takes a step to allocate on the heap
+    assert!(heap.starts_with("@0.grow("), "{heap}");
+}
+
+#[test]
+fn time_limit() {
+    let (ct, term) = load(CLASSES, "new A().loop()");
+    let limits = EvalLimits {
+        timeout: Some(Duration::ZERO),
+        detect_cycles: false,
+        ..EvalLimits::default()
+    };
+    for (evaluator, error) in errors(&ct, &term, &limits) {
+        assert!(
+            matches!(error, EvalError::TimeLimitExceeded(Duration::ZERO, 0, _)),
+            "{evaluator}: {error:?}"
+        );
+    }
+}
+
+#[test]
+fn cycle_detection() {
+    let (ct, term) = load(CLASSES, "new A().loop()");
+    let limits = EvalLimits::default();
+    let error = eval_full_with(&ct, term.clone(), &limits, |_| {}).unwrap_err();
+    assert!(
+        matches!(&error, EvalError::Diverges(term, 1) if term.to_string() == "new A().loop()"),
+        "{error:?}"
+    );
+    // the first step allocates the object, which is shared afterwards
+    let error = eval_heap_with(&ct, term, &limits, |_| {}).unwrap_err();
+    assert!(
+        matches!(&error, EvalError::Diverges(term, 1) if term.to_string() == "@0.loop()"),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn no_cycle_detection_without_recurring_terms() {
+    // the argument grows, so no term recurs and only the step limit applies
+    let (ct, term) = load(CLASSES, "new Grow().grow(new Object())");
+    let limits = EvalLimits {
+        max_steps: Some(50),
+        ..EvalLimits::default()
+    };
+    for (evaluator, error) in errors(&ct, &term, &limits) {
+        assert!(
+            matches!(error, EvalError::StepLimitExceeded(50, _)),
+            "{evaluator}: {error:?}"
+        );
+    }
+}
+
+/// The number of objects nested in the first argument of `term`.
+fn nesting(mut term: &Term) -> usize {
+    let mut depth = 0;
+    loop {
+        term = match term {
+            Term::MethodCall(MethodCall { arg_terms, .. }) => &arg_terms[0],
+            Term::NewCall(NewCall { arg_terms, .. }) if !arg_terms.is_empty() => {
+                depth += 1;
+                &arg_terms[0]
+            }
+            _ => return depth,
+        }
+    }
+}
+
+#[test]
+fn step_limit_with_deep_terms() {
+    // every reduction step recurses over the term, which is nested more
+    // deeply than the stack of the calling thread allows
+    let (ct, term) = load(CLASSES, "new Grow().grow(new Object())");
+    let limits = EvalLimits {
+        max_steps: Some(300),
+        ..EvalLimits::default()
+    };
+    let error = eval_full_with(&ct, term, &limits, |_| {}).unwrap_err();
+    let EvalError::StepLimitExceeded(300, term) = &error else {
+        panic!("{error:?}");
+    };
+    assert_eq!(nesting(term), 3000);
+}
+
+#[test]
+fn big_step_call_depth() {
+    let (ct, term) = load(CLASSES, "new A().loop()");
+    let result = interpreter::eval(&ct, &term, &EvalLimits::default()).unwrap_err();
+    assert!(
+        matches!(result, EvalError::CallDepthExceeded(_, _)),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn vm_call_depth() {
+    let (ct, term) = load(CLASSES, "new A().loop()");
+    let result = vm::eval(&ct, &term, &EvalLimits::default())
+        .unwrap_err()
+        .downcast()
+        .unwrap();
+    assert!(
+        matches!(result, EvalError::CallDepthExceeded(vm::MAX_FRAMES, _)),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn vm_step_limit_with_deep_objects() {
+    // builds a chain of a million objects before running out of steps
+    let (ct, term) = load(CLASSES, "new Grow().grow(new Object())");
+    let limits = EvalLimits {
+        max_steps: Some(100_000),
+        ..EvalLimits::default()
+    };
+    let result = vm::eval(&ct, &term, &limits)
+        .unwrap_err()
+        .downcast()
+        .unwrap();
+    assert!(
+        matches!(result, EvalError::StepLimitExceeded(100_000, _)),
+        "{result:?}"
+    );
+}
@@ -0,0 +1,386 @@
+//! Printing a syntax tree and parsing the result gives the same tree, at any
+//! line width.
+
+use featherweight_java::ast::{
+    Ast, BinaryOp, BinaryOperator, Cast, Catch, ClassDefinition, ClassName, ClassType, Constructor,
+    FieldAccess, FieldAssign, FieldName, If, Import, InterfaceDefinition, Lambda, Let, Literal,
+    MethodCall, MethodDefinition, MethodName, MethodSignature, NewCall, Primitive, PrimitiveType,
+    Script, Seq, Span, Term, Throw, Try, Type, TypeParam, TypeVariable, UnaryOp, UnaryOperator,
+};
+use featherweight_java::{parse, parse_eval_input, parse_script, pretty, FileId};
+use proptest::prelude::*;
+
+fn name() -> impl Strategy<Value = String> {
+    prop::sample::select(vec!["a", "b", "this", "next_1"]).prop_map(String::from)
+}
+
+fn class_name() -> impl Strategy<Value = ClassName> {
+    prop::sample::select(vec!["A", "B2", "Object"]).prop_map(|name| ClassName(name.into()))
+}
+
+fn class_type() -> impl Strategy<Value = ClassType> {
+    (
+        class_name(),
+        prop::collection::vec(
+            prop_oneof![
+                class_name().prop_map(|name| ClassType::new(name).into_type()),
+                primitive_type(),
+            ],
+            0..3,
+        ),
+    )
+        .prop_map(|(name, args)| ClassType { name, args })
+}
+
+fn primitive_type() -> impl Strategy<Value = Type> {
+    prop::sample::select(vec![
+        PrimitiveType::Int,
+        PrimitiveType::Boolean,
+        PrimitiveType::String,
+    ])
+    .prop_map(PrimitiveType::into_type)
+}
+
+/// Types outside of the scope of any type parameter.
+fn ty() -> impl Strategy<Value = Type> {
+    prop_oneof![class_type().prop_map(Type::Class), primitive_type()]
+}
+
+fn args() -> impl Strategy<Value = Vec<(Type, FieldName)>> {
+    prop::collection::vec((ty(), name().prop_map(FieldName)), 0..3)
+}
+
+fn type_params(names: &'static [&'static str]) -> impl Strategy<Value = Vec<TypeParam>> {
+    prop::collection::vec(
+        (prop::sample::select(names), class_type()).prop_map(|(name, bound)| TypeParam {
+            name: TypeVariable(name.into()),
+            bound,
+        }),
+        0..3,
+    )
+}
+
+fn binary_operator() -> impl Strategy<Value = BinaryOperator> {
+    use BinaryOperator::*;
+    prop::sample::select(vec![
+        Or, And, Eq, Ne, Lt, Le, Gt, Ge, Add, Sub, Mul, Div, Rem,
+    ])
+}
+
+fn literal() -> impl Strategy<Value = Term> {
+    prop_oneof![
+        (0..=i64::MAX).prop_map(Primitive::Int),
+        any::<bool>().prop_map(Primitive::Boolean),
+        "[a-z \"\\\\\n\t]{0,4}".prop_map(Primitive::String),
+    ]
+    .prop_map(|value| {
+        Literal {
+            value,
+            span: Span::default(),
+        }
+        .into_term()
+    })
+}
+
+/// Terms as the parser produces them, so without heap locations, lambda
+/// targets or negative literals.
+fn term() -> impl Strategy<Value = Term> {
+    let leaf = prop_oneof![
+        name().prop_map(|name| Term::from_variable_str(&name, Span::default())),
+        literal(),
+    ];
+    leaf.prop_recursive(5, 48, 3, |inner| {
+        let boxed = inner.clone().prop_map(Term::boxed);
+        let terms = prop::collection::vec(boxed.clone(), 0..3);
+        let span = Span::default();
+        prop_oneof![
+            (boxed.clone(), name()).prop_map(move |(object_term, field)| FieldAccess {
+                object_term,
+                field: FieldName(field),
+                span,
+            }
+            .into_term()),
+            (
+                boxed.clone(),
+                name(),
+                prop::collection::vec(ty(), 0..2),
+                terms.clone()
+            )
+                .prop_map(move |(object_term, method_name, type_args, arg_terms)| {
+                    MethodCall {
+                        object_term,
+                        method_name: MethodName(method_name),
+                        type_args,
+                        arg_terms,
+                        span,
+                    }
+                    .into_term()
+                }),
+            (class_name(), prop::collection::vec(ty(), 0..2), terms).prop_map(
+                move |(class_name, type_args, arg_terms)| NewCall {
+                    class_name,
+                    type_args,
+                    arg_terms,
+                    span,
+                }
+                .into_term()
+            ),
+            (
+                class_name(),
+                prop::collection::vec(ty(), 0..2),
+                boxed.clone()
+            )
+                .prop_map(move |(to_class_name, type_args, term)| Cast {
+                    to_class_name,
+                    type_args,
+                    term,
+                    span,
+                }
+                .into_term()),
+            (args(), boxed.clone()).prop_map(move |(args, body)| Lambda {
+                args,
+                body,
+                target: None,
+                span,
+            }
+            .into_term()),
+            (binary_operator(), boxed.clone(), boxed.clone())
+                .prop_map(move |(op, lhs, rhs)| { BinaryOp { op, lhs, rhs, span }.into_term() }),
+            (
+                prop::sample::select(vec![UnaryOperator::Not, UnaryOperator::Neg]),
+                boxed.clone()
+            )
+                .prop_map(move |(op, term)| UnaryOp { op, term, span }.into_term()),
+            (boxed.clone(), boxed.clone(), boxed.clone()).prop_map(
+                move |(condition, then_term, else_term)| If {
+                    condition,
+                    then_term,
+                    else_term,
+                    span,
+                }
+                .into_term()
+            ),
+            (boxed.clone(), name(), boxed.clone()).prop_map(
+                move |(object_term, field, value_term)| FieldAssign {
+                    object_term,
+                    field: FieldName(field),
+                    value_term,
+                    span,
+                }
+                .into_term()
+            ),
+            (ty(), name(), boxed.clone(), boxed.clone()).prop_map(
+                move |(ty, name, value_term, body)| Let {
+                    ty,
+                    name: FieldName(name),
+                    value_term,
+                    body,
+                    span,
+                }
+                .into_term()
+            ),
+            (boxed.clone(), boxed.clone()).prop_map(move |(first, second)| Seq {
+                first,
+                second,
+                span
+            }
+            .into_term()),
+            boxed
+                .clone()
+                .prop_map(move |term| Throw { term, span }.into_term()),
+            (
+                boxed.clone(),
+                prop::collection::vec((class_name(), name(), boxed), 1..3)
+            )
+                .prop_map(move |(body, catches)| Try {
+                    body,
+                    catches: catches
+                        .into_iter()
+                        .map(|(class_name, name, body)| Catch {
+                            class_name,
+                            name: FieldName(name),
+                            body,
+                            span,
+                        })
+                        .collect(),
+                    span,
+                }
+                .into_term()),
+        ]
+    })
+}
+
+fn signature() -> impl Strategy<Value = MethodSignature> {
+    (type_params(&["Y"]), ty(), name(), args()).prop_map(
+        |(type_params, return_type, method_name, args)| MethodSignature {
+            type_params,
+            return_type,
+            method_name: MethodName(method_name),
+            args,
+            span: Span::default(),
+            name_span: Span::default(),
+        },
+    )
+}
+
+fn method() -> impl Strategy<Value = MethodDefinition> {
+    (signature(), term()).prop_map(|(signature, return_term)| MethodDefinition {
+        type_params: signature.type_params,
+        return_type: signature.return_type,
+        method_name: signature.method_name,
+        args: signature.args,
+        return_term: return_term.boxed(),
+        span: Span::default(),
+        name_span: Span::default(),
+    })
+}
+
+fn constructor() -> impl Strategy<Value = Constructor> {
+    let field = || name().prop_map(FieldName);
+    (
+        class_name(),
+        args(),
+        prop::collection::vec(field(), 0..3),
+        prop::collection::vec((field(), field()), 0..3),
+    )
+        .prop_map(|(name, args, super_call, assignments)| Constructor {
+            name,
+            args,
+            super_call,
+            assignments,
+            span: Span::default(),
+        })
+}
+
+fn class() -> impl Strategy<Value = ClassDefinition> {
+    (
+        any::<bool>(),
+        class_name(),
+        type_params(&["X", "Z"]),
+        class_type(),
+        prop::collection::vec(class_type(), 0..2),
+        args(),
+        constructor(),
+        prop::collection::vec(method(), 0..3),
+        prop::collection::vec(signature(), 0..2),
+    )
+        .prop_map(
+            |(
+                is_abstract,
+                name,
+                type_params,
+                super_type,
+                interfaces,
+                fields,
+                constructor,
+                methods,
+                abstract_methods,
+            )| ClassDefinition {
+                is_abstract,
+                name,
+                type_params,
+                super_type,
+                interfaces,
+                fields,
+                constructor,
+                methods,
+                abstract_methods,
+                span: Span::default(),
+                name_span: Span::default(),
+            },
+        )
+}
+
+fn interface() -> impl Strategy<Value = InterfaceDefinition> {
+    (
+        class_name(),
+        type_params(&["X", "Z"]),
+        prop::collection::vec(class_type(), 0..2),
+        prop::collection::vec(signature(), 0..3),
+    )
+        .prop_map(
+            |(name, type_params, super_types, methods)| InterfaceDefinition {
+                name,
+                type_params,
+                super_types,
+                methods,
+                span: Span::default(),
+                name_span: Span::default(),
+            },
+        )
+}
+
+fn ast() -> impl Strategy<Value = Ast> {
+    (
+        prop::collection::vec("[a-z]{1,4}\\.fj", 0..2),
+        prop::collection::vec(class(), 1..3),
+        prop::collection::vec(interface(), 0..2),
+    )
+        .prop_map(|(imports, class_definitions, interface_definitions)| Ast {
+            imports: imports
+                .into_iter()
+                .map(|path| Import {
+                    path,
+                    span: Span::default(),
+                })
+                .collect(),
+            class_definitions,
+            interface_definitions,
+        })
+}
+
+/// Terms whose printing needs parentheses that precedence alone does not
+/// call for.
+#[test]
+fn ambiguous_terms_round_trip() {
+    let sources = [
+        "(a.f < b) > (A) c",
+        "x.m((a.f < b), c > (A) d)",
+        "x.m((A y) -> (a.f < b), c > (A) d)",
+        "(A) (-x)",
+        "-(-1)",
+        "-9223372036854775808",
+        "-(-9223372036854775808)",
+        "(A) ((B y) -> y).f",
+        "(if (c) 1 else 2) + 3",
+    ];
+    for source in sources {
+        let term = parse_eval_input(FileId(0), source).unwrap();
+        for width in [0, usize::MAX] {
+            let printed = pretty::print_term(&term, width);
+            assert_eq!(
+                parse_eval_input(FileId(0), &printed).ok().as_ref(),
+                Some(&term),
+                "{}",
+                printed
+            );
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn term_round_trips(term in term(), width in 0..120usize) {
+        let printed = pretty::print_term(&term, width);
+        let parsed = parse_eval_input(FileId(0), &printed);
+        prop_assert!(parsed.as_ref().is_ok_and(|parsed| *parsed == term), "{}", printed);
+        prop_assert_eq!(parse_eval_input(FileId(0), &term.to_string())?, term);
+    }
+
+    #[test]
+    fn ast_round_trips(ast in ast()) {
+        prop_assert_eq!(parse(FileId(0), &ast.to_string())?, ast);
+    }
+
+    #[test]
+    fn script_round_trips(
+        ast in ast(),
+        eval_terms in prop::collection::vec(term(), 0..3),
+        width in 0..120usize,
+    ) {
+        let script = Script { ast, eval_terms };
+        let printed = pretty::print_script(&script, width);
+        let parsed = parse_script(FileId(0), &printed);
+        prop_assert!(parsed.as_ref().is_ok_and(|parsed| *parsed == script), "{}", printed);
+    }
+}